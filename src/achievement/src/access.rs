@@ -1,9 +1,11 @@
-//! This module provides access control functions for checking if the caller is a controller
+//! This module provides access control functions for checking if the caller is a controller or an admin
 //! and for retrieving the caller's principal ID.
 
 use ic_cdk::query;
 use candid::Principal;
 
+use crate::state::is_admin_principal;
+
 /// Checks if the caller is a controller.
 ///
 /// This function retrieves the caller's ID and checks if the caller is a controller.
@@ -13,10 +15,19 @@ use candid::Principal;
 /// * `bool` - `true` if the caller is a controller, `false` otherwise.
 #[query(name = "isController")]
 pub fn is_controller() -> bool {
-    let id = ic_cdk::api::caller();
-    let is_controller = ic_cdk::api::is_controller(&id);
+    ic_cdk::api::is_controller(&ic_cdk::api::caller())
+}
 
-    return is_controller;
+/// Checks if the caller is a controller or an admin.
+///
+/// Admins are set at install time and may call every endpoint restricted to controllers.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the caller is a controller or an admin, `false` otherwise.
+#[query(name = "isAdmin")]
+pub fn is_admin() -> bool {
+    is_controller() || is_admin_principal(ic_cdk::api::caller())
 }
 
 /// Retrieves the caller's principal ID.
@@ -28,7 +39,5 @@ pub fn is_controller() -> bool {
 /// * `Principal` - The principal ID of the caller.
 #[query(name = "caller")]
 pub fn caller() -> Principal {
-    let id = ic_cdk::api::caller();
-
-    return id;
+    ic_cdk::api::caller()
 }
\ No newline at end of file
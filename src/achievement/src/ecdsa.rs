@@ -2,32 +2,19 @@
 
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
-use std::str::FromStr;
 
-/// Response containing the public key in hexadecimal format.
-#[derive(CandidType, Serialize, Debug)]
-pub struct PublicKeyReply {
-    pub public_key_hex: String,
-}
-
-/// Response containing the signature in hexadecimal format.
-#[derive(CandidType, Serialize, Debug, Clone)]
-pub struct SignatureReply {
-    pub signature_hex: String,
-}
-
-/// Response indicating whether a signature is valid.
-#[derive(CandidType, Serialize, Debug)]
-pub struct SignatureVerificationReply {
-    pub is_signature_valid: bool,
-}
+use crate::errors::{CallError, SigningError};
+use crate::state::get_ecdsa_key;
+use crate::storable::EcdsaKeyIds;
+use std::convert::TryFrom;
 
-pub type CanisterId = Principal;
+/// The cycles attached to every `sign_with_ecdsa` call.
+pub const SIGN_WITH_ECDSA_CYCLES: u64 = 30_000_000_000;
 
+// The management canister types down to `to_key_id` are kept identical to those of the reputation module.
 #[derive(CandidType, Serialize, Debug)]
 struct ECDSAPublicKey {
-    pub canister_id: Option<CanisterId>,
+    pub canister_id: Option<Principal>,
     pub derivation_path: Vec<Vec<u8>>,
     pub key_id: EcdsaKeyId,
 }
@@ -62,6 +49,38 @@ enum EcdsaCurve {
     Secp256k1,
 }
 
+impl EcdsaKeyIds {
+    fn to_key_id(self) -> EcdsaKeyId {
+        EcdsaKeyId {
+            curve: EcdsaCurve::Secp256k1,
+            name: match self {
+                Self::TestKeyLocalDevelopment => "dfx_test_key",
+                Self::TestKey1 => "test_key_1",
+                Self::ProductionKey1 => "key_1",
+            }
+            .to_string(),
+        }
+    }
+}
+
+/// Response containing the public key in hexadecimal format.
+#[derive(CandidType, Serialize, Debug)]
+pub struct PublicKeyReply {
+    pub public_key_hex: String,
+}
+
+/// Response containing the signature in hexadecimal format.
+#[derive(CandidType, Serialize, Debug, Clone)]
+pub struct SignatureReply {
+    pub signature_hex: String,
+}
+
+/// Response indicating whether a signature is valid.
+#[derive(CandidType, Serialize, Debug)]
+pub struct SignatureVerificationReply {
+    pub is_signature_valid: bool,
+}
+
 /// Builds a message string from the caller and identity wallet principals.
 ///
 /// # Arguments
@@ -84,18 +103,18 @@ pub fn build_principals_message(caller: Principal, identity_wallet: Principal) -
 ///
 /// # Returns
 ///
-/// * `Result<PublicKeyReply, String>` - The public key in hexadecimal format.
-pub async fn public_key() -> Result<PublicKeyReply, String> {
+/// * `Result<PublicKeyReply, SigningError>` - The public key in hexadecimal format.
+pub async fn public_key() -> Result<PublicKeyReply, SigningError> {
     let request = ECDSAPublicKey {
         canister_id: None,
         derivation_path: vec![],
-        key_id: EcdsaKeyIds::ProductionKey1.to_key_id(),
+        key_id: get_ecdsa_key().to_key_id(),
     };
 
     let (res,): (ECDSAPublicKeyReply,) =
-        ic_cdk::call(mgmt_canister_id(), "ecdsa_public_key", (request,))
+        ic_cdk::call(Principal::management_canister(), "ecdsa_public_key", (request,))
             .await
-            .map_err(|e| format!("ecdsa_public_key failed {}", e.1))?;
+            .map_err(|rejection| SigningError::PublicKeyFailed(CallError::new(Principal::management_canister(), "ecdsa_public_key", rejection)))?;
 
     Ok(PublicKeyReply {
         public_key_hex: hex::encode(&res.public_key),
@@ -110,22 +129,22 @@ pub async fn public_key() -> Result<PublicKeyReply, String> {
 ///
 /// # Returns
 ///
-/// * `Result<SignatureReply, String>` - The signature in hexadecimal format.
-pub async fn sign(message: String) -> Result<SignatureReply, String> {
+/// * `Result<SignatureReply, SigningError>` - The signature in hexadecimal format.
+pub async fn sign(message: String) -> Result<SignatureReply, SigningError> {
     let request = SignWithECDSA {
         message_hash: sha256(&message).to_vec(),
         derivation_path: vec![],
-        key_id: EcdsaKeyIds::ProductionKey1.to_key_id(),
+        key_id: get_ecdsa_key().to_key_id(),
     };
 
     let (response,): (SignWithECDSAReply,) = ic_cdk::api::call::call_with_payment(
-        mgmt_canister_id(),
+        Principal::management_canister(),
         "sign_with_ecdsa",
         (request,),
-        30_000_000_000,
+        SIGN_WITH_ECDSA_CYCLES,
     )
     .await
-    .map_err(|e| format!("sign_with_ecdsa failed {}", e.1))?;
+    .map_err(|rejection| SigningError::SignFailed(CallError::new(Principal::management_canister(), "sign_with_ecdsa", rejection)))?;
 
     Ok(SignatureReply {
         signature_hex: hex::encode(&response.signature),
@@ -142,21 +161,23 @@ pub async fn sign(message: String) -> Result<SignatureReply, String> {
 ///
 /// # Returns
 ///
-/// * `Result<SignatureVerificationReply, String>` - The result of the verification.
+/// * `Result<SignatureVerificationReply, SigningError>` - The result of the verification.
 pub async fn verify(
     signature_hex: String,
     message: String,
     public_key_hex: String,
-) -> Result<SignatureVerificationReply, String> {
-    let signature_bytes = hex::decode(&signature_hex).expect("failed to hex-decode signature");
-    let pubkey_bytes = hex::decode(&public_key_hex).expect("failed to hex-decode public key");
+) -> Result<SignatureVerificationReply, SigningError> {
+    let signature_bytes = hex::decode(&signature_hex)
+        .map_err(|e| SigningError::InvalidSignature(format!("failed to hex-decode signature: {}", e)))?;
+    let pubkey_bytes = hex::decode(&public_key_hex)
+        .map_err(|e| SigningError::InvalidSignature(format!("failed to hex-decode public key: {}", e)))?;
     let message_bytes = message.as_bytes();
 
     use k256::ecdsa::signature::Verifier;
     let signature = k256::ecdsa::Signature::try_from(signature_bytes.as_slice())
-        .expect("failed to deserialize signature");
+        .map_err(|e| SigningError::InvalidSignature(format!("failed to deserialize signature: {}", e)))?;
     let is_signature_valid= k256::ecdsa::VerifyingKey::from_sec1_bytes(&pubkey_bytes)
-        .expect("failed to deserialize sec1 encoding into public key")
+        .map_err(|e| SigningError::InvalidSignature(format!("failed to deserialize sec1 encoding into public key: {}", e)))?
         .verify(message_bytes, &signature)
         .is_ok();
 
@@ -165,10 +186,6 @@ pub async fn verify(
     })
 }
 
-fn mgmt_canister_id() -> CanisterId {
-    CanisterId::from_str(&"aaaaa-aa").unwrap()
-}
-
 fn sha256(input: &String) -> [u8; 32] {
     use sha2::Digest;
     let mut hasher = sha2::Sha256::new();
@@ -176,29 +193,6 @@ fn sha256(input: &String) -> [u8; 32] {
     hasher.finalize().into()
 }
 
-enum EcdsaKeyIds {
-    #[allow(unused)]
-    TestKeyLocalDevelopment,
-    #[allow(unused)]
-    TestKey1,
-    #[allow(unused)]
-    ProductionKey1,
-}
-
-impl EcdsaKeyIds {
-    fn to_key_id(&self) -> EcdsaKeyId {
-        EcdsaKeyId {
-            curve: EcdsaCurve::Secp256k1,
-            name: match self {
-                Self::TestKeyLocalDevelopment => "dfx_test_key",
-                Self::TestKey1 => "test_key_1",
-                Self::ProductionKey1 => "key_1",
-            }
-            .to_string(),
-        }
-    }
-}
-
 // In the following, we register a custom getrandom implementation because
 // otherwise getrandom (which is a dependency of k256) fails to compile.
 // This is necessary because getrandom by default fails to compile for the
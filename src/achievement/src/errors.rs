@@ -0,0 +1,161 @@
+//! This module defines the error types returned by the achievement canister.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::call::RejectionCode;
+use serde::Deserialize;
+
+/// A rejected inter-canister call.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CallError {
+    pub canister: Principal,
+    pub method: String,
+    pub code: RejectionCode,
+    pub message: String,
+}
+
+impl CallError {
+    /// Builds a call error from the rejection returned by `ic_cdk::call`.
+    ///
+    /// # Arguments
+    ///
+    /// * `canister` - The principal of the called canister.
+    /// * `method` - The name of the called method.
+    /// * `rejection` - The reject code and message of the call.
+    ///
+    /// # Returns
+    ///
+    /// * `CallError` - The call error.
+    pub fn new(canister: Principal, method: &str, rejection: (RejectionCode, String)) -> Self {
+        CallError {
+            canister,
+            method: String::from(method),
+            code: rejection.0,
+            message: rejection.1,
+        }
+    }
+}
+
+/// Errors related to access control.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum AccessError {
+    AccessDenied,
+}
+
+/// Errors related to the eligibility of a principal for the achievement.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum EligibilityError {
+    NotEligible,
+    HashNotFound,
+    StatusNotFound,
+    SignatureMismatch,
+    CheckFailed(CallError),
+}
+
+/// Errors related to ECDSA signing and verification.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum SigningError {
+    PublicKeyFailed(CallError),
+    SignFailed(CallError),
+    InvalidSignature(String),
+    LowCycles { balance: u128, threshold: u128 },
+}
+
+/// Errors related to the configuration passed at install time.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ConfigError {
+    EmptyField(String),
+    InvalidPrincipal(Principal),
+    DuplicatePrincipal(Principal),
+}
+
+/// Errors returned when threshold signing is rate limited.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum RateLimitError {
+    Cooldown { retry_after_seconds: u64 },
+    PrincipalLimitReached,
+    DailyBudgetExhausted,
+}
+
+/// Errors returned when the reputation module could not be notified of a change.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum NotificationError {
+    Refused(String),
+    CallFailed(CallError),
+}
+
+/// Errors returned by the achievement canister.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum AchievementError {
+    Access(AccessError),
+    Eligibility(EligibilityError),
+    Signing(SigningError),
+    Config(ConfigError),
+    Paused,
+    RateLimit(RateLimitError),
+    Notification(NotificationError),
+}
+
+impl From<AccessError> for AchievementError {
+    fn from(err: AccessError) -> Self {
+        AchievementError::Access(err)
+    }
+}
+
+impl From<EligibilityError> for AchievementError {
+    fn from(err: EligibilityError) -> Self {
+        AchievementError::Eligibility(err)
+    }
+}
+
+impl From<SigningError> for AchievementError {
+    fn from(err: SigningError) -> Self {
+        AchievementError::Signing(err)
+    }
+}
+
+impl From<ConfigError> for AchievementError {
+    fn from(err: ConfigError) -> Self {
+        AchievementError::Config(err)
+    }
+}
+
+impl From<RateLimitError> for AchievementError {
+    fn from(err: RateLimitError) -> Self {
+        AchievementError::RateLimit(err)
+    }
+}
+
+impl From<NotificationError> for AchievementError {
+    fn from(err: NotificationError) -> Self {
+        AchievementError::Notification(err)
+    }
+}
+
+impl AchievementError {
+    /// Retrieves the name of the error, used as the reason of the failure metrics.
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The name of the error variant.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            AchievementError::Access(AccessError::AccessDenied) => "AccessDenied",
+            AchievementError::Eligibility(EligibilityError::NotEligible) => "NotEligible",
+            AchievementError::Eligibility(EligibilityError::HashNotFound) => "HashNotFound",
+            AchievementError::Eligibility(EligibilityError::StatusNotFound) => "StatusNotFound",
+            AchievementError::Eligibility(EligibilityError::SignatureMismatch) => "SignatureMismatch",
+            AchievementError::Eligibility(EligibilityError::CheckFailed(_)) => "CheckFailed",
+            AchievementError::Signing(SigningError::PublicKeyFailed(_)) => "PublicKeyFailed",
+            AchievementError::Signing(SigningError::SignFailed(_)) => "SignFailed",
+            AchievementError::Signing(SigningError::InvalidSignature(_)) => "InvalidSignature",
+            AchievementError::Signing(SigningError::LowCycles { .. }) => "LowCycles",
+            AchievementError::Config(_) => "InvalidConfig",
+            AchievementError::Paused => "Paused",
+            AchievementError::RateLimit(RateLimitError::Cooldown { .. }) => "Cooldown",
+            AchievementError::RateLimit(RateLimitError::PrincipalLimitReached) => "PrincipalLimitReached",
+            AchievementError::RateLimit(RateLimitError::DailyBudgetExhausted) => "DailyBudgetExhausted",
+            AchievementError::Notification(NotificationError::Refused(_)) => "NotificationRefused",
+            AchievementError::Notification(NotificationError::CallFailed(_)) => "NotificationFailed",
+        }
+    }
+}
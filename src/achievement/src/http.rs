@@ -0,0 +1,149 @@
+//! This module serves the metadata of the achievement canister as JSON over the HTTP gateway, with certified responses.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ic_cdk::query;
+use ic_certification::{labeled, labeled_hash, AsHashTree, Hash, RbTree};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::metrics::render_metrics;
+use crate::state::get_achievement_metadata;
+use crate::storable::{HttpRequest, HttpResponse};
+
+/// The label the certified response hashes are stored under, as expected by the HTTP gateway.
+const LABEL_ASSETS: &[u8] = b"http_assets";
+
+const METADATA_PATH: &str = "/metadata.json";
+const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+thread_local! {
+    /// The SHA-256 hash of the body of every certified response, by path.
+    static CERTIFIED_RESPONSES: RefCell<RbTree<String, Hash>> = const { RefCell::new(RbTree::new()) };
+}
+
+/// Renders the body of the response served at a path.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request, without query string.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The JSON document served at the path, or `None` if the path is unknown.
+fn render(path: &str) -> Option<Value> {
+    if path != METADATA_PATH {
+        return None;
+    }
+
+    let metadata = get_achievement_metadata();
+
+    Some(json!({
+        "achievement": ic_cdk::api::id().to_text(),
+        "achievement_name": metadata.achievement_name,
+        "achievement_description": metadata.achievement_description
+    }))
+}
+
+/// Encodes a JSON document as a response body.
+fn encode(value: &Value) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)))
+}
+
+/// Certifies the response served at `/metadata.json`.
+///
+/// Must be called from an update call, `init` or `post_upgrade` after the metadata changed. The certified
+/// hashes are kept on the heap, so they are recomputed after every upgrade.
+pub fn certify_metadata() {
+    CERTIFIED_RESPONSES.with(|c| {
+        let mut responses = c.borrow_mut();
+
+        if let Some(value) = render(METADATA_PATH) {
+            responses.insert(String::from(METADATA_PATH), Sha256::digest(encode(&value)).into());
+        }
+
+        ic_cdk::api::set_certified_data(&labeled_hash(LABEL_ASSETS, &responses.root_hash()));
+    });
+}
+
+/// Builds the `IC-Certificate` header proving the response served at a path.
+///
+/// # Arguments
+///
+/// * `path` - The certified path.
+///
+/// # Returns
+///
+/// * `(String, String)` - The header name and value.
+fn certificate_header(path: &str) -> (String, String) {
+    let certificate = ic_cdk::api::data_certificate().unwrap_or_default();
+    let witness = CERTIFIED_RESPONSES.with(|c| c.borrow().witness(path.as_bytes()));
+    let tree = labeled(LABEL_ASSETS, witness);
+
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe()
+        .and_then(|_| tree.serialize(&mut serializer))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    (
+        String::from("IC-Certificate"),
+        format!("certificate=:{}:, tree=:{}:", BASE64.encode(certificate), BASE64.encode(serializer.into_inner()))
+    )
+}
+
+/// Builds an uncertified JSON error response.
+fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![(String::from("Content-Type"), String::from("application/json"))],
+        body: encode(&json!({ "error": message }))
+    }
+}
+
+/// Serves the metadata of the achievement canister as JSON at `/metadata.json`.
+///
+/// The response is certified, so that browsers can fetch it through the boundary nodes. `/metrics` serves
+/// the metrics of the achievement canister in the Prometheus text format; it changes on every call, so it
+/// is not certified and must be fetched through the raw domain.
+///
+/// # Arguments
+///
+/// * `request` - The HTTP request.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The JSON document served at the requested path.
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" && request.method != "HEAD" {
+        return error_response(405, "Method not allowed");
+    }
+
+    let path = request.url.split(['?', '#']).next().unwrap_or_default();
+
+    if path == METRICS_PATH {
+        return HttpResponse {
+            status_code: 200,
+            headers: vec![(String::from("Content-Type"), String::from(METRICS_CONTENT_TYPE))],
+            body: render_metrics()
+        };
+    }
+
+    let certified = CERTIFIED_RESPONSES.with(|c| c.borrow().get(path.as_bytes()).is_some());
+
+    match render(path) {
+        Some(value) if certified => HttpResponse {
+            status_code: 200,
+            headers: vec![
+                (String::from("Content-Type"), String::from("application/json")),
+                (String::from("Access-Control-Allow-Origin"), String::from("*")),
+                certificate_header(path)
+            ],
+            body: encode(&value)
+        },
+        _ => error_response(404, "Not found")
+    }
+}
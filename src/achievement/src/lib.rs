@@ -5,8 +5,16 @@ pub mod storable;
 pub mod access;
 pub mod state;
 pub mod logic;
+pub mod errors;
+pub mod migrations;
+pub mod pause;
+pub mod limits;
+pub mod status;
+pub mod http;
+pub mod metrics;
 
 use candid::Principal;
 use storable::*;
+use errors::*;
 
 ic_cdk::export_candid!();
\ No newline at end of file
@@ -0,0 +1,317 @@
+//! This module rate limits threshold ECDSA signing per principal and enforces a daily signing budget.
+
+use candid::Principal;
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use std::cell::RefCell;
+
+use crate::access::is_admin;
+use crate::ecdsa::SIGN_WITH_ECDSA_CYCLES;
+use crate::errors::{AccessError, AchievementError, RateLimitError};
+use crate::state::{MEMORY_MANAGER, PRINCIPAL_TO_HASH};
+use crate::storable::{
+    Memory, PrincipalStorable, SigningLimits, SigningUsage, SigningSpend, SignatureRecord
+};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+thread_local! {
+    pub static SIGNING_LIMITS: RefCell<StableCell<SigningLimits, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))), SigningLimits::default(),
+        ).unwrap()
+    );
+
+    pub static SIGNING_SPEND: RefCell<StableCell<SigningSpend, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))), SigningSpend::default(),
+        ).unwrap()
+    );
+
+    pub static PRINCIPAL_TO_SIGNING_USAGE: RefCell<StableBTreeMap<PrincipalStorable, SigningUsage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    pub static PRINCIPAL_TO_SIGNATURE_RECORD: RefCell<StableBTreeMap<PrincipalStorable, SignatureRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+}
+
+/// Returns the current time in seconds.
+fn now_seconds() -> u64 {
+    ic_cdk::api::time() / NANOS_PER_SECOND
+}
+
+/// Retrieves the limits applied to threshold ECDSA signing.
+///
+/// # Returns
+///
+/// * `SigningLimits` - The current signing limits.
+#[query(name = "getSigningLimits")]
+pub fn get_signing_limits() -> SigningLimits {
+    SIGNING_LIMITS.with(|l| l.borrow().get().clone())
+}
+
+/// Updates the limits applied to threshold ECDSA signing.
+///
+/// # Arguments
+///
+/// * `limits` - The new signing limits.
+///
+/// # Returns
+///
+/// * `Result<SigningLimits, AchievementError>` - The previous signing limits.
+#[update(name = "updateSigningLimits")]
+pub fn update_signing_limits(limits: SigningLimits) -> Result<SigningLimits, AchievementError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    Ok(SIGNING_LIMITS.with(|l| l.borrow_mut().set(limits))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err))))
+}
+
+/// Retrieves the cycles spent on signing.
+///
+/// # Returns
+///
+/// * `SigningSpend` - The cycles spent today and since install.
+#[query(name = "getSigningSpend")]
+pub fn get_signing_spend() -> SigningSpend {
+    signing_spend_at(now_seconds())
+}
+
+/// Retrieves the cycles spent on signing at a given time, as described in `get_signing_spend`.
+fn signing_spend_at(now: u64) -> SigningSpend {
+    let mut spend = SIGNING_SPEND.with(|s| s.borrow().get().clone());
+
+    if spend.day != now / SECONDS_PER_DAY {
+        spend.cycles = 0;
+    }
+
+    spend
+}
+
+/// Retrieves the signature of a principal for an identity wallet, if it has not expired yet.
+///
+/// # Arguments
+///
+/// * `principal` - The principal the hash was signed for.
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Option<String>` - The signature in hexadecimal format.
+pub fn reusable_signature(principal: Principal, identity_wallet: Principal) -> Option<String> {
+    reusable_signature_at(principal, identity_wallet, now_seconds())
+}
+
+/// Retrieves a reusable signature at a given time, as described in `reusable_signature`.
+fn reusable_signature_at(principal: Principal, identity_wallet: Principal, now: u64) -> Option<String> {
+    let record = PRINCIPAL_TO_SIGNATURE_RECORD.with(|r| r.borrow().get(&PrincipalStorable(principal)))?;
+    let limits = get_signing_limits();
+
+    if record.identity_wallet != identity_wallet
+        || now.saturating_sub(record.signed_at) >= limits.signature_ttl_seconds {
+        return None;
+    }
+
+    PRINCIPAL_TO_HASH.with(|p| p.borrow().get(&PrincipalStorable(principal))).map(|signature| signature.0)
+}
+
+/// Checks that a principal may request a new signature.
+///
+/// # Arguments
+///
+/// * `principal` - The principal requesting the signature.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - `Ok` if the principal is neither cooling down nor over its daily limit.
+pub fn ensure_signing_allowed(principal: Principal) -> Result<(), AchievementError> {
+    ensure_signing_allowed_at(principal, now_seconds())
+}
+
+/// Checks that a principal may request a new signature at a given time, as described in `ensure_signing_allowed`.
+fn ensure_signing_allowed_at(principal: Principal, now: u64) -> Result<(), AchievementError> {
+    let limits = get_signing_limits();
+
+    if let Some(usage) = PRINCIPAL_TO_SIGNING_USAGE.with(|u| u.borrow().get(&PrincipalStorable(principal))) {
+        let elapsed = now.saturating_sub(usage.last_signed_at);
+
+        if elapsed < limits.cooldown_seconds {
+            return Err(RateLimitError::Cooldown { retry_after_seconds: limits.cooldown_seconds - elapsed }.into());
+        }
+
+        if usage.day == now / SECONDS_PER_DAY && usage.count >= limits.max_signatures_per_principal_per_day {
+            return Err(RateLimitError::PrincipalLimitReached.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reserves the cycles of a signature on the daily budget and counts it against the principal.
+///
+/// The reservation is made before the signing call, so that concurrent calls cannot overspend.
+///
+/// # Arguments
+///
+/// * `principal` - The principal requesting the signature.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - `Ok` if the daily budget allows one more signature.
+pub fn reserve_signing(principal: Principal) -> Result<(), AchievementError> {
+    reserve_signing_at(principal, now_seconds())
+}
+
+/// Reserves a signature at a given time, as described in `reserve_signing`.
+fn reserve_signing_at(principal: Principal, now: u64) -> Result<(), AchievementError> {
+    ensure_signing_allowed_at(principal, now)?;
+
+    let limits = get_signing_limits();
+    let today = now / SECONDS_PER_DAY;
+    let mut spend = signing_spend_at(now);
+
+    if spend.cycles + SIGN_WITH_ECDSA_CYCLES > limits.daily_cycles_budget {
+        return Err(RateLimitError::DailyBudgetExhausted.into());
+    }
+
+    spend.day = today;
+    spend.cycles += SIGN_WITH_ECDSA_CYCLES;
+    spend.total_cycles += SIGN_WITH_ECDSA_CYCLES;
+    SIGNING_SPEND.with(|s| s.borrow_mut().set(spend))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    PRINCIPAL_TO_SIGNING_USAGE.with(|u| {
+        let mut usages = u.borrow_mut();
+        let mut usage = usages.get(&PrincipalStorable(principal)).unwrap_or_default();
+
+        if usage.day != today {
+            usage.day = today;
+            usage.count = 0;
+        }
+        usage.count += 1;
+        usage.last_signed_at = now;
+
+        usages.insert(PrincipalStorable(principal), usage);
+    });
+
+    Ok(())
+}
+
+/// Returns the cycles of a failed signature to the daily budget.
+///
+/// The principal keeps its cooldown, so that failing calls cannot be retried in a loop.
+pub fn release_signing() {
+    let mut spend = get_signing_spend();
+
+    spend.cycles = spend.cycles.saturating_sub(SIGN_WITH_ECDSA_CYCLES);
+    spend.total_cycles = spend.total_cycles.saturating_sub(SIGN_WITH_ECDSA_CYCLES);
+    SIGNING_SPEND.with(|s| s.borrow_mut().set(spend))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
+
+/// Records the identity wallet a hash was signed for, so that the signature can be reused.
+///
+/// # Arguments
+///
+/// * `principal` - The principal the hash was signed for.
+/// * `identity_wallet` - The principal of the identity wallet.
+pub fn record_signature(principal: Principal, identity_wallet: Principal) {
+    record_signature_at(principal, identity_wallet, now_seconds());
+}
+
+/// Records a signature made at a given time, as described in `record_signature`.
+fn record_signature_at(principal: Principal, identity_wallet: Principal, now: u64) {
+    PRINCIPAL_TO_SIGNATURE_RECORD.with(|r| r.borrow_mut().insert(PrincipalStorable(principal), SignatureRecord {
+        identity_wallet,
+        signed_at: now
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storable::Signature;
+
+    const DAY: u64 = SECONDS_PER_DAY;
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n])
+    }
+
+    fn set_limits(limits: SigningLimits) {
+        SIGNING_LIMITS.with(|l| l.borrow_mut().set(limits)).ok();
+    }
+
+    fn limits() -> SigningLimits {
+        SigningLimits {
+            cooldown_seconds: 60,
+            max_signatures_per_principal_per_day: 2,
+            daily_cycles_budget: 10 * SIGN_WITH_ECDSA_CYCLES,
+            signature_ttl_seconds: 3600
+        }
+    }
+
+    #[test]
+    fn cooldown_delays_the_next_signature() {
+        set_limits(limits());
+        reserve_signing_at(principal(1), 10 * DAY).unwrap();
+
+        assert!(matches!(
+            ensure_signing_allowed_at(principal(1), 10 * DAY + 15),
+            Err(AchievementError::RateLimit(RateLimitError::Cooldown { retry_after_seconds: 45 }))
+        ));
+        assert!(ensure_signing_allowed_at(principal(1), 10 * DAY + 60).is_ok());
+        assert!(ensure_signing_allowed_at(principal(2), 10 * DAY).is_ok());
+    }
+
+    #[test]
+    fn daily_count_is_reset_the_next_day() {
+        set_limits(limits());
+        reserve_signing_at(principal(1), 10 * DAY).unwrap();
+        reserve_signing_at(principal(1), 10 * DAY + 60).unwrap();
+
+        assert!(matches!(
+            reserve_signing_at(principal(1), 10 * DAY + 120),
+            Err(AchievementError::RateLimit(RateLimitError::PrincipalLimitReached))
+        ));
+        assert!(reserve_signing_at(principal(1), 11 * DAY).is_ok());
+    }
+
+    #[test]
+    fn cycles_budget_is_shared_by_every_principal_and_reset_daily() {
+        set_limits(SigningLimits { daily_cycles_budget: 2 * SIGN_WITH_ECDSA_CYCLES, ..limits() });
+        reserve_signing_at(principal(1), 10 * DAY).unwrap();
+        reserve_signing_at(principal(2), 10 * DAY).unwrap();
+
+        assert!(matches!(
+            reserve_signing_at(principal(3), 10 * DAY),
+            Err(AchievementError::RateLimit(RateLimitError::DailyBudgetExhausted))
+        ));
+        assert_eq!(signing_spend_at(10 * DAY).cycles, 2 * SIGN_WITH_ECDSA_CYCLES);
+        assert_eq!(signing_spend_at(11 * DAY).cycles, 0);
+        assert!(reserve_signing_at(principal(3), 11 * DAY).is_ok());
+        assert_eq!(signing_spend_at(11 * DAY).total_cycles, 3 * SIGN_WITH_ECDSA_CYCLES);
+    }
+
+    #[test]
+    fn signature_is_reused_for_the_same_wallet_until_it_expires() {
+        set_limits(limits());
+        PRINCIPAL_TO_HASH.with(|p| p.borrow_mut().insert(PrincipalStorable(principal(1)), Signature(String::from("ab"))));
+        record_signature_at(principal(1), principal(9), 10 * DAY);
+
+        assert_eq!(reusable_signature_at(principal(1), principal(9), 10 * DAY + 3599), Some(String::from("ab")));
+        assert_eq!(reusable_signature_at(principal(1), principal(8), 10 * DAY), None);
+        assert_eq!(reusable_signature_at(principal(1), principal(9), 10 * DAY + 3600), None);
+        assert_eq!(reusable_signature_at(principal(2), principal(9), 10 * DAY), None);
+    }
+}
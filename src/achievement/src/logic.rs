@@ -1,11 +1,19 @@
 //! This module contains the logic for checking achievement eligibility and managing achievements.
 
-use ic_cdk::{query, update};
-use candid::Principal;
+use ic_cdk::update;
+use candid::{IDLValue, Principal};
 
-use crate::state::{get_principal_to_hash_value, update_principal_to_hash, update_principal_to_achievement_status};
+use crate::state::{
+    get_principal_to_hash_value, update_principal_to_hash, update_principal_to_achievement_status,
+    get_reputation_module_settings
+};
 use crate::ecdsa::{public_key, build_principals_message, sign, verify};
 use crate::storable::{Signature, AchievementStatusEnum, AchievementStatus};
+use crate::errors::{AchievementError, CallError, EligibilityError};
+use crate::pause::ensure_not_paused;
+use crate::status::ensure_cycles_available;
+use crate::metrics::{observe_claim, observe_signing, count_signature};
+use crate::limits::{reusable_signature, reserve_signing, release_signing, record_signature};
 
 /// Checks if a principal is eligible for an achievement.
 ///
@@ -16,7 +24,7 @@ use crate::storable::{Signature, AchievementStatusEnum, AchievementStatus};
 ///
 /// # Returns
 ///
-/// * `Result<bool, String>` - `true` if the principal is eligible, `false` otherwise.
+/// * `Result<bool, AchievementError>` - `true` if the principal is eligible, `false` otherwise.
 /// 
 /// # Example
 /// 
@@ -31,14 +39,19 @@ use crate::storable::{Signature, AchievementStatusEnum, AchievementStatus};
 ///     })
 /// ```
 #[update(name = "checkAchievementEligibility")]
-async fn check_achievement_eligibility(principal: Principal, blob: Vec<u8>) -> Result<bool, String> {
+#[allow(unused_variables)]
+async fn check_achievement_eligibility(principal: Principal, blob: Vec<u8>) -> Result<bool, AchievementError> {
 
     // Your conditions for achievement
     // Example of calling another canister
 
     let example_backend_canister_id = Principal::from_text("4esxg-gyaaa-aaaaj-qnclq-cai").unwrap();
 
-    let is_posted: (bool, ) = ic_cdk::call(example_backend_canister_id, "getPrincipalToIsPosted", (principal,)).await.unwrap();
+    let is_posted: (bool, ) = ic_cdk::call(example_backend_canister_id, "getPrincipalToIsPosted", (principal,))
+        .await
+        .map_err(|rejection| EligibilityError::CheckFailed(
+            CallError::new(example_backend_canister_id, "getPrincipalToIsPosted", rejection)
+        ))?;
 
     Ok(is_posted.0)
 }
@@ -52,7 +65,7 @@ async fn check_achievement_eligibility(principal: Principal, blob: Vec<u8>) -> R
 ///
 /// # Returns
 ///
-/// * `Result<String, String>` - The result of the hash generation.
+/// * `Result<String, AchievementError>` - The result of the hash generation.
 ///
 /// # Example
 ///
@@ -77,19 +90,82 @@ async fn check_achievement_eligibility(principal: Principal, blob: Vec<u8>) -> R
 /// })
 /// ```
 #[update(name = "generateHashToIdentityWallet")]
-async fn generate_hash_to_identity_wallet(identity_wallet: Principal, blob: Vec<u8>) -> Result<String, String> {
+async fn generate_hash_to_identity_wallet(identity_wallet: Principal, blob: Vec<u8>) -> Result<String, AchievementError> {
+    let result = sign_identity_wallet_hash(identity_wallet, blob).await;
+    observe_signing(&result);
+
+    result
+}
+
+/// Signs the hash of an identity wallet, as described in `generate_hash_to_identity_wallet`.
+async fn sign_identity_wallet_hash(identity_wallet: Principal, blob: Vec<u8>) -> Result<String, AchievementError> {
+    ensure_not_paused()?;
+
     let caller = ic_cdk::api::caller();
-    let eligibility = check_achievement_eligibility(caller, blob).await.unwrap();
+
+    let eligibility = check_achievement_eligibility(caller, blob).await?;
 
     if eligibility {
+        if let Some(signature_hex) = reusable_signature(caller, identity_wallet) {
+            return Ok(format!("Succesfully generate hash for Identity Wallet. Signature {}", signature_hex));
+        }
+
+        ensure_cycles_available()?;
+        reserve_signing(caller)?;
+
         let message = build_principals_message(caller, identity_wallet);
-        let signature = sign(message).await?;
+        let signature = match sign(message).await {
+            Ok(signature) => signature,
+            Err(err) => {
+                release_signing();
+                return Err(err.into());
+            }
+        };
 
         update_principal_to_hash(caller, Signature(signature.clone().signature_hex))?;
+        record_signature(caller, identity_wallet);
+        count_signature();
 
-        Ok(String::from(format!("Succesfully generate hash for Identity Wallet. Signature {}", signature.signature_hex)))
+        Ok(format!("Succesfully generate hash for Identity Wallet. Signature {}", signature.signature_hex))
     } else {
-        Err(String::from("Caller principal is not eligible"))
+        Err(EligibilityError::NotEligible.into())
+    }
+}
+
+/// Pushes the achievement to an identity wallet through the reputation module.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Option<Result<u128, String>>` - The result of the issuance, or `None` if push issuance is disabled.
+async fn push_achievement(identity_wallet: Principal) -> Option<Result<u128, String>> {
+    let settings = get_reputation_module_settings();
+
+    if !settings.push_issuance {
+        return None;
+    }
+
+    let issued: Result<(Result<u128, IDLValue>, ), _> = ic_cdk::call(
+        settings.reputation_module,
+        "issueAchievementFromAchievementCanister",
+        (identity_wallet,)
+    ).await;
+
+    Some(match issued {
+        Ok((result, )) => result.map_err(|err| err.to_string()),
+        Err(rejection) => Err(format!("{:?}", CallError::new(settings.reputation_module, "issueAchievementFromAchievementCanister", rejection)))
+    })
+}
+
+/// Builds the reply of a successful achievement reception.
+fn allowed_status_message(push_result: Option<Result<u128, String>>) -> String {
+    match push_result {
+        None => String::from("Achievement status changed to allowed"),
+        Some(Ok(n)) => format!("Achievement status changed to allowed and issued. Token {}", n),
+        Some(Err(err)) => format!("Achievement status changed to allowed, but it could not be issued: {}", err)
     }
 }
 
@@ -101,7 +177,7 @@ async fn generate_hash_to_identity_wallet(identity_wallet: Principal, blob: Vec<
 ///
 /// # Returns
 ///
-/// * `Result<String, String>` - The result of the achievement reception.
+/// * `Result<String, AchievementError>` - The result of the achievement reception.
 ///
 /// # Example
 ///
@@ -111,17 +187,27 @@ async fn generate_hash_to_identity_wallet(identity_wallet: Principal, blob: Vec<
 /// (variant { Ok = "Achievement issued" })
 /// ```
 #[update(name = "receiveAchievementFromIdentityWallet")]
-async fn receive_achievement_from_identity_wallet(blob: Vec<u8>) -> Result<String, String> {
+async fn receive_achievement_from_identity_wallet(blob: Vec<u8>) -> Result<String, AchievementError> {
+    let result = receive_achievement(blob).await;
+    observe_claim(&result);
+
+    result
+}
+
+/// Receives an achievement for the caller, as described in `receive_achievement_from_identity_wallet`.
+async fn receive_achievement(blob: Vec<u8>) -> Result<String, AchievementError> {
+    ensure_not_paused()?;
+
     let caller = ic_cdk::api::caller();
-    let eligibility = check_achievement_eligibility(caller, blob).await.unwrap();
+    let eligibility = check_achievement_eligibility(caller, blob).await?;
 
     if eligibility {
         let allowed_status = AchievementStatusEnum::Allowed;
         update_principal_to_achievement_status(caller, AchievementStatus(allowed_status.to_u8()))?;
 
-        Ok(String::from("Achievement status changed to allowed"))
+        Ok(allowed_status_message(push_achievement(caller).await))
     } else {
-        Err(String::from("Caller principal is not eligible"))
+        Err(EligibilityError::NotEligible.into())
     }
 }
 
@@ -133,7 +219,7 @@ async fn receive_achievement_from_identity_wallet(blob: Vec<u8>) -> Result<Strin
 ///
 /// # Returns
 ///
-/// * `Result<String, String>` - The result of the achievement reception.
+/// * `Result<String, AchievementError>` - The result of the achievement reception.
 ///
 /// # Example
 ///
@@ -143,7 +229,17 @@ async fn receive_achievement_from_identity_wallet(blob: Vec<u8>) -> Result<Strin
 /// (variant { Ok = "Achievement status changed to allowed" })
 /// ```
 #[update(name = "receiveAchievementFromIdentityWalletWithHash")]
-async fn receive_achievement_from_identity_wallet_with_hash(principal: Principal) -> Result<String, String> {
+async fn receive_achievement_from_identity_wallet_with_hash(principal: Principal) -> Result<String, AchievementError> {
+    let result = receive_achievement_with_hash(principal).await;
+    observe_claim(&result);
+
+    result
+}
+
+/// Receives an achievement for the caller using a hash, as described in `receive_achievement_from_identity_wallet_with_hash`.
+async fn receive_achievement_with_hash(principal: Principal) -> Result<String, AchievementError> {
+    ensure_not_paused()?;
+
     let caller = ic_cdk::api::caller();
     let hash = get_principal_to_hash_value(principal)?;
     let public_key = public_key().await?;
@@ -154,8 +250,8 @@ async fn receive_achievement_from_identity_wallet_with_hash(principal: Principal
         let allowed_status = AchievementStatusEnum::Allowed;
         update_principal_to_achievement_status(caller, AchievementStatus(allowed_status.to_u8()))?;
 
-        Ok(String::from("Achievement status changed to allowed"))
+        Ok(allowed_status_message(push_achievement(caller).await))
     } else {
-        Err(String::from("Principal is not eligible or hash mismatch"))
+        Err(EligibilityError::SignatureMismatch.into())
     }
 }
\ No newline at end of file
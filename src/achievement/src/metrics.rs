@@ -0,0 +1,140 @@
+//! This module counts claims, signatures and failures and exports them, with resource gauges, in the Prometheus text format.
+
+use ic_metrics_encoder::MetricsEncoder;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableCell;
+use std::cell::RefCell;
+use std::io;
+
+use crate::errors::AchievementError;
+use crate::limits::get_signing_spend;
+use crate::state::{MEMORY_MANAGER, PRINCIPAL_TO_ACHIEVEMENT_STATUS};
+use crate::status::get_low_cycles_threshold;
+use crate::storable::{Memory, MetricCounters, FailureCount};
+
+/// The size of a WebAssembly page, in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+thread_local! {
+    pub static METRIC_COUNTERS: RefCell<StableCell<MetricCounters, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))), MetricCounters::default(),
+        ).unwrap()
+    );
+}
+
+/// Applies a change to the metric counters.
+fn update_counters(f: impl FnOnce(&mut MetricCounters)) {
+    METRIC_COUNTERS.with(|c| {
+        let mut cell = c.borrow_mut();
+        let mut counters = cell.get().clone();
+        f(&mut counters);
+        cell.set(counters)
+    }).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
+
+/// Counts a failed call of an operation and records the time of the error.
+///
+/// # Arguments
+///
+/// * `operation` - The name of the failed operation.
+/// * `err` - The error returned by the operation.
+pub fn record_failure(operation: &str, err: &AchievementError) {
+    let reason = err.reason();
+
+    update_counters(|counters| {
+        match counters.failures.iter_mut().find(|f| f.operation == operation && f.reason == reason) {
+            Some(failure) => failure.count += 1,
+            None => counters.failures.push(FailureCount {
+                operation: String::from(operation),
+                reason: String::from(reason),
+                count: 1
+            })
+        }
+        counters.last_error_at = Some(ic_cdk::api::time());
+    });
+}
+
+/// Counts a claim of the achievement and its failure, if any.
+///
+/// # Arguments
+///
+/// * `result` - The result of the claim.
+pub fn observe_claim<T>(result: &Result<T, AchievementError>) {
+    update_counters(|counters| counters.claims += 1);
+
+    if let Err(err) = result {
+        record_failure("claim", err);
+    }
+}
+
+/// Counts the failure of a hash signing request, if any.
+///
+/// # Arguments
+///
+/// * `result` - The result of the request.
+pub fn observe_signing<T>(result: &Result<T, AchievementError>) {
+    if let Err(err) = result {
+        record_failure("signature", err);
+    }
+}
+
+/// Counts a hash signed with threshold ECDSA.
+pub fn count_signature() {
+    update_counters(|counters| counters.signatures += 1);
+}
+
+/// Retrieves the size of the heap memory, in bytes.
+fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return 0;
+}
+
+/// Writes every metric of the achievement canister.
+fn encode_metrics(encoder: &mut MetricsEncoder<Vec<u8>>) -> io::Result<()> {
+    let counters = METRIC_COUNTERS.with(|c| c.borrow().get().clone());
+    let spend = get_signing_spend();
+
+    encoder.encode_counter("achievement_claims_total", counters.claims as f64, "Number of achievement claims received.")?;
+    encoder.encode_counter("achievement_signatures_total", counters.signatures as f64, "Number of hashes signed with threshold ECDSA.")?;
+    encoder.encode_counter("achievement_signing_cycles_total", spend.total_cycles as f64, "Cycles spent on signing since install.")?;
+
+    let mut failures = encoder.counter_vec("achievement_failures_total", "Number of failed operations by reason.")?;
+    for failure in &counters.failures {
+        failures = failures.value(&[("operation", &failure.operation), ("reason", &failure.reason)], failure.count as f64)?;
+    }
+
+    encoder.encode_gauge(
+        "achievement_holders",
+        PRINCIPAL_TO_ACHIEVEMENT_STATUS.with(|s| s.borrow().len()) as f64,
+        "Number of principals with an achievement status."
+    )?;
+    encoder.encode_gauge("achievement_signing_cycles_today", spend.cycles as f64, "Cycles spent on signing today.")?;
+    encoder.encode_gauge("achievement_cycles_balance", ic_cdk::api::canister_balance128() as f64, "Cycles balance of the achievement canister.")?;
+    encoder.encode_gauge("achievement_low_cycles_threshold", get_low_cycles_threshold() as f64, "Cycles balance below which signing is refused.")?;
+    encoder.encode_gauge("achievement_stable_memory_bytes", (ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE) as f64, "Size of the stable memory, in bytes.")?;
+    encoder.encode_gauge("achievement_heap_memory_bytes", heap_memory_bytes() as f64, "Size of the heap memory, in bytes.")?;
+    encoder.encode_gauge(
+        "achievement_last_error_timestamp_seconds",
+        counters.last_error_at.map_or(0.0, |at| (at / 1_000_000_000) as f64),
+        "Time of the last failed operation, in seconds since the epoch, or 0 if none failed."
+    )?;
+
+    Ok(())
+}
+
+/// Renders the metrics of the achievement canister in the Prometheus text exposition format.
+///
+/// # Returns
+///
+/// * `Vec<u8>` - The metrics document.
+pub fn render_metrics() -> Vec<u8> {
+    let mut encoder = MetricsEncoder::new(vec![], (ic_cdk::api::time() / 1_000_000) as i64);
+
+    encode_metrics(&mut encoder).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    encoder.into_inner()
+}
@@ -0,0 +1,49 @@
+//! This module versions the stable-memory schema of the achievement canister and migrates older layouts after an upgrade.
+
+use crate::state::{get_schema_version, _set_schema_version};
+
+/// The stable-memory schema version written by this build.
+///
+/// Canisters deployed before the schema was versioned report version `0`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A migration upgrading the stable memory from the previous schema version.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    migrate: fn(),
+}
+
+/// The migrations of the schema, in ascending order of version.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Adopt the unversioned layout as the first schema version",
+        migrate: adopt_unversioned_layout,
+    },
+];
+
+/// The unversioned layout is read as is: statuses, hashes, metadata and settings keep their encoding.
+fn adopt_unversioned_layout() {}
+
+/// Runs every migration newer than the stored schema version.
+///
+/// The schema version is bumped after each migration, so that an upgrade interrupted by a trap
+/// resumes from the last applied migration. Traps if the stored schema is newer than this build,
+/// which prevents downgrading to a build unable to read it.
+pub fn run_migrations() {
+    let stored_version = get_schema_version();
+
+    if stored_version > SCHEMA_VERSION {
+        ic_cdk::trap(&format!(
+            "Stable memory schema version {} is newer than the supported version {}",
+            stored_version, SCHEMA_VERSION
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > stored_version) {
+        ic_cdk::println!("Migrating stable memory to schema version {}: {}", migration.version, migration.description);
+        (migration.migrate)();
+        _set_schema_version(migration.version);
+    }
+}
@@ -0,0 +1,106 @@
+//! This module provides the emergency pause switch of the achievement canister.
+
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{StableCell, StableVec};
+use std::cell::RefCell;
+
+use crate::access::is_admin;
+use crate::errors::{AccessError, AchievementError};
+use crate::state::MEMORY_MANAGER;
+use crate::storable::{Memory, PauseEvent};
+
+/// The maximum number of pause events returned by a single call to `getPauseLog`.
+const MAX_PAGE: u64 = 1000;
+
+thread_local! {
+    pub static PAUSED: RefCell<StableCell<bool, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))), false,
+        ).unwrap()
+    );
+
+    pub static PAUSE_LOG: RefCell<StableVec<PauseEvent, Memory>> = RefCell::new(
+        StableVec::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        ).unwrap()
+    );
+}
+
+/// Checks that the achievement canister is not paused.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - `Ok` if claims and signing are enabled.
+pub fn ensure_not_paused() -> Result<(), AchievementError> {
+    if is_paused() {
+        return Err(AchievementError::Paused);
+    }
+
+    Ok(())
+}
+
+/// Pauses or resumes every claim and signature of the achievement canister.
+///
+/// Queries keep working while the achievement canister is paused.
+///
+/// # Arguments
+///
+/// * `paused` - `true` to pause, `false` to resume.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - The result of the update operation.
+#[update(name = "setPaused")]
+pub fn set_paused(paused: bool) -> Result<(), AchievementError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    let event = PauseEvent {
+        paused,
+        changed_by: ic_cdk::api::caller(),
+        changed_at: ic_cdk::api::time()
+    };
+
+    PAUSED.with(|p| p.borrow_mut().set(paused))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+    PAUSE_LOG.with(|l| l.borrow_mut().push(&event))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    ic_cdk::println!("Pause switch set to {} by {}", paused, event.changed_by);
+
+    Ok(())
+}
+
+/// Checks whether the achievement canister is paused.
+///
+/// # Returns
+///
+/// * `bool` - `true` if claims and signing are paused.
+#[query(name = "isPaused")]
+pub fn is_paused() -> bool {
+    PAUSED.with(|p| *p.borrow().get())
+}
+
+/// Retrieves the changes of the pause switch, oldest first.
+///
+/// # Arguments
+///
+/// * `offset` - The number of events to skip.
+/// * `limit` - The maximum number of events to return, capped at 1000.
+///
+/// # Returns
+///
+/// * `Vec<PauseEvent>` - The pause events of the page.
+#[query(name = "getPauseLog")]
+pub fn get_pause_log(offset: u64, limit: u64) -> Vec<PauseEvent> {
+    let limit = limit.min(MAX_PAGE);
+
+    PAUSE_LOG.with(|l| {
+        let log = l.borrow();
+        let end = offset.saturating_add(limit).min(log.len());
+
+        (offset..end).filter_map(|index| log.get(index)).collect()
+    })
+}
@@ -1,7 +1,7 @@
 //! This module manages the state of the achievement system, including metadata, hashes, and achievement statuses.
 
-use ic_cdk::{query, update};
-use candid::Principal;
+use ic_cdk::{query, update, init, post_upgrade};
+use candid::{IDLValue, Principal};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::{
     DefaultMemoryImpl, StableBTreeMap, StableCell
@@ -9,9 +9,13 @@ use ic_stable_structures::{
 use std::cell::RefCell;
 
 use crate::storable::{
-    PrincipalStorable, AchievementStatus, Memory, Signature, AchievementMetadata
+    PrincipalStorable, AchievementStatus, Memory, Signature, AchievementMetadata, ReputationModuleSettings,
+    EcdsaKeyIds, AchievementInitArgs
 };
-use crate::access::is_controller;
+use crate::access::is_admin;
+use crate::migrations::{run_migrations, SCHEMA_VERSION};
+use crate::http::certify_metadata;
+use crate::errors::{AccessError, AchievementError, CallError, ConfigError, EligibilityError, NotificationError};
 
 thread_local! {
     pub static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -34,6 +38,30 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
         )
     );
+
+    pub static REPUTATION_MODULE_SETTINGS: RefCell<StableCell<ReputationModuleSettings, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))), ReputationModuleSettings::default(),
+        ).unwrap()
+    );
+
+    pub static ECDSA_KEY: RefCell<StableCell<EcdsaKeyIds, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))), EcdsaKeyIds::default(),
+        ).unwrap()
+    );
+
+    pub static ADMINS: RefCell<StableBTreeMap<PrincipalStorable, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    pub static STABLE_SCHEMA_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))), 0,
+        ).unwrap()
+    );
 }
 
 /// Updates the metadata of the achievement canister.
@@ -44,31 +72,75 @@ thread_local! {
 ///
 /// # Returns
 ///
-/// * `Result<AchievementMetadata, String>` - The result of the update operation.
-pub fn _update_canister_metadata(metadata: AchievementMetadata) -> Result<AchievementMetadata, String> {
-    Ok(METADATA.with(|m| {
+/// * `Result<AchievementMetadata, AchievementError>` - The result of the update operation.
+pub fn _update_canister_metadata(metadata: AchievementMetadata) -> Result<AchievementMetadata, AchievementError> {
+    let previous = METADATA.with(|m| {
         let mut metadata_module = m.borrow_mut();
         metadata_module.set(metadata)
     }).unwrap_or_else(|err| {
         ic_cdk::trap(&format!("{:?}", err))
-    }))
+    });
+    certify_metadata();
+
+    Ok(previous)
 }
 
 /// Updates the metadata of the achievement canister.
 ///
+/// The reputation module, if configured, is notified so that it refreshes its cached metadata.
+/// The new metadata is saved even when the notification fails: the failure is logged as a
+/// warning, and the reputation module picks the change up on its daily refresh.
+///
 /// # Arguments
 ///
 /// * `metadata` - The new metadata for the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<AchievementMetadata, String>` - The result of the update operation.
+/// * `Result<AchievementMetadata, AchievementError>` - The previous metadata.
 #[update(name = "updateAchivementMetadata")]
-pub fn update_achievement_metadata(metadata: AchievementMetadata) -> Result<AchievementMetadata, String> {
-    if(!is_controller()) {
-        return Err(String::from("Access denied"));
+pub async fn update_achievement_metadata(metadata: AchievementMetadata) -> Result<AchievementMetadata, AchievementError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+    let previous_metadata = _update_canister_metadata(metadata.clone())?;
+
+    if let Err(err) = notify_metadata_changed(metadata).await {
+        ic_cdk::println!("Warning: unable to notify the reputation module of the new metadata: {:?}", err);
+    }
+
+    Ok(previous_metadata)
+}
+
+/// Notifies the reputation module that the metadata of the achievement canister changed.
+///
+/// # Arguments
+///
+/// * `metadata` - The new metadata for the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), NotificationError>` - The result of the notification.
+async fn notify_metadata_changed(metadata: AchievementMetadata) -> Result<(), NotificationError> {
+    let settings = get_reputation_module_settings();
+
+    if settings.reputation_module == Principal::anonymous() {
+        return Ok(());
+    }
+
+    let notified: Result<(Result<(), IDLValue>, ), _> = ic_cdk::call(
+        settings.reputation_module,
+        "notifyAchievementMetadataChanged",
+        (metadata,)
+    ).await;
+
+    match notified {
+        Ok((Ok(()), )) => Ok(()),
+        Ok((Err(err), )) => Err(NotificationError::Refused(err.to_string())),
+        Err(rejection) => Err(NotificationError::CallFailed(
+            CallError::new(settings.reputation_module, "notifyAchievementMetadataChanged", rejection)
+        ))
     }
-    _update_canister_metadata(metadata)
 }
 
 /// Updates the hash for a principal.
@@ -80,8 +152,8 @@ pub fn update_achievement_metadata(metadata: AchievementMetadata) -> Result<Achi
 ///
 /// # Returns
 ///
-/// * `Result<(), String>` - The result of the update operation.
-pub fn update_principal_to_hash(principal: Principal, hash: Signature) -> Result<(), String> {
+/// * `Result<(), AchievementError>` - The result of the update operation.
+pub fn update_principal_to_hash(principal: Principal, hash: Signature) -> Result<(), AchievementError> {
     PRINCIPAL_TO_HASH.with(|p| p.borrow_mut().insert(PrincipalStorable(principal), hash));
 
     Ok(())
@@ -96,8 +168,8 @@ pub fn update_principal_to_hash(principal: Principal, hash: Signature) -> Result
 ///
 /// # Returns
 ///
-/// * `Result<(), String>` - The result of the update operation.
-pub fn update_principal_to_achievement_status(principal: Principal, achievement_status: AchievementStatus) -> Result<(), String> {
+/// * `Result<(), AchievementError>` - The result of the update operation.
+pub fn update_principal_to_achievement_status(principal: Principal, achievement_status: AchievementStatus) -> Result<(), AchievementError> {
     PRINCIPAL_TO_ACHIEVEMENT_STATUS.with(|p| p.borrow_mut().insert(PrincipalStorable(principal), achievement_status));
 
     Ok(())
@@ -124,13 +196,13 @@ pub fn get_achievement_metadata() -> AchievementMetadata {
 ///
 /// # Returns
 ///
-/// * `Result<Signature, String>` - The hash for the principal.
+/// * `Result<Signature, AchievementError>` - The hash for the principal.
 #[query(name = "getPrincipalToHashValue")]
-pub fn get_principal_to_hash_value(principal: Principal) -> Result<Signature, String> {
+pub fn get_principal_to_hash_value(principal: Principal) -> Result<Signature, AchievementError> {
     if let Some(hash) = PRINCIPAL_TO_HASH.with(|p| p.borrow().get(&PrincipalStorable(principal))) {
         Ok(hash)
     } else {
-        Err(String::from("Hash not found"))
+        Err(EligibilityError::HashNotFound.into())
     }
 }
 
@@ -142,13 +214,189 @@ pub fn get_principal_to_hash_value(principal: Principal) -> Result<Signature, St
 ///
 /// # Returns
 ///
-/// * `Result<u8, String>` - The achievement status for the principal.
+/// * `Result<u8, AchievementError>` - The achievement status for the principal.
 #[query(name = "getPrincipalToAchievementStatusValue")]
-pub fn get_principal_to_achievement_status_value(principal: Principal) -> Result<u8, String> {
+pub fn get_principal_to_achievement_status_value(principal: Principal) -> Result<u8, AchievementError> {
     if let Some(achievement_status) = PRINCIPAL_TO_ACHIEVEMENT_STATUS.with(|p| p.borrow().get(&PrincipalStorable(principal))) {
         Ok(achievement_status.0)
     } else {
-        Err(String::from("Achievement status not found"))
+        Err(EligibilityError::StatusNotFound.into())
+    }
+}
+
+/// Updates the settings of the reputation module the achievement is issued by.
+///
+/// # Arguments
+///
+/// * `settings` - The new reputation module settings.
+///
+/// # Returns
+///
+/// * `Result<ReputationModuleSettings, AchievementError>` - The result of the update operation.
+#[update(name = "updateReputationModuleSettings")]
+pub fn update_reputation_module_settings(settings: ReputationModuleSettings) -> Result<ReputationModuleSettings, AchievementError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    Ok(REPUTATION_MODULE_SETTINGS.with(|r| {
+        r.borrow_mut().set(settings)
+    }).unwrap_or_else(|err| {
+        ic_cdk::trap(&format!("{:?}", err))
+    }))
+}
+
+/// Retrieves the settings of the reputation module the achievement is issued by.
+///
+/// # Returns
+///
+/// * `ReputationModuleSettings` - The current reputation module settings.
+#[query(name = "getReputationModuleSettings")]
+pub fn get_reputation_module_settings() -> ReputationModuleSettings {
+    REPUTATION_MODULE_SETTINGS.with(|r| r.borrow().get().clone())
+}
+
+/// Retrieves the threshold ECDSA key used to sign identity wallet hashes.
+///
+/// # Returns
+///
+/// * `EcdsaKeyIds` - The configured key, `ProductionKey1` unless set at install time.
+#[query(name = "getEcdsaKey")]
+pub fn get_ecdsa_key() -> EcdsaKeyIds {
+    ECDSA_KEY.with(|k| *k.borrow().get())
+}
+
+/// Checks whether a principal is an admin of the achievement canister.
+///
+/// # Arguments
+///
+/// * `principal` - The principal to check.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the principal is an admin, `false` otherwise.
+pub fn is_admin_principal(principal: Principal) -> bool {
+    ADMINS.with(|a| a.borrow().contains_key(&PrincipalStorable(principal)))
+}
+
+/// Retrieves the admins of the achievement canister.
+///
+/// # Returns
+///
+/// * `Vec<Principal>` - The admins set at install time.
+#[query(name = "getAdmins")]
+pub fn get_admins() -> Vec<Principal> {
+    ADMINS.with(|a| a.borrow().iter().map(|(admin, _)| admin.0).collect())
+}
+
+/// Validates the arguments passed to the achievement canister at install time.
+///
+/// # Arguments
+///
+/// * `args` - The init arguments.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - `Ok` if the arguments are valid.
+fn _validate_init_args(args: &AchievementInitArgs) -> Result<(), AchievementError> {
+    if args.metadata.achievement_name.trim().is_empty() {
+        return Err(ConfigError::EmptyField(String::from("achievement_name")).into());
     }
+
+    let reserved = [Principal::anonymous(), Principal::management_canister(), ic_cdk::api::id()];
+
+    if let Some(settings) = &args.reputation_module {
+        if reserved.contains(&settings.reputation_module) {
+            return Err(ConfigError::InvalidPrincipal(settings.reputation_module).into());
+        }
+    }
+
+    for (i, admin) in args.admins.iter().enumerate() {
+        if reserved.contains(admin) {
+            return Err(ConfigError::InvalidPrincipal(*admin).into());
+        }
+        if args.admins[..i].contains(admin) {
+            return Err(ConfigError::DuplicatePrincipal(*admin).into());
+        }
+    }
+
+    Ok(())
 }
 
+/// Applies the arguments passed to the achievement canister at install time.
+///
+/// # Arguments
+///
+/// * `args` - The init arguments.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - The result of the configuration.
+fn _apply_init_args(args: AchievementInitArgs) -> Result<(), AchievementError> {
+    _validate_init_args(&args)?;
+
+    _update_canister_metadata(args.metadata)?;
+
+    if let Some(settings) = args.reputation_module {
+        REPUTATION_MODULE_SETTINGS.with(|r| r.borrow_mut().set(settings))
+            .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+    }
+
+    if let Some(ecdsa_key) = args.ecdsa_key {
+        ECDSA_KEY.with(|k| k.borrow_mut().set(ecdsa_key))
+            .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+    }
+
+    ADMINS.with(|a| {
+        let mut admins = a.borrow_mut();
+        for admin in &args.admins {
+            admins.insert(PrincipalStorable(*admin), ());
+        }
+    });
+
+    Ok(())
+}
+
+/// Initializes the achievement canister, optionally configuring it from the install arguments.
+///
+/// # Arguments
+///
+/// * `args` - The initial configuration. When omitted, the canister must be configured after deployment.
+#[init]
+fn init(args: Option<AchievementInitArgs>) {
+    if let Some(args) = args {
+        if let Err(err) = _apply_init_args(args) {
+            ic_cdk::trap(&format!("Invalid init arguments: {:?}", err));
+        }
+    }
+
+    _set_schema_version(SCHEMA_VERSION);
+    certify_metadata();
+}
+
+/// Migrates the stable memory to the current schema version after an upgrade and certifies the HTTP responses again.
+#[post_upgrade]
+fn post_upgrade() {
+    run_migrations();
+    certify_metadata();
+}
+
+/// Retrieves the stable-memory schema version of the achievement canister.
+///
+/// # Returns
+///
+/// * `u32` - The schema version, `0` for state written before the schema was versioned.
+#[query(name = "getSchemaVersion")]
+pub fn get_schema_version() -> u32 {
+    STABLE_SCHEMA_VERSION.with(|v| *v.borrow().get())
+}
+
+/// Stores the stable-memory schema version of the achievement canister.
+///
+/// # Arguments
+///
+/// * `version` - The schema version.
+pub fn _set_schema_version(version: u32) {
+    STABLE_SCHEMA_VERSION.with(|v| v.borrow_mut().set(version))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
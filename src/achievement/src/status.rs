@@ -0,0 +1,101 @@
+//! This module reports the resource usage of the achievement canister and refuses signing when its cycles run low.
+
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableCell;
+use std::cell::RefCell;
+
+use crate::access::is_admin;
+use crate::errors::{AccessError, AchievementError, SigningError};
+use crate::limits::{get_signing_spend, PRINCIPAL_TO_SIGNING_USAGE, PRINCIPAL_TO_SIGNATURE_RECORD};
+use crate::pause::PAUSE_LOG;
+use crate::state::{MEMORY_MANAGER, PRINCIPAL_TO_ACHIEVEMENT_STATUS, PRINCIPAL_TO_HASH, ADMINS};
+use crate::storable::{Memory, CanisterStatus, MapSize};
+
+/// The size of a WebAssembly page, in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// The default cycles balance below which signing is refused.
+const DEFAULT_LOW_CYCLES_THRESHOLD: u128 = 200_000_000_000;
+
+thread_local! {
+    pub static LOW_CYCLES_THRESHOLD: RefCell<StableCell<u128, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))), DEFAULT_LOW_CYCLES_THRESHOLD,
+        ).unwrap()
+    );
+}
+
+/// Retrieves the cycles balance below which signing is refused.
+///
+/// # Returns
+///
+/// * `u128` - The low cycles threshold.
+pub fn get_low_cycles_threshold() -> u128 {
+    LOW_CYCLES_THRESHOLD.with(|t| *t.borrow().get())
+}
+
+/// Sets the cycles balance below which signing is refused.
+///
+/// # Arguments
+///
+/// * `threshold` - The new low cycles threshold. `0` never refuses signing.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - The result of the update operation.
+#[update(name = "setLowCyclesThreshold")]
+pub fn set_low_cycles_threshold(threshold: u128) -> Result<(), AchievementError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    LOW_CYCLES_THRESHOLD.with(|t| t.borrow_mut().set(threshold))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    Ok(())
+}
+
+/// Checks that the cycles balance is above the low cycles threshold.
+///
+/// # Returns
+///
+/// * `Result<(), AchievementError>` - `Ok` if expensive operations may proceed.
+pub fn ensure_cycles_available() -> Result<(), AchievementError> {
+    let balance = ic_cdk::api::canister_balance128();
+    let threshold = get_low_cycles_threshold();
+
+    if balance < threshold {
+        return Err(SigningError::LowCycles { balance, threshold }.into());
+    }
+
+    Ok(())
+}
+
+/// Retrieves the resource usage of the achievement canister.
+///
+/// # Returns
+///
+/// * `CanisterStatus` - The cycles balance, stable memory usage, number of entries of every stable map and signing spend.
+#[query(name = "getCanisterStatus")]
+pub fn get_canister_status() -> CanisterStatus {
+    let map_sizes = [
+        ("achievement_statuses", PRINCIPAL_TO_ACHIEVEMENT_STATUS.with(|m| m.borrow().len())),
+        ("hashes", PRINCIPAL_TO_HASH.with(|m| m.borrow().len())),
+        ("signature_records", PRINCIPAL_TO_SIGNATURE_RECORD.with(|m| m.borrow().len())),
+        ("signing_usage", PRINCIPAL_TO_SIGNING_USAGE.with(|m| m.borrow().len())),
+        ("admins", ADMINS.with(|m| m.borrow().len())),
+        ("pause_log", PAUSE_LOG.with(|m| m.borrow().len())),
+    ];
+
+    CanisterStatus {
+        cycles_balance: ic_cdk::api::canister_balance128(),
+        low_cycles_threshold: get_low_cycles_threshold(),
+        stable_memory_bytes: ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE,
+        map_sizes: map_sizes.into_iter().map(|(name, len)| MapSize {
+            name: String::from(name),
+            len
+        }).collect(),
+        signing_spend: get_signing_spend()
+    }
+}
@@ -26,9 +26,74 @@ pub struct AchievementMetadata {
     pub achievement_description: String
 }
 
-impl AchievementMetadata {
+/// Settings of the reputation module the achievement is issued by.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ReputationModuleSettings {
+    pub reputation_module: Principal,
+    pub push_issuance: bool
+}
+
+/// The threshold ECDSA key used to sign identity wallet hashes.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Default)]
+pub enum EcdsaKeyIds {
+    TestKeyLocalDevelopment,
+    TestKey1,
+    #[default]
+    ProductionKey1,
+}
+
+impl Storable for EcdsaKeyIds {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_VALUE_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// Arguments configuring the achievement canister at install time.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct AchievementInitArgs {
+    pub metadata: AchievementMetadata,
+    pub reputation_module: Option<ReputationModuleSettings>,
+    pub admins: Vec<Principal>,
+    pub ecdsa_key: Option<EcdsaKeyIds>
+}
+
+impl Default for ReputationModuleSettings {
+    /// Creates settings that name no reputation module and do not push issuances.
+    fn default() -> Self {
+        Self {
+            reputation_module: Principal::anonymous(),
+            push_issuance: false
+        }
+    }
+}
+
+impl Storable for ReputationModuleSettings {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_VALUE_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+impl Default for AchievementMetadata {
     /// Creates a default instance of `AchievementMetadata`.
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             achievement_description: String::default(),
             achievement_name: String::default()
@@ -37,11 +102,11 @@ impl AchievementMetadata {
 }
 
 impl Storable for AchievementMetadata {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Decode!(bytes.as_ref(), Self).unwrap()
     }
 
@@ -74,7 +139,6 @@ impl AchievementStatusEnum {
         match Self::from_u8(value) {
             AchievementStatusEnum::NotAllowed => String::from("not_allowed"),
             AchievementStatusEnum::Allowed => String::from("allowed"),
-            _ => panic!("Invalid value for AchievementStatusEnum"),
         }
     }
 }
@@ -88,11 +152,11 @@ pub struct PrincipalStorable(pub Principal);
 pub struct AchievementStatus(pub u8);
 
 impl Storable for PrincipalStorable {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(Principal::from_bytes(bytes))
     }
 
@@ -103,11 +167,11 @@ impl Storable for PrincipalStorable {
 }
 
 impl Storable for AchievementStatus {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(u8::from_bytes(bytes))
     }
 
@@ -117,16 +181,168 @@ impl Storable for AchievementStatus {
     };
 }
 
+/// Records a change of the pause switch of the achievement canister.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct PauseEvent {
+    pub paused: bool,
+    pub changed_by: Principal,
+    pub changed_at: u64
+}
+
+impl Storable for PauseEvent {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_VALUE_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// Limits applied to threshold ECDSA signing.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct SigningLimits {
+    pub cooldown_seconds: u64,
+    pub max_signatures_per_principal_per_day: u64,
+    pub daily_cycles_budget: u64,
+    pub signature_ttl_seconds: u64
+}
+
+impl Default for SigningLimits {
+    fn default() -> Self {
+        Self {
+            cooldown_seconds: 60,
+            max_signatures_per_principal_per_day: 5,
+            daily_cycles_budget: 3_000_000_000_000,
+            signature_ttl_seconds: 7 * 24 * 60 * 60
+        }
+    }
+}
+
+/// The signatures requested by a principal on a given day.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct SigningUsage {
+    pub day: u64,
+    pub count: u64,
+    pub last_signed_at: u64
+}
+
+/// The cycles spent on signing on a given day.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct SigningSpend {
+    pub day: u64,
+    pub cycles: u64,
+    pub total_cycles: u64
+}
+
+/// Records the identity wallet a hash was signed for and when.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct SignatureRecord {
+    pub identity_wallet: Principal,
+    pub signed_at: u64
+}
+
+macro_rules! impl_candid_storable {
+    ($($t:ty),*) => {
+        $(
+            impl Storable for $t {
+                fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+                    Cow::Owned(Encode!(self).unwrap())
+                }
+
+                fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+                    Decode!(bytes.as_ref(), Self).unwrap()
+                }
+
+                const BOUND: Bound = Bound::Bounded {
+                    max_size: MAX_VALUE_SIZE,
+                    is_fixed_size: false,
+                };
+            }
+        )*
+    };
+}
+
+impl_candid_storable!(SigningLimits, SigningUsage, SigningSpend, SignatureRecord);
+
+/// Represents the number of failed calls of an operation for a reason.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct FailureCount {
+    pub operation: String,
+    pub reason: String,
+    pub count: u64
+}
+
+/// Represents the counters exported by the metrics endpoint.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct MetricCounters {
+    pub claims: u64,
+    pub signatures: u64,
+    pub failures: Vec<FailureCount>,
+    pub last_error_at: Option<u64>
+}
+
+impl Storable for MetricCounters {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// The number of entries of a stable map.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct MapSize {
+    pub name: String,
+    pub len: u64
+}
+
+/// The resource usage of the achievement canister.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CanisterStatus {
+    pub cycles_balance: u128,
+    pub low_cycles_threshold: u128,
+    pub stable_memory_bytes: u64,
+    pub map_sizes: Vec<MapSize>,
+    pub signing_spend: SigningSpend
+}
+
+/// An HTTP request received through the HTTP gateway.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+/// An HTTP response returned through the HTTP gateway.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
 /// Represents a signature.
 #[derive(CandidType, Serialize, Debug, Deserialize)]
 pub struct Signature(pub String);
 
 impl Storable for Signature {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(String::from_bytes(bytes))
     }
 
@@ -0,0 +1,693 @@
+//! This module exports the state of the reputation module and imports it into a fresh canister, for backups and moves.
+//!
+//! Unlike the other endpoints restricted to controllers, the backup endpoints are not open to admins:
+//! an export reveals the whole state, and an import replaces it, admins included.
+
+use candid::Encode;
+use ic_cdk::{query, update};
+use ic_stable_structures::{StableVec, Storable};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::access::is_controller;
+use crate::errors::{AccessError, BackupError, ReputationError};
+use crate::migrations::SCHEMA_VERSION;
+use crate::http::certify_all;
+use crate::state::{
+    ACHIEVEMENT_CANISTER_TO_BOOL, SUPPORTED_STANDARDS, TOKEN_ID_TO_ISSUANCE, ACHIEVEMENT_CANISTER_TO_WEIGHT,
+    PRINCIPAL_TO_REPUTATION, ACHIEVEMENT_CANISTER_TO_DECAY, ACHIEVEMENT_CANISTER_TO_METADATA,
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED, ACHIEVEMENT_CANISTER_TO_MODULE_HASH, ADMINS, RELEASED_TOKEN_IDS,
+    TOKEN_ID_TO_REVOCATION,
+    get_reputation_module_metadata, _update_canister_metadata, _change_account_achievement_status_to_issued, account_of,
+    _set_canister_permission, _import_issuance, _update_leaderboard
+};
+use crate::pause::{PAUSED, PAUSED_ACHIEVEMENTS, PAUSE_LOG, is_paused};
+use crate::status::{LOW_CYCLES_THRESHOLD, get_low_cycles_threshold};
+use crate::metrics::METRIC_COUNTERS;
+use crate::ecdsa::{get_ecdsa_key, _set_ecdsa_key};
+use crate::credential::PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL;
+use crate::storable::{Memory, StorablePrincipal, ModuleHash, AchievementWeight, PrincipalPair};
+use crate::types::{BackupSection, BackupRecords, BackupChunk, BackupSectionSize, BackupManifest};
+
+/// The version of the backup format produced by this build.
+pub const BACKUP_FORMAT_VERSION: u32 = 4;
+
+/// The maximum number of records returned by a single export call.
+const MAX_BACKUP_PAGE: u64 = 1000;
+
+/// The sections of a backup, in the order their checksums are folded and they should be imported.
+///
+/// Indexes derived from these sections (leaderboard, held achievements, account identifiers,
+/// holder counts, issuance counts and catalog) are not exported; they are maintained chunk by
+/// chunk while importing.
+const BACKUP_SECTIONS: [BackupSection; 20] = [
+    BackupSection::Metadata,
+    BackupSection::Admins,
+    BackupSection::Standards,
+    BackupSection::Permissions,
+    BackupSection::ModuleHashes,
+    BackupSection::AchievementMetadata,
+    BackupSection::Weights,
+    BackupSection::Decays,
+    BackupSection::Issuances,
+    BackupSection::RevokedTokens,
+    BackupSection::IssuedStatuses,
+    BackupSection::Reputations,
+    BackupSection::Credentials,
+    BackupSection::ReleasedTokenIds,
+    BackupSection::Paused,
+    BackupSection::PausedAchievements,
+    BackupSection::PauseLog,
+    BackupSection::LowCyclesThreshold,
+    BackupSection::MetricCounters,
+    BackupSection::EcdsaKey,
+];
+
+/// The import progress of a backup section.
+#[derive(Default)]
+struct SectionProgress {
+    imported: u64
+}
+
+/// The progress of the import in progress.
+struct ImportProgress {
+    manifest: BackupManifest,
+    sections: BTreeMap<BackupSection, SectionProgress>
+}
+
+thread_local! {
+    /// The progress of the import in progress, if any.
+    static IMPORT_IN_PROGRESS: RefCell<Option<ImportProgress>> = const { RefCell::new(None) };
+}
+
+/// Counts the records of a backup section.
+///
+/// # Arguments
+///
+/// * `section` - The backup section.
+///
+/// # Returns
+///
+/// * `u64` - The number of records of the section.
+fn section_len(section: BackupSection) -> u64 {
+    match section {
+        BackupSection::Metadata
+        | BackupSection::Paused
+        | BackupSection::LowCyclesThreshold
+        | BackupSection::MetricCounters
+        | BackupSection::EcdsaKey => 1,
+        BackupSection::Admins => ADMINS.with(|a| a.borrow().len()),
+        BackupSection::Standards => SUPPORTED_STANDARDS.with(|s| s.borrow().len()),
+        BackupSection::Permissions => ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| p.borrow().len()),
+        BackupSection::ModuleHashes => ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().len()),
+        BackupSection::AchievementMetadata => ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| m.borrow().len()),
+        BackupSection::Weights => ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| w.borrow().len()),
+        BackupSection::Decays => ACHIEVEMENT_CANISTER_TO_DECAY.with(|d| d.borrow().len()),
+        BackupSection::Issuances => TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow().len()),
+        BackupSection::RevokedTokens => TOKEN_ID_TO_REVOCATION.with(|r| r.borrow().len()),
+        BackupSection::IssuedStatuses => ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow().len()),
+        BackupSection::Reputations => PRINCIPAL_TO_REPUTATION.with(|r| r.borrow().len()),
+        BackupSection::Credentials => PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| c.borrow().len()),
+        BackupSection::ReleasedTokenIds => RELEASED_TOKEN_IDS.with(|r| r.borrow().len()),
+        BackupSection::PausedAchievements => PAUSED_ACHIEVEMENTS.with(|p| p.borrow().len()),
+        BackupSection::PauseLog => PAUSE_LOG.with(|l| l.borrow().len()),
+    }
+}
+
+/// Retrieves the section of a set of backup records.
+///
+/// # Arguments
+///
+/// * `records` - The backup records.
+///
+/// # Returns
+///
+/// * `(BackupSection, u64)` - The section of the records and their number.
+fn records_section(records: &BackupRecords) -> (BackupSection, u64) {
+    let (section, len) = match records {
+        BackupRecords::Metadata(r) => (BackupSection::Metadata, r.len()),
+        BackupRecords::Admins(r) => (BackupSection::Admins, r.len()),
+        BackupRecords::Standards(r) => (BackupSection::Standards, r.len()),
+        BackupRecords::Permissions(r) => (BackupSection::Permissions, r.len()),
+        BackupRecords::ModuleHashes(r) => (BackupSection::ModuleHashes, r.len()),
+        BackupRecords::AchievementMetadata(r) => (BackupSection::AchievementMetadata, r.len()),
+        BackupRecords::Weights(r) => (BackupSection::Weights, r.len()),
+        BackupRecords::Decays(r) => (BackupSection::Decays, r.len()),
+        BackupRecords::Issuances(r) => (BackupSection::Issuances, r.len()),
+        BackupRecords::RevokedTokens(r) => (BackupSection::RevokedTokens, r.len()),
+        BackupRecords::IssuedStatuses(r) => (BackupSection::IssuedStatuses, r.len()),
+        BackupRecords::Reputations(r) => (BackupSection::Reputations, r.len()),
+        BackupRecords::Credentials(r) => (BackupSection::Credentials, r.len()),
+        BackupRecords::ReleasedTokenIds(r) => (BackupSection::ReleasedTokenIds, r.len()),
+        BackupRecords::Paused(r) => (BackupSection::Paused, r.len()),
+        BackupRecords::PausedAchievements(r) => (BackupSection::PausedAchievements, r.len()),
+        BackupRecords::PauseLog(r) => (BackupSection::PauseLog, r.len()),
+        BackupRecords::LowCyclesThreshold(r) => (BackupSection::LowCyclesThreshold, r.len()),
+        BackupRecords::MetricCounters(r) => (BackupSection::MetricCounters, r.len()),
+        BackupRecords::EcdsaKey(r) => (BackupSection::EcdsaKey, r.len()),
+    };
+
+    (section, len as u64)
+}
+
+/// Reads a page of the records of a backup section.
+///
+/// # Arguments
+///
+/// * `section` - The backup section.
+/// * `offset` - The number of records to skip.
+/// * `limit` - The maximum number of records to read.
+///
+/// # Returns
+///
+/// * `BackupRecords` - The records of the page.
+fn read_section(section: BackupSection, offset: u64, limit: u64) -> BackupRecords {
+    let (offset, limit) = (offset as usize, limit as usize);
+
+    match section {
+        BackupSection::Metadata => BackupRecords::Metadata(
+            std::iter::once(get_reputation_module_metadata()).skip(offset).take(limit).collect()
+        ),
+        BackupSection::Admins => BackupRecords::Admins(ADMINS.with(|a| {
+            a.borrow().iter().skip(offset).take(limit).map(|(admin, _)| admin.0).collect()
+        })),
+        BackupSection::Standards => BackupRecords::Standards(SUPPORTED_STANDARDS.with(|s| {
+            s.borrow().iter().skip(offset).take(limit).collect()
+        })),
+        BackupSection::Permissions => BackupRecords::Permissions(ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| {
+            p.borrow().iter().skip(offset).take(limit).map(|(canister, permission)| (canister.0, permission.0)).collect()
+        })),
+        BackupSection::ModuleHashes => BackupRecords::ModuleHashes(ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| {
+            m.borrow().iter().skip(offset).take(limit).map(|(canister, hash)| (canister.0, hash.0)).collect()
+        })),
+        BackupSection::AchievementMetadata => BackupRecords::AchievementMetadata(ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| {
+            m.borrow().iter().skip(offset).take(limit).map(|(canister, metadata)| (canister.0, metadata)).collect()
+        })),
+        BackupSection::Weights => BackupRecords::Weights(ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| {
+            w.borrow().iter().skip(offset).take(limit).map(|(canister, weight)| (canister.0, weight.0)).collect()
+        })),
+        BackupSection::Decays => BackupRecords::Decays(ACHIEVEMENT_CANISTER_TO_DECAY.with(|d| {
+            d.borrow().iter().skip(offset).take(limit).map(|(canister, decay)| (canister.0, decay)).collect()
+        })),
+        BackupSection::Issuances => BackupRecords::Issuances(TOKEN_ID_TO_ISSUANCE.with(|t| {
+            t.borrow().iter().skip(offset).take(limit).collect()
+        })),
+        BackupSection::RevokedTokens => BackupRecords::RevokedTokens(TOKEN_ID_TO_REVOCATION.with(|r| {
+            r.borrow().iter().skip(offset).take(limit).collect()
+        })),
+        BackupSection::IssuedStatuses => BackupRecords::IssuedStatuses(ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| {
+            p.borrow().iter().skip(offset).take(limit).map(|(key, _)| (account_of(key.0, key.2), key.1)).collect()
+        })),
+        BackupSection::Reputations => BackupRecords::Reputations(PRINCIPAL_TO_REPUTATION.with(|r| {
+            r.borrow().iter().skip(offset).take(limit).map(|(principal, reputation)| (principal.0, reputation)).collect()
+        })),
+        BackupSection::Credentials => BackupRecords::Credentials(PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| {
+            c.borrow().iter().skip(offset).take(limit).map(|(pair, credential)| (pair.0, pair.1, credential)).collect()
+        })),
+        BackupSection::ReleasedTokenIds => BackupRecords::ReleasedTokenIds(RELEASED_TOKEN_IDS.with(|r| {
+            r.borrow().iter().skip(offset).take(limit).map(|(token_id, _)| token_id).collect()
+        })),
+        BackupSection::Paused => BackupRecords::Paused(
+            std::iter::once(is_paused()).skip(offset).take(limit).collect()
+        ),
+        BackupSection::PausedAchievements => BackupRecords::PausedAchievements(PAUSED_ACHIEVEMENTS.with(|p| {
+            p.borrow().iter().skip(offset).take(limit).map(|(achievement, _)| achievement.0).collect()
+        })),
+        BackupSection::PauseLog => BackupRecords::PauseLog(PAUSE_LOG.with(|l| {
+            l.borrow().iter().skip(offset).take(limit).collect()
+        })),
+        BackupSection::LowCyclesThreshold => BackupRecords::LowCyclesThreshold(
+            std::iter::once(get_low_cycles_threshold()).skip(offset).take(limit).collect()
+        ),
+        BackupSection::MetricCounters => BackupRecords::MetricCounters(
+            std::iter::once(METRIC_COUNTERS.with(|c| c.borrow().get().clone())).skip(offset).take(limit).collect()
+        ),
+        BackupSection::EcdsaKey => BackupRecords::EcdsaKey(
+            std::iter::once(get_ecdsa_key()).skip(offset).take(limit).collect()
+        ),
+    }
+}
+
+/// Computes the checksum of a backup chunk.
+///
+/// # Arguments
+///
+/// * `section` - The backup section of the chunk.
+/// * `offset` - The offset of the first record of the chunk.
+/// * `records` - The records of the chunk.
+///
+/// # Returns
+///
+/// * `String` - The SHA-256 of the candid encoding of the section, offset and records, in hexadecimal format.
+pub fn chunk_checksum(section: BackupSection, offset: u64, records: &BackupRecords) -> String {
+    let encoded = Encode!(&section, &offset, records).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    hex::encode(Sha256::digest(encoded))
+}
+
+/// Folds the checksum of a chunk into the checksum of the chunks of its section imported before it.
+///
+/// # Arguments
+///
+/// * `folded` - The folded checksum of the previous chunks of the section, all zeros for the first chunk.
+/// * `checksum` - The checksum of the chunk, in hexadecimal format.
+///
+/// # Returns
+///
+/// * `[u8; 32]` - The SHA-256 of the folded checksum followed by the checksum of the chunk.
+pub fn fold_checksum(folded: &[u8; 32], checksum: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(folded);
+    hasher.update(checksum.as_bytes());
+
+    hasher.finalize().into()
+}
+
+/// Computes the checksum of the whole backup of the current state.
+///
+/// The records of every section are read in chunks of `MAX_BACKUP_PAGE` records, as exported by
+/// `exportStateChunk` with the largest limit. The chunk checksums of a section are folded with
+/// `fold_checksum` starting from 32 zero bytes, and the folded checksums of every section are hashed
+/// in backup order.
+///
+/// # Returns
+///
+/// * `String` - The SHA-256 of the folded section checksums, in hexadecimal format.
+pub fn backup_checksum() -> String {
+    let mut hasher = Sha256::new();
+
+    for section in BACKUP_SECTIONS {
+        let mut folded = [0; 32];
+
+        for offset in (0..section_len(section)).step_by(MAX_BACKUP_PAGE as usize) {
+            let records = read_section(section, offset, MAX_BACKUP_PAGE);
+            folded = fold_checksum(&folded, &chunk_checksum(section, offset, &records));
+        }
+        hasher.update(folded);
+    }
+
+    hex::encode(hasher.finalize())
+}
+
+/// Exports the manifest of a full state backup.
+///
+/// The state should not change while it is exported, otherwise the section sizes and the checksum
+/// will not match the exported chunks.
+///
+/// # Returns
+///
+/// * `Result<BackupManifest, ReputationError>` - The format and schema versions, the section sizes and the checksum of the backup.
+#[query(name = "exportStateManifest")]
+pub fn export_state_manifest() -> Result<BackupManifest, ReputationError> {
+    if !is_controller() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    Ok(BackupManifest {
+        version: BACKUP_FORMAT_VERSION,
+        schema_version: SCHEMA_VERSION,
+        sections: BACKUP_SECTIONS.iter().map(|&section| BackupSectionSize {
+            section,
+            count: section_len(section)
+        }).collect(),
+        checksum: backup_checksum()
+    })
+}
+
+/// Exports a page of the records of a backup section.
+///
+/// # Arguments
+///
+/// * `section` - The backup section.
+/// * `offset` - The number of records to skip.
+/// * `limit` - The maximum number of records to return, capped at 1000.
+///
+/// # Returns
+///
+/// * `Result<BackupChunk, ReputationError>` - The records, their checksum and the offset of the next page, if any.
+#[query(name = "exportStateChunk")]
+pub fn export_state_chunk(section: BackupSection, offset: u64, limit: u64) -> Result<BackupChunk, ReputationError> {
+    if !is_controller() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    let limit = limit.min(MAX_BACKUP_PAGE);
+    let next_offset = offset.saturating_add(limit);
+    let records = read_section(section, offset, limit);
+
+    Ok(BackupChunk {
+        version: BACKUP_FORMAT_VERSION,
+        section,
+        offset,
+        checksum: chunk_checksum(section, offset, &records),
+        records,
+        next_offset: (next_offset < section_len(section)).then_some(next_offset)
+    })
+}
+
+/// Starts importing a state backup into the reputation module.
+///
+/// The reputation module must not hold any permission, issuance or reputation yet, and the backup
+/// must have been exported with the same stable-memory schema version. An import already in
+/// progress is never replaced; it is only dropped by finalizing it or by upgrading the canister.
+///
+/// # Arguments
+///
+/// * `manifest` - The manifest of the backup to import.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the operation.
+#[update(name = "beginStateImport")]
+pub fn begin_state_import(manifest: BackupManifest) -> Result<(), ReputationError> {
+    if !is_controller() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    if manifest.version != BACKUP_FORMAT_VERSION {
+        return Err(BackupError::UnsupportedVersion(manifest.version).into());
+    }
+
+    if manifest.schema_version != SCHEMA_VERSION {
+        return Err(BackupError::UnsupportedSchemaVersion(manifest.schema_version).into());
+    }
+
+    if IMPORT_IN_PROGRESS.with(|i| i.borrow().is_some()) {
+        return Err(BackupError::ImportInProgress.into());
+    }
+
+    let is_empty = [
+        BackupSection::Permissions,
+        BackupSection::Issuances,
+        BackupSection::IssuedStatuses,
+        BackupSection::Reputations
+    ].into_iter().all(|section| section_len(section) == 0);
+
+    if !is_empty {
+        return Err(BackupError::StateNotEmpty.into());
+    }
+
+    IMPORT_IN_PROGRESS.with(|i| *i.borrow_mut() = Some(ImportProgress {
+        manifest,
+        sections: BTreeMap::new()
+    }));
+
+    Ok(())
+}
+
+/// Writes an item at a position of a stable vector, appending it at the end.
+///
+/// # Arguments
+///
+/// * `vec` - The stable vector.
+/// * `index` - The position of the item.
+/// * `item` - The item to write.
+fn write_at<T: Storable>(vec: &StableVec<T, Memory>, index: u64, item: &T) {
+    if index < vec.len() {
+        vec.set(index, item);
+    } else {
+        vec.push(item).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+    }
+}
+
+/// Writes imported records to the state, together with the indexes derived from them.
+///
+/// # Arguments
+///
+/// * `offset` - The offset of the first record within its section.
+/// * `records` - The records to write.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+fn write_records(offset: u64, records: BackupRecords) -> Result<(), ReputationError> {
+    match records {
+        BackupRecords::Metadata(records) => {
+            for metadata in records {
+                _update_canister_metadata(metadata)?;
+            }
+        },
+        BackupRecords::Admins(records) => ADMINS.with(|a| {
+            let mut admins = a.borrow_mut();
+            records.into_iter().for_each(|admin| { admins.insert(StorablePrincipal(admin), ()); });
+        }),
+        BackupRecords::Standards(records) => SUPPORTED_STANDARDS.with(|s| {
+            let standards = s.borrow_mut();
+            (offset..).zip(&records).for_each(|(index, standard)| write_at(&standards, index, standard));
+        }),
+        BackupRecords::Permissions(records) => {
+            for (canister, permission) in records {
+                _set_canister_permission(canister, permission)?;
+            }
+        },
+        BackupRecords::ModuleHashes(records) => ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| {
+            let mut hashes = m.borrow_mut();
+            records.into_iter().for_each(|(canister, hash)| { hashes.insert(StorablePrincipal(canister), ModuleHash(hash)); });
+        }),
+        BackupRecords::AchievementMetadata(records) => ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| {
+            let mut metadata = m.borrow_mut();
+            records.into_iter().for_each(|(canister, m)| { metadata.insert(StorablePrincipal(canister), m); });
+        }),
+        BackupRecords::Weights(records) => ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| {
+            let mut weights = w.borrow_mut();
+            records.into_iter().for_each(|(canister, weight)| { weights.insert(StorablePrincipal(canister), AchievementWeight(weight)); });
+        }),
+        BackupRecords::Decays(records) => ACHIEVEMENT_CANISTER_TO_DECAY.with(|d| {
+            let mut decays = d.borrow_mut();
+            records.into_iter().for_each(|(canister, decay)| { decays.insert(StorablePrincipal(canister), decay); });
+        }),
+        BackupRecords::Issuances(records) => {
+            records.into_iter().for_each(|(token_id, issuance)| _import_issuance(token_id, issuance));
+        },
+        BackupRecords::RevokedTokens(records) => TOKEN_ID_TO_REVOCATION.with(|r| {
+            let mut revocations = r.borrow_mut();
+            records.into_iter().for_each(|(token_id, revocation)| { revocations.insert(token_id, revocation); });
+        }),
+        BackupRecords::IssuedStatuses(records) => {
+            for (account, achievement) in records {
+                _change_account_achievement_status_to_issued(&account, achievement)?;
+            }
+        },
+        BackupRecords::Reputations(records) => {
+            for (principal, reputation) in records {
+                let total = reputation.total;
+                let previous = PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(principal), reputation));
+                _update_leaderboard(principal, previous.map(|previous| previous.total), Some(total));
+            }
+        },
+        BackupRecords::Credentials(records) => PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| {
+            let mut credentials = c.borrow_mut();
+            records.into_iter().for_each(|(identity_wallet, achievement, credential)| {
+                credentials.insert(PrincipalPair(identity_wallet, achievement), credential);
+            });
+        }),
+        BackupRecords::ReleasedTokenIds(records) => RELEASED_TOKEN_IDS.with(|r| {
+            let mut released = r.borrow_mut();
+            records.into_iter().for_each(|token_id| { released.insert(token_id, ()); });
+        }),
+        BackupRecords::Paused(records) => {
+            for paused in records {
+                PAUSED.with(|p| p.borrow_mut().set(paused))
+                    .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+            }
+        },
+        BackupRecords::PausedAchievements(records) => PAUSED_ACHIEVEMENTS.with(|p| {
+            let mut paused = p.borrow_mut();
+            records.into_iter().for_each(|achievement| { paused.insert(StorablePrincipal(achievement), ()); });
+        }),
+        BackupRecords::PauseLog(records) => PAUSE_LOG.with(|l| {
+            let log = l.borrow_mut();
+            (offset..).zip(&records).for_each(|(index, event)| write_at(&log, index, event));
+        }),
+        BackupRecords::LowCyclesThreshold(records) => {
+            for threshold in records {
+                LOW_CYCLES_THRESHOLD.with(|t| t.borrow_mut().set(threshold))
+                    .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+            }
+        },
+        BackupRecords::MetricCounters(records) => {
+            for counters in records {
+                METRIC_COUNTERS.with(|c| c.borrow_mut().set(counters))
+                    .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+            }
+        },
+        BackupRecords::EcdsaKey(records) => records.into_iter().for_each(_set_ecdsa_key),
+    }
+
+    Ok(())
+}
+
+/// Imports a chunk of a state backup.
+///
+/// The chunks of a section must be imported in order. Importing a chunk again is a no-op, so
+/// that a chunk whose reply was lost can safely be sent again.
+///
+/// # Arguments
+///
+/// * `chunk` - The chunk exported by `exportStateChunk`.
+///
+/// # Returns
+///
+/// * `Result<u64, ReputationError>` - The number of imported records, `0` if the chunk was already imported.
+#[update(name = "importStateChunk")]
+pub fn import_state_chunk(chunk: BackupChunk) -> Result<u64, ReputationError> {
+    if !is_controller() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    if chunk.version != BACKUP_FORMAT_VERSION {
+        return Err(BackupError::UnsupportedVersion(chunk.version).into());
+    }
+
+    let (section, len) = records_section(&chunk.records);
+
+    if section != chunk.section {
+        return Err(BackupError::SectionMismatch.into());
+    }
+
+    if chunk_checksum(chunk.section, chunk.offset, &chunk.records) != chunk.checksum {
+        return Err(BackupError::ChecksumMismatch.into());
+    }
+
+    let imported = IMPORT_IN_PROGRESS.with(|i| {
+        i.borrow().as_ref().map(|progress| progress.sections.get(&section).map_or(0, |s| s.imported))
+    }).ok_or(ReputationError::from(BackupError::ImportNotStarted))?;
+
+    if chunk.offset.saturating_add(len) <= imported {
+        return Ok(0);
+    }
+
+    if chunk.offset != imported {
+        return Err(BackupError::UnexpectedOffset { section, expected: imported }.into());
+    }
+
+    write_records(chunk.offset, chunk.records)?;
+
+    IMPORT_IN_PROGRESS.with(|i| {
+        if let Some(progress) = i.borrow_mut().as_mut() {
+            progress.sections.entry(section).or_default().imported += len;
+        }
+    });
+
+    Ok(len)
+}
+
+/// Finalizes the import of a state backup.
+///
+/// This function checks that every section of the manifest was fully imported, that the checksum
+/// of the imported state matches the checksum of the manifest, and certifies the imported state.
+/// If a section is incomplete, the import stays open, so that missing chunks can still be imported.
+///
+/// # Returns
+///
+/// * `Result<String, ReputationError>` - The checksum of the backup, computed by `backup_checksum`.
+#[update(name = "finalizeStateImport")]
+pub fn finalize_state_import() -> Result<String, ReputationError> {
+    if !is_controller() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    let (expected_checksum, standards, pause_log) = IMPORT_IN_PROGRESS.with(|i| {
+        let progress = i.borrow();
+        let progress = progress.as_ref().ok_or(ReputationError::from(BackupError::ImportNotStarted))?;
+        let imported = |section| progress.sections.get(&section).map_or(0, |s: &SectionProgress| s.imported);
+
+        for expected in &progress.manifest.sections {
+            if imported(expected.section) != expected.count {
+                return Err(BackupError::SectionIncomplete {
+                    section: expected.section,
+                    imported: imported(expected.section),
+                    expected: expected.count
+                }.into());
+            }
+        }
+
+        Ok::<_, ReputationError>((
+            progress.manifest.checksum.clone(),
+            imported(BackupSection::Standards),
+            imported(BackupSection::PauseLog)
+        ))
+    })?;
+
+    SUPPORTED_STANDARDS.with(|s| while s.borrow().len() > standards { s.borrow_mut().pop(); });
+    PAUSE_LOG.with(|l| while l.borrow().len() > pause_log { l.borrow_mut().pop(); });
+
+    let checksum = backup_checksum();
+    if checksum != expected_checksum {
+        return Err(BackupError::ChecksumMismatch.into());
+    }
+
+    IMPORT_IN_PROGRESS.with(|i| *i.borrow_mut() = None);
+    certify_all();
+
+    Ok(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::{Decode, Principal};
+
+    fn records() -> BackupRecords {
+        BackupRecords::Admins(vec![Principal::from_slice(&[1; 10]), Principal::from_slice(&[2; 10])])
+    }
+
+    #[test]
+    fn chunk_checksum_survives_a_candid_round_trip() {
+        let records = records();
+        let chunk = BackupChunk {
+            version: BACKUP_FORMAT_VERSION,
+            section: BackupSection::Admins,
+            offset: 0,
+            checksum: chunk_checksum(BackupSection::Admins, 0, &records),
+            records,
+            next_offset: None
+        };
+
+        let decoded = Decode!(&Encode!(&chunk).unwrap(), BackupChunk).unwrap();
+
+        assert_eq!(chunk_checksum(decoded.section, decoded.offset, &decoded.records), chunk.checksum);
+        assert_eq!(records_section(&decoded.records), (BackupSection::Admins, 2));
+    }
+
+    #[test]
+    fn chunk_checksum_covers_the_section_and_offset() {
+        let checksum = chunk_checksum(BackupSection::Admins, 0, &records());
+
+        assert_ne!(chunk_checksum(BackupSection::Admins, 1000, &records()), checksum);
+        assert_ne!(chunk_checksum(BackupSection::PausedAchievements, 0, &records()), checksum);
+    }
+
+    #[test]
+    fn fold_checksum_depends_on_chunk_order() {
+        let first = chunk_checksum(BackupSection::Admins, 0, &records());
+        let second = chunk_checksum(BackupSection::Admins, 2, &records());
+
+        let in_order = fold_checksum(&fold_checksum(&[0; 32], &first), &second);
+        let swapped = fold_checksum(&fold_checksum(&[0; 32], &second), &first);
+
+        assert_eq!(in_order, fold_checksum(&fold_checksum(&[0; 32], &first), &second));
+        assert_ne!(in_order, swapped);
+    }
+
+    #[test]
+    fn backup_checksum_folds_the_exported_chunks() {
+        let empty = backup_checksum();
+        ADMINS.with(|a| a.borrow_mut().insert(StorablePrincipal(Principal::from_slice(&[1; 10])), ()));
+
+        let mut hasher = Sha256::new();
+        for section in BACKUP_SECTIONS {
+            let chunk = read_section(section, 0, MAX_BACKUP_PAGE);
+            let folded = if section_len(section) == 0 {
+                [0; 32]
+            } else {
+                fold_checksum(&[0; 32], &chunk_checksum(section, 0, &chunk))
+            };
+            hasher.update(folded);
+        }
+
+        assert_eq!(backup_checksum(), hex::encode(hasher.finalize()));
+        assert_ne!(backup_checksum(), empty);
+    }
+}
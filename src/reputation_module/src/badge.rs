@@ -0,0 +1,188 @@
+//! This module renders the SVG badge of every issued achievement token.
+
+use sha2::{Digest, Sha256};
+
+use crate::state::{get_issuance, get_cached_achievement_metadata, get_reputation_module_metadata};
+use crate::utils::format_date;
+
+/// The domain the canisters are served from by the boundary nodes.
+pub const CANISTER_DOMAIN: &str = "icp0.io";
+
+/// The maximum number of characters of a line of text on a badge.
+const MAX_LINE_LENGTH: usize = 28;
+
+/// Retrieves the path the badge of a token is served at.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+///
+/// # Returns
+///
+/// * `String` - The path of the badge.
+pub fn badge_path(token_id: u128) -> String {
+    format!("/badges/{}.svg", token_id)
+}
+
+/// Retrieves the URL the badge of a token is served at, used as the logo of the token.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+///
+/// # Returns
+///
+/// * `String` - The URL of the badge.
+pub fn badge_url(token_id: u128) -> String {
+    format!("https://{}.{}{}", ic_cdk::api::id().to_text(), CANISTER_DOMAIN, badge_path(token_id))
+}
+
+/// Parses the token id of a badge path.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request.
+///
+/// # Returns
+///
+/// * `Option<u128>` - The token id, if the path is a badge path.
+pub fn parse_badge_path(path: &str) -> Option<u128> {
+    path.strip_prefix("/badges/")?.strip_suffix(".svg")?.parse().ok()
+}
+
+/// Escapes the characters with a special meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Shortens a line of text to fit on a badge.
+fn fit(text: &str) -> String {
+    if text.chars().count() <= MAX_LINE_LENGTH {
+        return String::from(text);
+    }
+
+    format!("{}…", text.chars().take(MAX_LINE_LENGTH - 1).collect::<String>().trim_end())
+}
+
+/// Renders the SVG badge of a token.
+///
+/// The badge shows the name of the achievement, the ordinal of the token among the tokens of the
+/// achievement, the issuance date and the issuer. Its color is derived from the achievement principal.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+///
+/// # Returns
+///
+/// * `Option<String>` - The SVG document, or `None` if the token was not issued or was revoked.
+pub fn render_badge(token_id: u128) -> Option<String> {
+    let issuance = get_issuance(token_id)?;
+    let name = get_cached_achievement_metadata(issuance.achievement)
+        .map(|metadata| metadata.achievement_name)
+        .unwrap_or_else(|| issuance.achievement.to_text());
+    let issuer = get_reputation_module_metadata().issuer_name;
+    let ordinal = issuance.ordinal.map(|ordinal| format!("#{}", ordinal)).unwrap_or_default();
+    let hue = Sha256::digest(issuance.achievement.as_slice())[0] as u32 * 360 / 256;
+
+    Some(format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="320" height="320" viewBox="0 0 320 320">"#,
+            r#"<circle cx="160" cy="160" r="152" fill="hsl({hue},60%,40%)"/>"#,
+            r#"<circle cx="160" cy="160" r="136" fill="none" stroke="hsl({hue},60%,80%)" stroke-width="4"/>"#,
+            r#"<g fill="white" font-family="sans-serif" text-anchor="middle">"#,
+            r#"<text x="160" y="110" font-size="40" font-weight="bold">{ordinal}</text>"#,
+            r#"<text x="160" y="160" font-size="20" font-weight="bold">{name}</text>"#,
+            r#"<text x="160" y="195" font-size="16">{date}</text>"#,
+            r#"<text x="160" y="235" font-size="13">Issued by {issuer}</text>"#,
+            r#"</g></svg>"#
+        ),
+        hue = hue,
+        ordinal = ordinal,
+        name = escape(&fit(&name)),
+        date = format_date(issuance.issued_at),
+        issuer = escape(&fit(&issuer))
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    use crate::state::{ACHIEVEMENT_CANISTER_TO_METADATA, METADATA, TOKEN_ID_TO_ISSUANCE};
+    use crate::storable::{Issuance, StorablePrincipal};
+    use crate::types::AchievementMetadata;
+
+    fn issue(token_id: u128, achievement_name: &str, issuer_name: &str) {
+        let achievement = Principal::from_slice(&[9]);
+
+        ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| m.borrow_mut().insert(StorablePrincipal(achievement), AchievementMetadata {
+            achievement_name: String::from(achievement_name),
+            achievement_description: String::new()
+        }));
+        METADATA.with(|m| {
+            let mut metadata = m.borrow().get().clone();
+            metadata.issuer_name = String::from(issuer_name);
+            m.borrow_mut().set(metadata).ok();
+        });
+        TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().insert(token_id, Issuance {
+            owner: Principal::from_slice(&[1]),
+            subaccount: None,
+            achievement,
+            issued_at: 1_709_164_800_000_000_000,
+            ordinal: Some(3)
+        }));
+    }
+
+    #[test]
+    fn badge_shows_the_ordinal_name_date_and_issuer() {
+        issue(1, "Early Adopter", "Issuer");
+
+        let badge = render_badge(1).unwrap();
+
+        assert!(badge.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(badge.ends_with("</g></svg>"));
+        assert!(badge.contains(">#3</text>"));
+        assert!(badge.contains(">Early Adopter</text>"));
+        assert!(badge.contains(">2024-02-29</text>"));
+        assert!(badge.contains(">Issued by Issuer</text>"));
+        assert!(render_badge(2).is_none());
+    }
+
+    #[test]
+    fn badge_escapes_xml_special_characters() {
+        issue(1, "<Tom & \"Jerry's\">", "A&B");
+
+        let badge = render_badge(1).unwrap();
+
+        assert!(badge.contains(">&lt;Tom &amp; &quot;Jerry&apos;s&quot;&gt;</text>"));
+        assert!(badge.contains(">Issued by A&amp;B</text>"));
+        assert_eq!(escape("<&\"'>"), "&lt;&amp;&quot;&apos;&gt;");
+    }
+
+    #[test]
+    fn long_names_are_shortened_to_fit() {
+        let long_name = "An achievement with a name far too long for a badge";
+
+        assert_eq!(fit("Short"), "Short");
+        assert_eq!(fit(&"x".repeat(MAX_LINE_LENGTH)), "x".repeat(MAX_LINE_LENGTH));
+        assert_eq!(fit(long_name), "An achievement with a name…");
+        assert_eq!(fit(&"é".repeat(MAX_LINE_LENGTH + 1)).chars().count(), MAX_LINE_LENGTH);
+
+        issue(1, long_name, "Issuer");
+        assert!(render_badge(1).unwrap().contains(">An achievement with a name…</text>"));
+    }
+
+    #[test]
+    fn badge_path_round_trips_through_its_token_id() {
+        assert_eq!(parse_badge_path(&badge_path(42)), Some(42));
+        assert_eq!(parse_badge_path(&badge_path(u128::MAX)), Some(u128::MAX));
+        assert_eq!(parse_badge_path("/badges/x.svg"), None);
+        assert_eq!(parse_badge_path("/badges/42.png"), None);
+    }
+}
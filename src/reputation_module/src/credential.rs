@@ -0,0 +1,352 @@
+//! This module exports issued achievements as W3C Verifiable Credentials signed with threshold ECDSA.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use candid::Principal;
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::badge::CANISTER_DOMAIN;
+use crate::ecdsa::{get_cached_public_key, public_key, sign};
+use crate::errors::{CredentialError, EligibilityError, ReputationError};
+use crate::guard::PendingGuard;
+use crate::http::certify_did_document;
+use crate::metrics::observe_credential;
+use crate::pause::ensure_not_paused;
+use crate::state::{MEMORY_MANAGER, get_issuance_of, get_cached_achievement_metadata, get_reputation_module_metadata};
+use crate::status::ensure_cycles_available;
+use crate::storable::{Memory, PrincipalPair, IssuedCredential};
+use crate::utils::format_datetime;
+
+/// The path the DID document of the issuer is served at, as resolved by the `did:web` method.
+pub const DID_DOCUMENT_PATH: &str = "/.well-known/did.json";
+
+/// The fragment identifying the signing key within the DID document.
+const KEY_FRAGMENT: &str = "#key-1";
+
+/// The time a credential is valid for after it is signed, in nanoseconds.
+const CREDENTIAL_VALIDITY: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+thread_local! {
+    pub(crate) static PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL: RefCell<StableBTreeMap<PrincipalPair, IssuedCredential, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))),
+        )
+    );
+}
+
+/// Retrieves the DID of the reputation module, the issuer of the credentials.
+///
+/// # Returns
+///
+/// * `String` - The `did:web` identifier of the domain the reputation module is served from.
+pub fn issuer_did() -> String {
+    format!("did:web:{}.{}", ic_cdk::api::id().to_text(), CANISTER_DOMAIN)
+}
+
+/// Deletes the cached credential of an achievement held by an identity wallet.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+pub fn _forget_credential(identity_wallet: Principal, achievement: Principal) {
+    PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| c.borrow_mut().remove(&PrincipalPair(identity_wallet, achievement)));
+}
+
+/// Retrieves the DID of a principal, the subject of a credential.
+fn subject_did(principal: Principal) -> String {
+    format!("did:icp:{}", principal.to_text())
+}
+
+/// Renders the DID document of the issuer.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The DID document, or `None` until the public key of the reputation module is fetched.
+pub fn render_did_document() -> Option<Value> {
+    did_document(&issuer_did(), &get_cached_public_key()?)
+}
+
+/// Renders a DID document carrying a secp256k1 public key.
+///
+/// # Arguments
+///
+/// * `did` - The DID the document describes.
+/// * `public_key` - The SEC1 encoded public key.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The DID document, or `None` if the public key is invalid.
+fn did_document(did: &str, public_key: &[u8]) -> Option<Value> {
+    let point = k256::PublicKey::from_sec1_bytes(public_key).ok()?.to_encoded_point(false);
+    let key_id = format!("{}{}", did, KEY_FRAGMENT);
+
+    Some(json!({
+        "@context": ["https://www.w3.org/ns/did/v1", "https://w3id.org/security/suites/secp256k1-2019/v1"],
+        "id": did,
+        "verificationMethod": [{
+            "id": key_id,
+            "type": "EcdsaSecp256k1VerificationKey2019",
+            "controller": did,
+            "publicKeyJwk": {
+                "kty": "EC",
+                "crv": "secp256k1",
+                "x": BASE64_URL.encode(point.x()?),
+                "y": BASE64_URL.encode(point.y()?)
+            }
+        }],
+        "assertionMethod": [key_id]
+    }))
+}
+
+/// Retrieves the DID document of the issuer of the credentials.
+///
+/// The document is also served at `/.well-known/did.json`, so that the `did:web` identifier of the issuer resolves.
+///
+/// # Returns
+///
+/// * `Option<String>` - The JSON DID document, or `None` until the public key of the reputation module is fetched.
+#[query(name = "getIssuerDidDocument")]
+pub fn get_issuer_did_document() -> Option<String> {
+    render_did_document().map(|document| document.to_string())
+}
+
+/// Fetches the public key of the reputation module once, so that the DID document can be served.
+///
+/// Calls cannot be made during install and upgrade, so the key is fetched by a one-off timer.
+pub fn _start_public_key_fetch() {
+    if get_cached_public_key().is_some() {
+        return;
+    }
+
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(async {
+        match public_key().await {
+            Ok(_) => certify_did_document(),
+            Err(err) => ic_cdk::println!("Unable to fetch the public key: {:?}", err)
+        }
+    }));
+}
+
+/// Encodes a JSON document as a JWT segment.
+fn encode_segment(value: &Value) -> String {
+    BASE64_URL.encode(value.to_string())
+}
+
+/// Builds the signing input of a JWT, the encoded header and payload joined by a dot.
+fn signing_input(header: &Value, payload: &Value) -> String {
+    format!("{}.{}", encode_segment(header), encode_segment(payload))
+}
+
+/// Appends a signature, the 64-byte `r || s` returned by `sign_with_ecdsa`, to the signing input of a JWT.
+fn compact_jwt(signing_input: &str, signature: &[u8]) -> String {
+    format!("{}.{}", signing_input, BASE64_URL.encode(signature))
+}
+
+/// Issues a W3C Verifiable Credential proving that the caller holds an achievement.
+///
+/// The credential is a JWT signed with `ES256K` by the threshold ECDSA key of the reputation module.
+/// Its issuer is the `did:web` identifier of the reputation module, whose DID document carries the
+/// public key, and its subject is the `did:icp` identifier of the caller. The credential expires 30 days
+/// after it is signed. The credential of a token is returned again on later calls until half of its validity
+/// has elapsed, so that holders cannot drain the cycles of the module; it is deleted when the achievement is revoked.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<String, ReputationError>` - The credential in the compact JWT format.
+///
+/// # Example
+///
+/// ```bash
+/// dfx --identity pa_identity_wallet canister call reputation_module issueCredential "(principal \"$(dfx canister id achievement)\")"
+/// ```
+#[update(name = "issueCredential")]
+pub async fn issue_credential(achievement: Principal) -> Result<String, ReputationError> {
+    let result = _issue_credential(achievement).await;
+    observe_credential(&result);
+
+    result.map(|(credential, _)| credential)
+}
+
+/// Issues a credential, as described in `issue_credential`.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(String, bool), ReputationError>` - The credential, and `true` if it was newly signed.
+async fn _issue_credential(achievement: Principal) -> Result<(String, bool), ReputationError> {
+    let caller = ic_cdk::api::caller();
+    let (token_id, issuance) = get_issuance_of(caller, achievement)
+        .ok_or(ReputationError::from(EligibilityError::NotIssued))?;
+    let key = PrincipalPair(caller, achievement);
+    let now = ic_cdk::api::time();
+
+    if let Some(issued) = PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| c.borrow().get(&key)) {
+        if issued.token_id == token_id && issued.expires_at.is_some_and(|at| at > now + CREDENTIAL_VALIDITY / 2) {
+            return Ok((issued.credential, false));
+        }
+    }
+
+    ensure_not_paused(achievement)?;
+    ensure_cycles_available()?;
+    let _pending = PendingGuard::credential(caller, achievement)?;
+
+    let public_key_cached = get_cached_public_key().is_some();
+    public_key().await?;
+    if !public_key_cached {
+        certify_did_document();
+    }
+    render_did_document().ok_or(ReputationError::from(CredentialError::InvalidPublicKey))?;
+
+    let did = issuer_did();
+    let reputation_metadata = get_reputation_module_metadata();
+    let metadata = get_cached_achievement_metadata(achievement);
+    let expires_at = now + CREDENTIAL_VALIDITY;
+
+    let header = json!({
+        "alg": "ES256K",
+        "typ": "JWT",
+        "kid": format!("{}{}", did, KEY_FRAGMENT)
+    });
+    let payload = json!({
+        "iss": did,
+        "sub": subject_did(caller),
+        "jti": format!("https://{}.{}/credentials/{}", ic_cdk::api::id().to_text(), CANISTER_DOMAIN, token_id),
+        "nbf": issuance.issued_at / 1_000_000_000,
+        "iat": now / 1_000_000_000,
+        "exp": expires_at / 1_000_000_000,
+        "vc": {
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": ["VerifiableCredential", "AchievementCredential"],
+            "issuer": {
+                "id": did,
+                "name": reputation_metadata.issuer_name
+            },
+            "issuanceDate": format_datetime(issuance.issued_at),
+            "expirationDate": format_datetime(expires_at),
+            "credentialSubject": {
+                "id": subject_did(caller),
+                "achievement": {
+                    "id": achievement.to_text(),
+                    "name": metadata.as_ref().map(|m| m.achievement_name.clone()),
+                    "description": metadata.map(|m| m.achievement_description)
+                },
+                "collection": reputation_metadata.achievement_collection.to_text(),
+                "tokenId": token_id.to_string(),
+                "ordinal": issuance.ordinal
+            }
+        }
+    });
+
+    let signing_input = signing_input(&header, &payload);
+    let signature = sign(Sha256::digest(signing_input.as_bytes()).into()).await?;
+
+    if get_issuance_of(caller, achievement).map(|(id, _)| id) != Some(token_id) {
+        return Err(EligibilityError::NotIssued.into());
+    }
+
+    let credential = compact_jwt(&signing_input, &signature);
+    PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|c| c.borrow_mut().insert(key, IssuedCredential {
+        token_id,
+        credential: credential.clone(),
+        expires_at: Some(expires_at)
+    }));
+
+    Ok((credential, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+    use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+
+    const ISSUER: &str = "did:web:ryjl3-tyaaa-aaaaa-aaaba-cai.icp0.io";
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32]).unwrap()
+    }
+
+    fn decode_segment(segment: &str) -> Value {
+        serde_json::from_slice(&BASE64_URL.decode(segment).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn segments_are_unpadded_base64url() {
+        let value = json!({"sub": "??>"});
+        let segment = encode_segment(&value);
+
+        assert_eq!(segment, "eyJzdWIiOiI_Pz4ifQ");
+        assert_eq!(decode_segment(&segment), value);
+    }
+
+    #[test]
+    fn subjects_are_did_icp_identifiers() {
+        let principal = Principal::from_text("2vxsx-fae").unwrap();
+
+        assert_eq!(subject_did(principal), "did:icp:2vxsx-fae");
+    }
+
+    #[test]
+    fn did_documents_carry_the_public_key_as_a_jwk() {
+        let public_key = signing_key().verifying_key().to_encoded_point(true);
+        let document = did_document(ISSUER, public_key.as_bytes()).unwrap();
+        let key_id = format!("{}#key-1", ISSUER);
+        let method = &document["verificationMethod"][0];
+
+        assert_eq!(document["id"], ISSUER);
+        assert_eq!(document["assertionMethod"], json!([key_id]));
+        assert_eq!(method["id"], key_id);
+        assert_eq!(method["type"], "EcdsaSecp256k1VerificationKey2019");
+        assert_eq!(method["controller"], ISSUER);
+        assert_eq!(method["publicKeyJwk"]["kty"], "EC");
+        assert_eq!(method["publicKeyJwk"]["crv"], "secp256k1");
+
+        let uncompressed = signing_key().verifying_key().to_encoded_point(false);
+        assert_eq!(method["publicKeyJwk"]["x"], BASE64_URL.encode(uncompressed.x().unwrap()));
+        assert_eq!(method["publicKeyJwk"]["y"], BASE64_URL.encode(uncompressed.y().unwrap()));
+        assert!(did_document(ISSUER, &[4; 65]).is_none());
+    }
+
+    #[test]
+    fn credentials_verify_against_the_did_document() {
+        let header = json!({"alg": "ES256K", "typ": "JWT", "kid": format!("{}{}", ISSUER, KEY_FRAGMENT)});
+        let payload = json!({"iss": ISSUER, "sub": "did:icp:2vxsx-fae"});
+        let input = signing_input(&header, &payload);
+        let signature: Signature = signing_key().sign_prehash(&Sha256::digest(input.as_bytes())).unwrap();
+        let credential = compact_jwt(&input, &signature.to_bytes());
+
+        let segments: Vec<&str> = credential.split('.').collect();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(decode_segment(segments[0]), header);
+        assert_eq!(decode_segment(segments[1]), payload);
+
+        let signature = BASE64_URL.decode(segments[2]).unwrap();
+        assert_eq!(signature.len(), 64);
+
+        let public_key = signing_key().verifying_key().to_encoded_point(true);
+        let jwk = &did_document(ISSUER, public_key.as_bytes()).unwrap()["verificationMethod"][0]["publicKeyJwk"];
+        let mut point = vec![4];
+        point.extend(BASE64_URL.decode(jwk["x"].as_str().unwrap()).unwrap());
+        point.extend(BASE64_URL.decode(jwk["y"].as_str().unwrap()).unwrap());
+
+        let verifying_key = VerifyingKey::from_sec1_bytes(&point).unwrap();
+        let signing_input = format!("{}.{}", segments[0], segments[1]);
+        assert!(verifying_key
+            .verify_prehash(&Sha256::digest(signing_input.as_bytes()), &Signature::try_from(signature.as_slice()).unwrap())
+            .is_ok());
+    }
+}
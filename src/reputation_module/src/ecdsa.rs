@@ -0,0 +1,190 @@
+//! This module signs with the threshold ECDSA key of the reputation module and caches its public key.
+
+use candid::{CandidType, Principal};
+use ic_cdk::query;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::errors::{CallError, CredentialError, ReputationError};
+use crate::state::MEMORY_MANAGER;
+use crate::storable::{EcdsaKeyIds, Memory};
+
+/// The cycles attached to every `sign_with_ecdsa` call.
+pub const SIGN_WITH_ECDSA_CYCLES: u64 = 30_000_000_000;
+
+// The management canister types down to `to_key_id` are kept identical to those of the achievement canister.
+#[derive(CandidType, Serialize, Debug)]
+struct ECDSAPublicKey {
+    pub canister_id: Option<Principal>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: EcdsaKeyId,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct ECDSAPublicKeyReply {
+    pub public_key: Vec<u8>,
+    pub chain_code: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Debug)]
+struct SignWithECDSA {
+    pub message_hash: Vec<u8>,
+    pub derivation_path: Vec<Vec<u8>>,
+    pub key_id: EcdsaKeyId,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct SignWithECDSAReply {
+    pub signature: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Debug, Clone)]
+struct EcdsaKeyId {
+    pub curve: EcdsaCurve,
+    pub name: String,
+}
+
+#[derive(CandidType, Serialize, Debug, Clone)]
+enum EcdsaCurve {
+    #[serde(rename = "secp256k1")]
+    Secp256k1,
+}
+
+impl EcdsaKeyIds {
+    fn to_key_id(self) -> EcdsaKeyId {
+        EcdsaKeyId {
+            curve: EcdsaCurve::Secp256k1,
+            name: match self {
+                Self::TestKeyLocalDevelopment => "dfx_test_key",
+                Self::TestKey1 => "test_key_1",
+                Self::ProductionKey1 => "key_1",
+            }
+            .to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static ECDSA_KEY: RefCell<StableCell<EcdsaKeyIds, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))), EcdsaKeyIds::default(),
+        ).unwrap()
+    );
+
+    static PUBLIC_KEY: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))), vec![],
+        ).unwrap()
+    );
+}
+
+/// Retrieves the threshold ECDSA key used to sign credentials.
+///
+/// # Returns
+///
+/// * `EcdsaKeyIds` - The configured key, `ProductionKey1` unless set at install time.
+#[query(name = "getEcdsaKey")]
+pub fn get_ecdsa_key() -> EcdsaKeyIds {
+    ECDSA_KEY.with(|k| *k.borrow().get())
+}
+
+/// Sets the threshold ECDSA key used to sign credentials and forgets the cached public key.
+///
+/// # Arguments
+///
+/// * `ecdsa_key` - The threshold ECDSA key.
+pub fn _set_ecdsa_key(ecdsa_key: EcdsaKeyIds) {
+    ECDSA_KEY.with(|k| k.borrow_mut().set(ecdsa_key))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+    PUBLIC_KEY.with(|k| k.borrow_mut().set(vec![]))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
+
+/// Retrieves the cached public key of the reputation module.
+///
+/// # Returns
+///
+/// * `Option<Vec<u8>>` - The SEC1 compressed public key, or `None` if it was not fetched yet.
+pub fn get_cached_public_key() -> Option<Vec<u8>> {
+    Some(PUBLIC_KEY.with(|k| k.borrow().get().clone())).filter(|key| !key.is_empty())
+}
+
+/// Retrieves the public key of the reputation module, fetching and caching it on first use.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, ReputationError>` - The SEC1 compressed public key.
+pub async fn public_key() -> Result<Vec<u8>, ReputationError> {
+    if let Some(public_key) = get_cached_public_key() {
+        return Ok(public_key);
+    }
+
+    let request = ECDSAPublicKey {
+        canister_id: None,
+        derivation_path: vec![],
+        key_id: get_ecdsa_key().to_key_id(),
+    };
+
+    let (response,): (ECDSAPublicKeyReply,) = ic_cdk::call(Principal::management_canister(), "ecdsa_public_key", (request,))
+        .await
+        .map_err(|rejection| CredentialError::PublicKeyFailed(
+            CallError::new(Principal::management_canister(), "ecdsa_public_key", rejection)
+        ))?;
+
+    PUBLIC_KEY.with(|k| k.borrow_mut().set(response.public_key.clone()))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    Ok(response.public_key)
+}
+
+/// Signs a message hash with the threshold ECDSA key of the reputation module.
+///
+/// # Arguments
+///
+/// * `message_hash` - The SHA-256 hash of the message.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, ReputationError>` - The 64-byte signature, the concatenation of `r` and `s`.
+pub async fn sign(message_hash: [u8; 32]) -> Result<Vec<u8>, ReputationError> {
+    let request = SignWithECDSA {
+        message_hash: message_hash.to_vec(),
+        derivation_path: vec![],
+        key_id: get_ecdsa_key().to_key_id(),
+    };
+
+    let (response,): (SignWithECDSAReply,) = ic_cdk::api::call::call_with_payment(
+        Principal::management_canister(),
+        "sign_with_ecdsa",
+        (request,),
+        SIGN_WITH_ECDSA_CYCLES,
+    )
+    .await
+    .map_err(|rejection| CredentialError::SignFailed(
+        CallError::new(Principal::management_canister(), "sign_with_ecdsa", rejection)
+    ))?;
+
+    Ok(response.signature)
+}
+
+#[cfg(test)]
+mod tests {
+    /// Extracts the management canister types, from `ECDSAPublicKey` down to `to_key_id`.
+    fn management_types(source: &str) -> &str {
+        let start = source.find("#[derive(CandidType, Serialize, Debug)]\nstruct ECDSAPublicKey").unwrap();
+        let end = start + source[start..].find("impl EcdsaKeyIds").unwrap();
+        let end = end + source[end..].find("\n}\n").unwrap();
+
+        &source[start..end]
+    }
+
+    #[test]
+    fn management_types_match_the_achievement_canister() {
+        assert_eq!(
+            management_types(include_str!("ecdsa.rs")),
+            management_types(include_str!("../../achievement/src/ecdsa.rs"))
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! This module defines the error types returned by the reputation module.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::call::RejectionCode;
+use serde::Deserialize;
+
+use crate::icrc_7::errors::MintError;
+use crate::types::BackupSection;
+
+/// A rejected inter-canister call.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CallError {
+    pub canister: Principal,
+    pub method: String,
+    pub code: RejectionCode,
+    pub message: String,
+}
+
+impl CallError {
+    /// Builds a call error from the rejection returned by `ic_cdk::call`.
+    ///
+    /// # Arguments
+    ///
+    /// * `canister` - The principal of the called canister.
+    /// * `method` - The name of the called method.
+    /// * `rejection` - The reject code and message of the call.
+    ///
+    /// # Returns
+    ///
+    /// * `CallError` - The call error.
+    pub fn new(canister: Principal, method: &str, rejection: (RejectionCode, String)) -> Self {
+        CallError {
+            canister,
+            method: String::from(method),
+            code: rejection.0,
+            message: rejection.1,
+        }
+    }
+}
+
+/// Errors related to access control.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum AccessError {
+    AccessDenied,
+    AccountNotOwned,
+}
+
+/// Errors related to the eligibility of an identity wallet for an achievement.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum EligibilityError {
+    CanisterNotFound,
+    AchievementNotAllowed,
+    ModuleNotApproved,
+    ModuleChanged,
+    NotEligible,
+    AlreadyIssued,
+    NotIssued,
+    DuplicateAchievement,
+    IssuanceInProgress,
+    StatusUnavailable,
+}
+
+/// Errors related to the issuance of achievements and the state backing it.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum IssuanceError {
+    MetadataNotCached,
+    CanisterHasNoModule,
+    CallFailed(CallError),
+    StorageError(String),
+    LowCycles { balance: u128, threshold: u128 },
+    ScoreOverflow,
+}
+
+/// Errors related to the achievement collection ledger.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum LedgerError {
+    Mint(MintError),
+    CallFailed(CallError),
+    IncompleteBatch { expected: u64, returned: u64 },
+}
+
+/// Errors related to the configuration passed at install time.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ConfigError {
+    EmptyField(String),
+    InvalidPrincipal(Principal),
+    DuplicatePrincipal(Principal),
+}
+
+/// Errors related to exporting and importing the state of the reputation module.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum BackupError {
+    UnsupportedVersion(u32),
+    UnsupportedSchemaVersion(u32),
+    StateNotEmpty,
+    ImportInProgress,
+    ImportNotStarted,
+    ChecksumMismatch,
+    SectionMismatch,
+    UnexpectedOffset { section: BackupSection, expected: u64 },
+    SectionIncomplete { section: BackupSection, imported: u64, expected: u64 },
+}
+
+/// Errors returned while issuance is paused.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum PauseError {
+    ModulePaused,
+    AchievementPaused(Principal),
+}
+
+/// Errors related to the verifiable credentials of issued achievements.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum CredentialError {
+    PublicKeyFailed(CallError),
+    SignFailed(CallError),
+    InvalidPublicKey,
+    SigningInProgress,
+}
+
+/// Errors returned by the reputation module.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ReputationError {
+    Access(AccessError),
+    Eligibility(EligibilityError),
+    Issuance(IssuanceError),
+    Ledger(LedgerError),
+    Config(ConfigError),
+    Backup(BackupError),
+    Pause(PauseError),
+    Credential(CredentialError),
+}
+
+impl From<AccessError> for ReputationError {
+    fn from(err: AccessError) -> Self {
+        ReputationError::Access(err)
+    }
+}
+
+impl From<EligibilityError> for ReputationError {
+    fn from(err: EligibilityError) -> Self {
+        ReputationError::Eligibility(err)
+    }
+}
+
+impl From<IssuanceError> for ReputationError {
+    fn from(err: IssuanceError) -> Self {
+        ReputationError::Issuance(err)
+    }
+}
+
+impl From<LedgerError> for ReputationError {
+    fn from(err: LedgerError) -> Self {
+        ReputationError::Ledger(err)
+    }
+}
+
+impl From<ConfigError> for ReputationError {
+    fn from(err: ConfigError) -> Self {
+        ReputationError::Config(err)
+    }
+}
+
+impl From<BackupError> for ReputationError {
+    fn from(err: BackupError) -> Self {
+        ReputationError::Backup(err)
+    }
+}
+
+impl From<PauseError> for ReputationError {
+    fn from(err: PauseError) -> Self {
+        ReputationError::Pause(err)
+    }
+}
+
+impl From<CredentialError> for ReputationError {
+    fn from(err: CredentialError) -> Self {
+        ReputationError::Credential(err)
+    }
+}
+
+impl From<MintError> for ReputationError {
+    fn from(err: MintError) -> Self {
+        ReputationError::Ledger(LedgerError::Mint(err))
+    }
+}
+
+impl ReputationError {
+    /// Retrieves the name of the error, used as the reason of the failure metrics.
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The name of the error variant.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ReputationError::Access(AccessError::AccessDenied) => "AccessDenied",
+            ReputationError::Access(AccessError::AccountNotOwned) => "AccountNotOwned",
+            ReputationError::Eligibility(EligibilityError::CanisterNotFound) => "CanisterNotFound",
+            ReputationError::Eligibility(EligibilityError::AchievementNotAllowed) => "AchievementNotAllowed",
+            ReputationError::Eligibility(EligibilityError::ModuleNotApproved) => "ModuleNotApproved",
+            ReputationError::Eligibility(EligibilityError::ModuleChanged) => "ModuleChanged",
+            ReputationError::Eligibility(EligibilityError::NotEligible) => "NotEligible",
+            ReputationError::Eligibility(EligibilityError::AlreadyIssued) => "AlreadyIssued",
+            ReputationError::Eligibility(EligibilityError::NotIssued) => "NotIssued",
+            ReputationError::Eligibility(EligibilityError::DuplicateAchievement) => "DuplicateAchievement",
+            ReputationError::Eligibility(EligibilityError::IssuanceInProgress) => "IssuanceInProgress",
+            ReputationError::Eligibility(EligibilityError::StatusUnavailable) => "StatusUnavailable",
+            ReputationError::Issuance(IssuanceError::MetadataNotCached) => "MetadataNotCached",
+            ReputationError::Issuance(IssuanceError::CanisterHasNoModule) => "CanisterHasNoModule",
+            ReputationError::Issuance(IssuanceError::CallFailed(_)) => "CallFailed",
+            ReputationError::Issuance(IssuanceError::StorageError(_)) => "StorageError",
+            ReputationError::Issuance(IssuanceError::LowCycles { .. }) => "LowCycles",
+            ReputationError::Issuance(IssuanceError::ScoreOverflow) => "ScoreOverflow",
+            ReputationError::Ledger(LedgerError::Mint(_)) => "MintFailed",
+            ReputationError::Ledger(LedgerError::CallFailed(_)) => "MintCallFailed",
+            ReputationError::Ledger(LedgerError::IncompleteBatch { .. }) => "MintBatchIncomplete",
+            ReputationError::Config(_) => "InvalidConfig",
+            ReputationError::Backup(_) => "BackupFailed",
+            ReputationError::Pause(PauseError::ModulePaused) => "ModulePaused",
+            ReputationError::Pause(PauseError::AchievementPaused(_)) => "AchievementPaused",
+            ReputationError::Credential(CredentialError::PublicKeyFailed(_)) => "PublicKeyFailed",
+            ReputationError::Credential(CredentialError::SignFailed(_)) => "SignFailed",
+            ReputationError::Credential(CredentialError::InvalidPublicKey) => "InvalidPublicKey",
+            ReputationError::Credential(CredentialError::SigningInProgress) => "SigningInProgress",
+        }
+    }
+}
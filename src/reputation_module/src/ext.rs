@@ -0,0 +1,302 @@
+//! This module exposes an EXT standard compatibility layer over issued achievements,
+//! so that legacy marketplaces and wallets can display them.
+
+use candid::Principal;
+use ic_cdk::query;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use icrc_ledger_types::icrc1::account::Subaccount;
+use std::cell::RefCell;
+use std::ops::Bound;
+
+use crate::icrc_7::errors::ExtCommonError;
+use crate::icrc_7::ext_types::{
+    AccountIdentifier, AccountIdentifierHex, Extension, ExtBearerResult, ExtMetadata, ExtMetadataResult,
+    ExtMetadataType, ExtRegistryEntry, ExtSupplyResult, ExtTokenIndex, ExtTokensResult, TokenIdentifier, EXTENSIONS
+};
+use crate::state::{MEMORY_MANAGER, TOKEN_ID_TO_ISSUANCE, get_issuance, get_held_achievements, get_reputation_module_metadata};
+use crate::storable::{Issuance, Memory, StorablePrincipal};
+use crate::types::MAX_PAGE;
+
+thread_local! {
+    /// The principal owning every account identifier that holds an achievement.
+    static ACCOUNT_IDENTIFIER_TO_PRINCIPAL: RefCell<StableBTreeMap<[u8; 32], StorablePrincipal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27))),
+        )
+    );
+}
+
+/// Computes the EXT account identifier of an account.
+fn account_identifier(owner: Principal, subaccount: Option<Subaccount>) -> AccountIdentifier {
+    AccountIdentifier::from_principal(&owner, &subaccount)
+}
+
+/// Computes the EXT account identifier of an achievement holder.
+fn holder_account_identifier(issuance: &Issuance) -> AccountIdentifierHex {
+    account_identifier(issuance.owner, issuance.subaccount).to_hex()
+}
+
+/// Indexes the account identifier of an account holding an achievement, so that `tokens_ext` can find its principal.
+///
+/// # Arguments
+///
+/// * `owner` - The principal of the account.
+/// * `subaccount` - The subaccount of the account.
+pub fn _index_account(owner: Principal, subaccount: Option<Subaccount>) {
+    ACCOUNT_IDENTIFIER_TO_PRINCIPAL.with(|a| {
+        a.borrow_mut().insert(*account_identifier(owner, subaccount).as_bytes(), StorablePrincipal(owner))
+    });
+}
+
+/// Removes the account identifier of an account that no longer holds any achievement.
+///
+/// # Arguments
+///
+/// * `owner` - The principal of the account.
+/// * `subaccount` - The subaccount of the account.
+pub fn _unindex_account(owner: Principal, subaccount: Option<Subaccount>) {
+    ACCOUNT_IDENTIFIER_TO_PRINCIPAL.with(|a| a.borrow_mut().remove(account_identifier(owner, subaccount).as_bytes()));
+}
+
+/// Clears the account identifier index before it is rebuilt.
+pub fn _clear_account_index() {
+    ACCOUNT_IDENTIFIER_TO_PRINCIPAL.with(|a| a.borrow_mut().clear_new());
+}
+
+/// Finds the principal owning an account identifier.
+///
+/// # Arguments
+///
+/// * `account` - The account identifier.
+///
+/// # Returns
+///
+/// * `Option<Principal>` - The principal, if the account holds an achievement.
+fn find_account_owner(account: &[u8; 32]) -> Option<Principal> {
+    ACCOUNT_IDENTIFIER_TO_PRINCIPAL.with(|a| a.borrow().get(account)).map(|owner| owner.0)
+}
+
+/// Converts a token id into an EXT token index.
+///
+/// EXT token indexes are 32 bits wide, so token ids above `u32::MAX` cannot be represented.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+///
+/// # Returns
+///
+/// * `Option<ExtTokenIndex>` - The token index, or `None` if the token id does not fit.
+fn token_index(token_id: u128) -> Option<ExtTokenIndex> {
+    ExtTokenIndex::try_from(token_id).ok()
+}
+
+/// Parses an EXT token identifier into a token id.
+///
+/// Token identifiers are built from the principal of the achievement collection, which mints the tokens.
+///
+/// # Arguments
+///
+/// * `token` - The EXT token identifier.
+///
+/// # Returns
+///
+/// * `Result<u128, ExtCommonError>` - The token id.
+fn parse_token(token: &TokenIdentifier) -> Result<u128, ExtCommonError> {
+    token.parse_token_index(get_reputation_module_metadata().achievement_collection)
+}
+
+/// Resolves an EXT token identifier to an issued achievement.
+///
+/// # Arguments
+///
+/// * `token` - The EXT token identifier.
+///
+/// # Returns
+///
+/// * `Result<(u128, Issuance), ExtCommonError>` - The token id and its issuance record.
+fn resolve_token(token: &TokenIdentifier) -> Result<(u128, Issuance), ExtCommonError> {
+    let token_id = parse_token(token)?;
+
+    match get_issuance(token_id) {
+        Some(issuance) => Ok((token_id, issuance)),
+        None => Err(ExtCommonError::InvalidToken(token.to_owned()))
+    }
+}
+
+/// Retrieves the EXT extensions supported by the reputation module.
+///
+/// # Returns
+///
+/// * `Vec<Extension>` - The list of supported extensions.
+#[query(name = "extensions")]
+pub fn extensions() -> Vec<Extension> {
+    EXTENSIONS.iter().map(|e| e.to_string()).collect()
+}
+
+/// Retrieves the holder of an issued achievement.
+///
+/// # Arguments
+///
+/// * `token` - The EXT token identifier.
+///
+/// # Returns
+///
+/// * `ExtBearerResult` - The account identifier of the holder.
+#[query(name = "bearer")]
+pub fn bearer(token: TokenIdentifier) -> ExtBearerResult {
+    match resolve_token(&token) {
+        Ok((_, issuance)) => ExtBearerResult::Ok(holder_account_identifier(&issuance)),
+        Err(err) => ExtBearerResult::Err(err)
+    }
+}
+
+/// Retrieves the EXT metadata of an issued achievement.
+///
+/// The metadata blob is a JSON document with the achievement canister and the issuance time.
+///
+/// # Arguments
+///
+/// * `token` - The EXT token identifier.
+///
+/// # Returns
+///
+/// * `ExtMetadataResult` - The non-fungible metadata of the token.
+#[query(name = "metadata")]
+pub fn metadata(token: TokenIdentifier) -> ExtMetadataResult {
+    match resolve_token(&token) {
+        Ok((token_id, issuance)) => ExtMetadataResult::Ok(ExtMetadata::Nonfungible(ExtMetadataType::new(format!(
+            "{{\"token_id\":{},\"achievement\":\"{}\",\"issued_at\":{}}}",
+            token_id,
+            issuance.achievement.to_text(),
+            issuance.issued_at
+        )))),
+        Err(err) => ExtMetadataResult::Err(err)
+    }
+}
+
+/// Retrieves the achievements held by an account.
+///
+/// # Arguments
+///
+/// * `account` - The EXT account identifier in hexadecimal format.
+///
+/// # Returns
+///
+/// * `ExtTokensResult` - The token indexes held by the account. Tokens whose id does not fit an EXT token index are skipped.
+#[query(name = "tokens_ext")]
+pub fn tokens_ext(account: AccountIdentifierHex) -> ExtTokensResult {
+    let account: Option<[u8; 32]> = hex::decode(account).ok().and_then(|bytes| bytes.try_into().ok());
+    let owner = account.as_ref().and_then(find_account_owner);
+
+    let tokens: Vec<_> = match (account, owner) {
+        (Some(account), Some(owner)) => get_held_achievements(owner)
+            .into_iter()
+            .filter(|held| *account_identifier(owner, held.subaccount).as_bytes() == account)
+            .filter_map(|held| token_index(held.token_id).map(|index| (index, None, None)))
+            .collect(),
+        _ => vec![]
+    };
+
+    if tokens.is_empty() {
+        ExtTokensResult::Err(ExtCommonError::Other(String::from("No tokens")))
+    } else {
+        ExtTokensResult::Ok(tokens)
+    }
+}
+
+/// Retrieves a page of the registry of issued achievements.
+///
+/// The registry is read from the issued tokens, ordered by token index. Pass the last token index of a
+/// page as `after` to retrieve the following one. Called without arguments, as by the EXT standard, it
+/// returns the first page. Tokens whose id does not fit an EXT token index are not listed.
+///
+/// # Arguments
+///
+/// * `after` - The last token index of the previous page, or `None` to start from the beginning.
+/// * `limit` - The maximum number of entries to return, capped at and defaulting to 1000.
+///
+/// # Returns
+///
+/// * `Vec<ExtRegistryEntry>` - The token indexes together with the account identifiers of their holders.
+#[query(name = "getRegistry")]
+pub fn get_registry(after: Option<ExtTokenIndex>, limit: Option<u64>) -> Vec<ExtRegistryEntry> {
+    let limit = limit.unwrap_or(MAX_PAGE).min(MAX_PAGE) as usize;
+    let start = after.map_or(Bound::Unbounded, |after| Bound::Excluded(after as u128));
+
+    TOKEN_ID_TO_ISSUANCE.with(|t| {
+        t.borrow()
+            .range((start, Bound::Unbounded))
+            .map_while(|(token_id, issuance)| token_index(token_id).map(|index| (index, holder_account_identifier(&issuance))))
+            .take(limit)
+            .collect()
+    })
+}
+
+/// Retrieves the supply of an achievement token.
+///
+/// # Arguments
+///
+/// * `token` - The EXT token identifier.
+///
+/// # Returns
+///
+/// * `ExtSupplyResult` - `1` if the token is issued, `0` if it was never issued or was revoked.
+#[query(name = "supply")]
+pub fn supply(token: TokenIdentifier) -> ExtSupplyResult {
+    match parse_token(&token) {
+        Ok(token_id) => ExtSupplyResult::Ok(get_issuance(token_id).is_some() as u128),
+        Err(err) => ExtSupplyResult::Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLLECTION: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+
+    #[test]
+    fn token_identifier_round_trips_through_the_collection() {
+        let collection = Principal::from_text(COLLECTION).unwrap();
+
+        for index in [0, 1, 255, 65_536, u32::MAX as u128] {
+            let token = TokenIdentifier::parse_token_identifier(collection, index);
+
+            assert_eq!(token.parse_token_index(collection).unwrap(), index);
+        }
+    }
+
+    #[test]
+    fn token_identifier_of_another_canister_is_rejected() {
+        let collection = Principal::from_text(COLLECTION).unwrap();
+        let token = TokenIdentifier::parse_token_identifier(Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap(), 1);
+
+        assert!(matches!(token.parse_token_index(collection), Err(ExtCommonError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn token_index_rejects_token_ids_above_u32() {
+        assert_eq!(token_index(7), Some(7));
+        assert_eq!(token_index(u32::MAX as u128), Some(u32::MAX));
+        assert_eq!(token_index(u32::MAX as u128 + 1), None);
+    }
+
+    #[test]
+    fn account_identifier_matches_the_ledger_derivation() {
+        assert_eq!(
+            account_identifier(Principal::anonymous(), None).to_hex(),
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79"
+        );
+    }
+
+    #[test]
+    fn account_identifier_depends_on_the_subaccount() {
+        let owner = Principal::from_text(COLLECTION).unwrap();
+        let default = account_identifier(owner, None);
+
+        assert_eq!(default.as_bytes(), account_identifier(owner, Some([0; 32])).as_bytes());
+        assert_ne!(default.as_bytes(), account_identifier(owner, Some([1; 32])).as_bytes());
+    }
+}
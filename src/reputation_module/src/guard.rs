@@ -0,0 +1,123 @@
+//! This module guards issuances and credential signatures that are in flight.
+//!
+//! An issuance awaits several inter-canister calls between its eligibility checks and the
+//! moment it is recorded. The pending sets make sure that no second issuance of the same
+//! achievement to the same identity wallet, and no second signature of its credential, starts in between.
+
+use candid::Principal;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::thread::LocalKey;
+
+use crate::storable::PrincipalPair;
+use crate::errors::{CredentialError, EligibilityError, ReputationError};
+
+thread_local! {
+    /// The identity wallet and achievement pairs of the issuances in flight.
+    static PENDING_ISSUANCES: RefCell<BTreeSet<PrincipalPair>> = const { RefCell::new(BTreeSet::new()) };
+
+    /// The identity wallet and achievement pairs whose credentials are being signed.
+    static PENDING_CREDENTIALS: RefCell<BTreeSet<PrincipalPair>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Keeps an identity wallet and achievement pair in a pending set while it is alive.
+///
+/// The pair is released when the guard is dropped, which also happens when the call traps
+/// after an await, so that a failed operation never leaves the pair pending.
+pub struct PendingGuard {
+    pending: &'static LocalKey<RefCell<BTreeSet<PrincipalPair>>>,
+    key: PrincipalPair
+}
+
+impl PendingGuard {
+    /// Marks the issuance of an achievement to an identity wallet as in flight.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity_wallet` - The principal of the identity wallet.
+    /// * `achievement` - The principal of the achievement canister.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PendingGuard, ReputationError>` - The guard, or an error if the same issuance is already in flight.
+    pub fn issuance(identity_wallet: Principal, achievement: Principal) -> Result<Self, ReputationError> {
+        Self::acquire(&PENDING_ISSUANCES, PrincipalPair(identity_wallet, achievement))
+            .ok_or_else(|| EligibilityError::IssuanceInProgress.into())
+    }
+
+    /// Marks the signature of the credential of an achievement held by an identity wallet as in flight.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity_wallet` - The principal of the identity wallet.
+    /// * `achievement` - The principal of the achievement canister.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PendingGuard, ReputationError>` - The guard, or an error if the same credential is already being signed.
+    pub fn credential(identity_wallet: Principal, achievement: Principal) -> Result<Self, ReputationError> {
+        Self::acquire(&PENDING_CREDENTIALS, PrincipalPair(identity_wallet, achievement))
+            .ok_or_else(|| CredentialError::SigningInProgress.into())
+    }
+
+    /// Inserts a pair into a pending set.
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - The pending set.
+    /// * `key` - The pair to insert.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<PendingGuard>` - The guard, or `None` if the pair is already pending.
+    fn acquire(pending: &'static LocalKey<RefCell<BTreeSet<PrincipalPair>>>, key: PrincipalPair) -> Option<Self> {
+        pending
+            .with(|p| p.borrow_mut().insert(key))
+            .then_some(Self { pending, key })
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.pending.with(|p| p.borrow_mut().remove(&self.key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 10])
+    }
+
+    #[test]
+    fn issuance_guard_rejects_a_pending_pair_until_dropped() {
+        let guard = PendingGuard::issuance(principal(1), principal(2)).unwrap();
+
+        assert!(matches!(
+            PendingGuard::issuance(principal(1), principal(2)),
+            Err(ReputationError::Eligibility(EligibilityError::IssuanceInProgress))
+        ));
+        assert!(PendingGuard::issuance(principal(1), principal(3)).is_ok());
+
+        drop(guard);
+
+        assert!(PendingGuard::issuance(principal(1), principal(2)).is_ok());
+    }
+
+    #[test]
+    fn credential_guard_is_independent_of_issuance_guard() {
+        let _issuance = PendingGuard::issuance(principal(1), principal(2)).unwrap();
+        let credential = PendingGuard::credential(principal(1), principal(2)).unwrap();
+
+        assert!(matches!(
+            PendingGuard::credential(principal(1), principal(2)),
+            Err(ReputationError::Credential(CredentialError::SigningInProgress))
+        ));
+
+        drop(credential);
+
+        assert!(PendingGuard::credential(principal(1), principal(2)).is_ok());
+    }
+}
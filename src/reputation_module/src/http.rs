@@ -0,0 +1,839 @@
+//! This module serves the state of the reputation module and the badges of issued tokens over the HTTP gateway, with certified responses.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use candid::Principal;
+use icrc_ledger_types::icrc1::account::Account;
+use ic_cdk::query;
+use ic_certification::{labeled, labeled_hash, AsHashTree, Hash, RbTree};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::ops::Bound;
+use std::time::Duration;
+
+use crate::badge::{badge_path, parse_badge_path, render_badge};
+use crate::credential::{render_did_document, DID_DOCUMENT_PATH};
+use crate::metrics::render_metrics;
+use crate::state::{
+    get_reputation_module_metadata, get_holders, get_holder_count, get_reputation, get_reputation_of_account,
+    get_held_achievements, get_held_achievements_of_account,
+    ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT, PRINCIPAL_TO_REPUTATION, ACCOUNT_TO_ACHIEVEMENTS,
+    TOKEN_ID_TO_ISSUANCE
+};
+use crate::storable::{StorableAccount, StorablePrincipal};
+use crate::types::{HttpRequest, HttpResponse};
+use crate::utils::normalize_subaccount;
+
+/// The label the certified response hashes are stored under, as expected by the HTTP gateway.
+const LABEL_ASSETS: &[u8] = b"http_assets";
+
+/// The maximum number of holders listed by `/holders/<principal>.json`.
+const MAX_HOLDERS_PER_RESPONSE: u64 = 1000;
+
+/// The number of paths certified at once by a background certification task.
+const CERTIFICATION_BATCH: usize = 100;
+
+/// The instructions a background certification task may use in a single timer callback.
+const CERTIFICATION_INSTRUCTION_BUDGET: u64 = 2_000_000_000;
+
+/// The number of seconds clients are asked to wait while responses are being certified.
+const RETRY_AFTER_SECONDS: &str = "5";
+
+const METADATA_PATH: &str = "/metadata.json";
+const METRICS_PATH: &str = "/metrics";
+const HOLDERS_PREFIX: &str = "/holders/";
+const REPUTATION_PREFIX: &str = "/reputation/";
+const JSON_SUFFIX: &str = ".json";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const SVG_CONTENT_TYPE: &str = "image/svg+xml";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A set of responses certified in the background, resumed after its last certified key.
+#[derive(Clone, Copy)]
+enum CertificationTask {
+    Holders(Option<Principal>),
+    HoldersOf(Principal),
+    Reputations(Option<Principal>),
+    AccountReputations(Option<Account>),
+    Badges(Option<u128>),
+    BadgesOf(Principal, Option<Account>),
+}
+
+impl CertificationTask {
+    /// Checks whether a queued task certifies the same set of responses as this one.
+    fn same_set(&self, queued: &CertificationTask) -> bool {
+        match (self, queued) {
+            (CertificationTask::BadgesOf(achievement, _), CertificationTask::BadgesOf(queued, _)) => achievement == queued,
+            (CertificationTask::HoldersOf(achievement), CertificationTask::HoldersOf(queued)) => achievement == queued,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(queued)
+        }
+    }
+}
+
+thread_local! {
+    /// The SHA-256 hash of the body of every certified response, by path.
+    static CERTIFIED_RESPONSES: RefCell<RbTree<String, Hash>> = const { RefCell::new(RbTree::new()) };
+
+    /// The background certification tasks left to run.
+    static CERTIFICATION_QUEUE: RefCell<VecDeque<CertificationTask>> = const { RefCell::new(VecDeque::new()) };
+
+    /// Whether a timer is set to run the background certification tasks.
+    static CERTIFICATION_SCHEDULED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Retrieves the path of the holders of an achievement.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `String` - The path of the holders of the achievement.
+fn holders_path(achievement: Principal) -> String {
+    format!("{}{}{}", HOLDERS_PREFIX, achievement.to_text(), JSON_SUFFIX)
+}
+
+/// Retrieves the path of the reputation of a principal.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `String` - The path of the reputation of the principal.
+fn reputation_path(identity_wallet: Principal) -> String {
+    format!("{}{}{}", REPUTATION_PREFIX, identity_wallet.to_text(), JSON_SUFFIX)
+}
+
+/// Retrieves the path of the reputation of an account, in the ICRC-1 textual encoding of the account.
+///
+/// The path of the default account of a principal is the path of the reputation of the principal.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+///
+/// # Returns
+///
+/// * `String` - The path of the reputation of the account.
+fn account_reputation_path(account: &Account) -> String {
+    format!("{}{}{}", REPUTATION_PREFIX, account, JSON_SUFFIX)
+}
+
+/// Parses the account of a path of the form `<prefix><account>.json`.
+fn parse_account(path: &str, prefix: &str) -> Option<Account> {
+    let text = path.strip_prefix(prefix)?.strip_suffix(JSON_SUFFIX)?;
+    text.parse().ok()
+}
+
+/// Parses the principal of a path of the form `<prefix><principal>.json`.
+fn parse_principal(path: &str, prefix: &str) -> Option<Principal> {
+    let text = path.strip_prefix(prefix)?.strip_suffix(JSON_SUFFIX)?;
+    Principal::from_text(text).ok()
+}
+
+/// Renders the body of the response served at a path.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request, without query string.
+///
+/// # Returns
+///
+/// * `Option<(&'static str, Vec<u8>)>` - The content type and body served at the path, or `None` if the path is unknown.
+fn render(path: &str) -> Option<(&'static str, Vec<u8>)> {
+    if let Some(token_id) = parse_badge_path(path) {
+        return render_badge(token_id).map(|badge| (SVG_CONTENT_TYPE, badge.into_bytes()));
+    }
+
+    render_json(path).map(|value| (JSON_CONTENT_TYPE, encode(&value)))
+}
+
+/// Renders the JSON document served at a path.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request, without query string.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The JSON document served at the path, or `None` if the path is unknown.
+fn render_json(path: &str) -> Option<Value> {
+    if path == DID_DOCUMENT_PATH {
+        return render_did_document();
+    }
+
+    if path == METADATA_PATH {
+        let metadata = get_reputation_module_metadata();
+        return Some(json!({
+            "achievement_collection": metadata.achievement_collection.to_text(),
+            "issuer_name": metadata.issuer_name,
+            "issuer_description": metadata.issuer_description,
+            "total_issued": metadata.total_issued
+        }));
+    }
+
+    if let Some(achievement) = parse_principal(path, HOLDERS_PREFIX) {
+        return render_holders(achievement, None);
+    }
+
+    if let Some(account) = parse_account(path, REPUTATION_PREFIX) {
+        return match normalize_subaccount(account.subaccount) {
+            None => render_reputation(account.owner),
+            Some(_) => render_account_reputation(&account)
+        };
+    }
+
+    None
+}
+
+/// Renders the reputation of a principal, earned by all of its accounts.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The JSON document of the reputation, or `None` if the principal holds no achievement.
+fn render_reputation(identity_wallet: Principal) -> Option<Value> {
+    if !PRINCIPAL_TO_REPUTATION.with(|r| r.borrow().contains_key(&StorablePrincipal(identity_wallet))) {
+        return None;
+    }
+
+    let reputation = get_reputation(identity_wallet);
+    Some(json!({
+        "principal": identity_wallet.to_text(),
+        "total": reputation.total,
+        "breakdown": reputation.breakdown.iter().map(|entry| json!({
+            "achievement": entry.achievement.to_text(),
+            "score": entry.score
+        })).collect::<Vec<Value>>(),
+        "achievements": get_held_achievements(identity_wallet).iter().map(|held| json!({
+            "achievement": held.achievement.to_text(),
+            "account": Account { owner: identity_wallet, subaccount: held.subaccount }.to_string(),
+            "token_id": held.token_id,
+            "issued_at": held.issued_at
+        })).collect::<Vec<Value>>()
+    }))
+}
+
+/// Renders the part of a principal's reputation earned by one of its accounts.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet, with a subaccount other than the default one.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The JSON document of the reputation, or `None` if the account holds no achievement.
+fn render_account_reputation(account: &Account) -> Option<Value> {
+    let held = get_held_achievements_of_account(account);
+
+    if held.is_empty() {
+        return None;
+    }
+
+    let reputation = get_reputation_of_account(*account);
+    Some(json!({
+        "principal": account.owner.to_text(),
+        "account": account.to_string(),
+        "total": reputation.total,
+        "breakdown": reputation.breakdown.iter().map(|entry| json!({
+            "achievement": entry.achievement.to_text(),
+            "score": entry.score
+        })).collect::<Vec<Value>>(),
+        "achievements": held.iter().map(|held| json!({
+            "achievement": held.achievement.to_text(),
+            "token_id": held.token_id,
+            "issued_at": held.issued_at
+        })).collect::<Vec<Value>>()
+    }))
+}
+
+/// Renders a page of the holders of an achievement.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+/// * `cursor` - The last holder of the previous page, or `None` for the first page.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The JSON document of the page, or `None` if the achievement has no holder.
+fn render_holders(achievement: Principal, cursor: Option<Account>) -> Option<Value> {
+    let holder_count = get_holder_count(achievement);
+
+    if holder_count == 0 {
+        return None;
+    }
+
+    let page = get_holders(achievement, cursor, MAX_HOLDERS_PER_RESPONSE);
+    Some(json!({
+        "achievement": achievement.to_text(),
+        "holder_count": holder_count,
+        "holders": page.holders.iter().map(|holder| json!({
+            "principal": holder.principal.to_text(),
+            "account": Account { owner: holder.principal, subaccount: holder.subaccount }.to_string(),
+            "token_id": holder.token_id,
+            "issued_at": holder.issued_at
+        })).collect::<Vec<Value>>(),
+        "next_cursor": page.next_cursor.map(|cursor| cursor.to_string())
+    }))
+}
+
+/// Encodes a JSON document as a response body.
+fn encode(value: &Value) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)))
+}
+
+/// Certifies the current responses served at several paths.
+///
+/// Must be called from an update call, `init` or `post_upgrade` after the state behind the paths changed.
+///
+/// # Arguments
+///
+/// * `paths` - The paths to certify.
+fn certify(paths: Vec<String>) {
+    CERTIFIED_RESPONSES.with(|c| {
+        let mut responses = c.borrow_mut();
+
+        for path in paths {
+            match render(&path) {
+                Some((_, body)) => responses.insert(path, Sha256::digest(body).into()),
+                None => responses.delete(path.as_bytes())
+            }
+        }
+
+        ic_cdk::api::set_certified_data(&labeled_hash(LABEL_ASSETS, &responses.root_hash()));
+    });
+}
+
+/// Certifies the response served at `/metadata.json`.
+pub fn certify_metadata() {
+    certify(vec![String::from(METADATA_PATH)]);
+}
+
+/// Certifies the responses affected by the issuance or revocation of an achievement.
+///
+/// Must be called once the reputation of the owner of the account was updated. The reputations of
+/// the owner are certified right away. The holders of the achievement, up to 1000 per document, are
+/// certified in the background, so that the issuances of an achievement made before the next timer
+/// callback render its holders once.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+pub fn certify_holding(account: &Account, achievement: Principal) {
+    let mut paths = vec![reputation_path(account.owner)];
+
+    if normalize_subaccount(account.subaccount).is_some() {
+        paths.push(account_reputation_path(account));
+    }
+
+    certify(paths);
+    schedule_certification(CertificationTask::HoldersOf(achievement));
+}
+
+/// Certifies the DID document of the issuer after the public key of the reputation module was fetched.
+pub fn certify_did_document() {
+    certify(vec![String::from(DID_DOCUMENT_PATH)]);
+}
+
+/// Certifies the badge of a token after it was issued or revoked.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+pub fn certify_badge(token_id: u128) {
+    certify(vec![badge_path(token_id)]);
+}
+
+/// Certifies the badges of the holders of an achievement in the background after its name changed.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+pub fn certify_badges_of(achievement: Principal) {
+    schedule_certification(CertificationTask::BadgesOf(achievement, None));
+}
+
+/// Certifies the badges of every issued token in the background after the issuer name changed.
+pub fn certify_all_badges() {
+    schedule_certification(CertificationTask::Badges(None));
+}
+
+/// Certifies the responses served at every path.
+///
+/// The certified hashes are kept on the heap, so they are recomputed after every upgrade. The
+/// metadata and the DID document are certified right away; the holders, reputations and badges
+/// are certified in the background, so that the work is not bounded by the number of principals.
+pub fn certify_all() {
+    certify(vec![String::from(METADATA_PATH), String::from(DID_DOCUMENT_PATH)]);
+
+    schedule_certification(CertificationTask::Holders(None));
+    schedule_certification(CertificationTask::Reputations(None));
+    schedule_certification(CertificationTask::AccountReputations(None));
+    schedule_certification(CertificationTask::Badges(None));
+}
+
+/// Queues a background certification task and sets a timer to run it.
+///
+/// A task restarting a set of responses from the beginning replaces a queued task of the same set.
+///
+/// # Arguments
+///
+/// * `task` - The task to queue.
+fn schedule_certification(task: CertificationTask) {
+    CERTIFICATION_QUEUE.with(|q| {
+        let mut queue = q.borrow_mut();
+        queue.retain(|queued| !task.same_set(queued));
+        queue.push_back(task);
+    });
+
+    if !CERTIFICATION_SCHEDULED.with(|s| s.replace(true)) {
+        ic_cdk_timers::set_timer(Duration::ZERO, run_certification);
+    }
+}
+
+/// Checks whether background certification tasks are left to run.
+///
+/// # Returns
+///
+/// * `bool` - `true` while some responses may not be certified yet.
+fn is_certification_pending() -> bool {
+    CERTIFICATION_QUEUE.with(|q| !q.borrow().is_empty())
+}
+
+/// Runs the queued background certification tasks until the instruction budget is spent.
+///
+/// Tasks left unfinished are resumed from another timer callback.
+fn run_certification() {
+    CERTIFICATION_SCHEDULED.with(|s| s.set(false));
+
+    while ic_cdk::api::performance_counter(0) < CERTIFICATION_INSTRUCTION_BUDGET {
+        let Some(task) = CERTIFICATION_QUEUE.with(|q| q.borrow_mut().pop_front()) else {
+            return;
+        };
+        let (paths, next) = next_certification_batch(task);
+
+        certify(paths);
+
+        if let Some(next) = next {
+            CERTIFICATION_QUEUE.with(|q| q.borrow_mut().push_front(next));
+        }
+    }
+
+    if is_certification_pending() && !CERTIFICATION_SCHEDULED.with(|s| s.replace(true)) {
+        ic_cdk_timers::set_timer(Duration::ZERO, run_certification);
+    }
+}
+
+/// Retrieves the next batch of paths of a background certification task.
+///
+/// # Arguments
+///
+/// * `task` - The task.
+///
+/// # Returns
+///
+/// * `(Vec<String>, Option<CertificationTask>)` - The paths to certify and the task resuming after them, if any.
+fn next_certification_batch(task: CertificationTask) -> (Vec<String>, Option<CertificationTask>) {
+    match task {
+        CertificationTask::Holders(cursor) => {
+            let achievements: Vec<Principal> = ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| {
+                h.borrow()
+                    .range((cursor.map_or(Bound::Unbounded, |c| Bound::Excluded(StorablePrincipal(c))), Bound::Unbounded))
+                    .take(CERTIFICATION_BATCH)
+                    .map(|(achievement, _)| achievement.0)
+                    .collect()
+            });
+            let next = (achievements.len() == CERTIFICATION_BATCH).then(|| CertificationTask::Holders(achievements.last().copied()));
+
+            (achievements.into_iter().map(holders_path).collect(), next)
+        },
+        CertificationTask::HoldersOf(achievement) => (vec![holders_path(achievement)], None),
+        CertificationTask::Reputations(cursor) => {
+            let principals: Vec<Principal> = PRINCIPAL_TO_REPUTATION.with(|r| {
+                r.borrow()
+                    .range((cursor.map_or(Bound::Unbounded, |c| Bound::Excluded(StorablePrincipal(c))), Bound::Unbounded))
+                    .take(CERTIFICATION_BATCH)
+                    .map(|(principal, _)| principal.0)
+                    .collect()
+            });
+            let next = (principals.len() == CERTIFICATION_BATCH).then(|| CertificationTask::Reputations(principals.last().copied()));
+
+            (principals.into_iter().map(reputation_path).collect(), next)
+        },
+        CertificationTask::AccountReputations(cursor) => {
+            let accounts: Vec<Account> = ACCOUNT_TO_ACHIEVEMENTS.with(|a| {
+                a.borrow()
+                    .range((cursor.map_or(Bound::Unbounded, |c| Bound::Excluded(StorableAccount(c))), Bound::Unbounded))
+                    .take(CERTIFICATION_BATCH)
+                    .map(|(account, _)| account.0)
+                    .collect()
+            });
+            let next = (accounts.len() == CERTIFICATION_BATCH).then(|| CertificationTask::AccountReputations(accounts.last().copied()));
+            let paths = accounts.iter()
+                .filter(|account| account.subaccount.is_some())
+                .map(account_reputation_path)
+                .collect();
+
+            (paths, next)
+        },
+        CertificationTask::Badges(cursor) => {
+            let token_ids: Vec<u128> = TOKEN_ID_TO_ISSUANCE.with(|t| {
+                t.borrow()
+                    .range((cursor.map_or(Bound::Unbounded, Bound::Excluded), Bound::Unbounded))
+                    .take(CERTIFICATION_BATCH)
+                    .map(|(token_id, _)| token_id)
+                    .collect()
+            });
+            let next = (token_ids.len() == CERTIFICATION_BATCH).then(|| CertificationTask::Badges(token_ids.last().copied()));
+
+            (token_ids.into_iter().map(badge_path).collect(), next)
+        },
+        CertificationTask::BadgesOf(achievement, cursor) => {
+            let page = get_holders(achievement, cursor, CERTIFICATION_BATCH as u64);
+            let next = page.next_cursor.map(|cursor| CertificationTask::BadgesOf(achievement, Some(cursor)));
+
+            (page.holders.iter().filter_map(|holder| holder.token_id).map(badge_path).collect(), next)
+        },
+    }
+}
+
+/// Builds the `IC-Certificate` header proving the response served at a path.
+///
+/// # Arguments
+///
+/// * `path` - The certified path.
+///
+/// # Returns
+///
+/// * `(String, String)` - The header name and value.
+fn certificate_header(path: &str) -> (String, String) {
+    build_certificate_header(&ic_cdk::api::data_certificate().unwrap_or_default(), path)
+}
+
+/// Builds the `IC-Certificate` header of a path from a data certificate.
+///
+/// # Arguments
+///
+/// * `certificate` - The data certificate of the canister.
+/// * `path` - The certified path.
+///
+/// # Returns
+///
+/// * `(String, String)` - The header name and value, carrying the certificate and the witness of the path.
+fn build_certificate_header(certificate: &[u8], path: &str) -> (String, String) {
+    let witness = CERTIFIED_RESPONSES.with(|c| c.borrow().witness(path.as_bytes()));
+    let tree = labeled(LABEL_ASSETS, witness);
+
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe()
+        .and_then(|_| tree.serialize(&mut serializer))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    (
+        String::from("IC-Certificate"),
+        format!("certificate=:{}:, tree=:{}:", BASE64.encode(certificate), BASE64.encode(serializer.into_inner()))
+    )
+}
+
+/// Builds an uncertified JSON error response.
+fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![(String::from("Content-Type"), String::from(JSON_CONTENT_TYPE))],
+        body: encode(&json!({ "error": message }))
+    }
+}
+
+/// Parses the holders cursor of a query string.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the request.
+///
+/// # Returns
+///
+/// * `Option<Result<Account, ()>>` - The cursor, an error if it is not an account, or `None` if the URL has no cursor.
+fn parse_cursor(url: &str) -> Option<Result<Account, ()>> {
+    let query = url.split_once('?')?.1.split('#').next().unwrap_or_default();
+
+    query
+        .split('&')
+        .find_map(|param| param.strip_prefix("cursor="))
+        .map(|cursor| cursor.parse().map_err(|_| ()))
+}
+
+/// Serves a request to a path that is not certified.
+///
+/// `/metrics` and the pages of holders requested with a `cursor` are served without an
+/// `IC-Certificate` header: `/metrics` changes on every call, and the pages after the first one are
+/// rendered on demand. The boundary nodes refuse uncertified responses, so these paths must be
+/// fetched through the raw domain, and clients cannot verify them.
+///
+/// # Arguments
+///
+/// * `request` - The HTTP request.
+/// * `path` - The path of the request, without query string.
+///
+/// # Returns
+///
+/// * `Option<HttpResponse>` - The uncertified response, or `None` if the path is certified.
+fn uncertified_response(request: &HttpRequest, path: &str) -> Option<HttpResponse> {
+    if path == METRICS_PATH {
+        return Some(HttpResponse {
+            status_code: 200,
+            headers: vec![(String::from("Content-Type"), String::from(METRICS_CONTENT_TYPE))],
+            body: render_metrics()
+        });
+    }
+
+    let achievement = parse_principal(path, HOLDERS_PREFIX)?;
+
+    Some(match parse_cursor(&request.url)? {
+        Ok(cursor) => match render_holders(achievement, Some(cursor)) {
+            Some(page) => HttpResponse {
+                status_code: 200,
+                headers: vec![
+                    (String::from("Content-Type"), String::from(JSON_CONTENT_TYPE)),
+                    (String::from("Access-Control-Allow-Origin"), String::from("*"))
+                ],
+                body: encode(&page)
+            },
+            None => error_response(404, "Not found")
+        },
+        Err(()) => error_response(400, "Invalid cursor")
+    })
+}
+
+/// Serves the metadata of the reputation module, the holders of an achievement and the reputation of a principal as JSON,
+/// and the badges of issued tokens as SVG.
+///
+/// The routes are `/metadata.json`, `/holders/<achievement principal>.json`, `/reputation/<principal or ICRC-1 account>.json`
+/// `/badges/<token id>.svg` and the DID document of the issuer at `/.well-known/did.json`. `/metrics` serves the metrics of the reputation module in the Prometheus
+/// text format; it changes on every call, so it is not certified and must be fetched through the raw domain.
+/// The following pages of the holders of an achievement, at `/holders/<achievement principal>.json?cursor=<ICRC-1 account>`,
+/// are not certified either and must also be fetched through the raw domain. The reputation of a principal covers all of its accounts, while the path of an account
+/// with a subaccount serves the part of the reputation earned by the achievements issued to that account.
+/// Responses are certified, so that browsers can fetch them through the boundary nodes. Only principals
+/// that hold an achievement, and achievements with at least one holder, are served. While responses are
+/// being certified in the background, requests for a response that is not certified yet get a `503`
+/// with a `Retry-After` header. `HEAD` requests get the headers of the response without its body and
+/// without the `IC-Certificate` header.
+///
+/// # Arguments
+///
+/// * `request` - The HTTP request.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The JSON document served at the requested path.
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" && request.method != "HEAD" {
+        return error_response(405, "Method not allowed");
+    }
+
+    let path = request.url.split(['?', '#']).next().unwrap_or_default();
+    let response = uncertified_response(&request, path).unwrap_or_else(|| certified_response(path));
+
+    if request.method == "HEAD" {
+        return head_response(response);
+    }
+
+    response
+}
+
+/// Turns the response of a `GET` request into the response of a `HEAD` request.
+///
+/// The body is dropped, and so is the `IC-Certificate` header, since the certified hash is the hash
+/// of the body and would not match the empty body.
+///
+/// # Arguments
+///
+/// * `response` - The response of the `GET` request.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The headers of the response without its body.
+fn head_response(mut response: HttpResponse) -> HttpResponse {
+    response.body.clear();
+    response.headers.retain(|(name, _)| !name.eq_ignore_ascii_case("IC-Certificate"));
+
+    response
+}
+
+/// Serves the certified response of a path.
+///
+/// # Arguments
+///
+/// * `path` - The path of the request, without query string.
+///
+/// # Returns
+///
+/// * `HttpResponse` - The certified response, or an error response if the path is unknown or not certified yet.
+fn certified_response(path: &str) -> HttpResponse {
+    let Some((content_type, body)) = render(path) else {
+        return error_response(404, "Not found");
+    };
+
+    let hash: Hash = Sha256::digest(&body).into();
+    let certified = CERTIFIED_RESPONSES.with(|c| c.borrow().get(path.as_bytes()) == Some(&hash));
+
+    if certified {
+        return HttpResponse {
+            status_code: 200,
+            headers: vec![
+                (String::from("Content-Type"), String::from(content_type)),
+                (String::from("Access-Control-Allow-Origin"), String::from("*")),
+                certificate_header(path)
+            ],
+            body
+        };
+    }
+
+    if is_certification_pending() {
+        let mut response = error_response(503, "Certification in progress");
+        response.headers.push((String::from("Retry-After"), String::from(RETRY_AFTER_SECONDS)));
+        return response;
+    }
+
+    error_response(404, "Not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLDER: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+
+    #[test]
+    fn parse_cursor_reads_the_cursor_parameter() {
+        let url = format!("/holders/ryjl3-tyaaa-aaaaa-aaaba-cai.json?format=json&cursor={}#top", HOLDER);
+
+        assert_eq!(parse_cursor(&url), Some(Ok(Account::from(Principal::from_text(HOLDER).unwrap()))));
+        assert_eq!(parse_cursor("/holders/ryjl3-tyaaa-aaaaa-aaaba-cai.json?cursor=nope"), Some(Err(())));
+        assert_eq!(parse_cursor("/holders/ryjl3-tyaaa-aaaaa-aaaba-cai.json"), None);
+    }
+
+    #[test]
+    fn reputation_paths_round_trip_through_their_account() {
+        let owner = Principal::from_text(HOLDER).unwrap();
+        let mut subaccount = [0u8; 32];
+        subaccount[31] = 1;
+        let account = Account { owner, subaccount: Some(subaccount) };
+
+        assert_eq!(account_reputation_path(&Account::from(owner)), reputation_path(owner));
+        assert_eq!(parse_account(&account_reputation_path(&account), REPUTATION_PREFIX), Some(account));
+        assert_eq!(parse_account(&reputation_path(owner), REPUTATION_PREFIX), Some(Account::from(owner)));
+        assert_eq!(parse_cursor(&format!("/holders/x.json?cursor={}", account)), Some(Ok(account)));
+    }
+
+    fn request(method: &str, url: &str) -> HttpRequest {
+        HttpRequest {
+            method: String::from(method),
+            url: String::from(url),
+            headers: vec![],
+            body: vec![]
+        }
+    }
+
+    fn header<'a>(response: &'a HttpResponse, name: &str) -> Option<&'a str> {
+        response.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn methods_other_than_get_and_head_are_not_allowed() {
+        for method in ["POST", "PUT", "DELETE", "get"] {
+            assert_eq!(http_request(request(method, METADATA_PATH)).status_code, 405);
+        }
+    }
+
+    #[test]
+    fn routes_render_their_documents() {
+        assert!(matches!(render(METADATA_PATH), Some((JSON_CONTENT_TYPE, _))));
+        assert!(render(&holders_path(Principal::from_text(HOLDER).unwrap())).is_none());
+        assert!(render(&reputation_path(Principal::from_text(HOLDER).unwrap())).is_none());
+        assert!(render("/unknown.json").is_none());
+        assert_eq!(parse_principal(&holders_path(Principal::from_text(HOLDER).unwrap()), HOLDERS_PREFIX), Some(Principal::from_text(HOLDER).unwrap()));
+        assert_eq!(parse_principal("/holders/not-a-principal.json", HOLDERS_PREFIX), None);
+        assert_eq!(parse_principal(&format!("/holders/{}.svg", HOLDER), HOLDERS_PREFIX), None);
+    }
+
+    #[test]
+    fn uncertified_responses_wait_for_pending_certification() {
+        let response = http_request(request("GET", &format!("{}?format=json", METADATA_PATH)));
+        assert_eq!(response.status_code, 404);
+        assert_eq!(http_request(request("GET", "/unknown.json")).status_code, 404);
+
+        CERTIFICATION_QUEUE.with(|q| q.borrow_mut().push_back(CertificationTask::Holders(None)));
+
+        let response = http_request(request("GET", METADATA_PATH));
+        assert_eq!(response.status_code, 503);
+        assert_eq!(header(&response, "Retry-After"), Some(RETRY_AFTER_SECONDS));
+        assert_eq!(http_request(request("GET", "/unknown.json")).status_code, 404);
+
+        let response = http_request(request("HEAD", METADATA_PATH));
+        assert_eq!(response.status_code, 503);
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn holders_cursor_is_validated() {
+        let path = holders_path(Principal::from_text(HOLDER).unwrap());
+
+        assert_eq!(http_request(request("GET", &format!("{}?cursor=nope", path))).status_code, 400);
+        assert_eq!(http_request(request("GET", &format!("{}?cursor={}", path, HOLDER))).status_code, 404);
+    }
+
+    #[test]
+    fn certification_tasks_of_an_achievement_replace_each_other() {
+        let first = Principal::from_text(HOLDER).unwrap();
+        let second = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+
+        assert!(CertificationTask::HoldersOf(first).same_set(&CertificationTask::HoldersOf(first)));
+        assert!(!CertificationTask::HoldersOf(first).same_set(&CertificationTask::HoldersOf(second)));
+        assert!(!CertificationTask::HoldersOf(first).same_set(&CertificationTask::Holders(None)));
+        assert!(CertificationTask::Badges(Some(1)).same_set(&CertificationTask::Badges(None)));
+        assert!(matches!(next_certification_batch(CertificationTask::HoldersOf(first)), (paths, None) if paths == vec![holders_path(first)]));
+    }
+
+    #[test]
+    fn certificate_header_carries_the_certificate_and_the_witness() {
+        CERTIFIED_RESPONSES.with(|c| c.borrow_mut().insert(String::from(METADATA_PATH), [7; 32]));
+
+        let (name, value) = build_certificate_header(b"certificate", METADATA_PATH);
+        let (certificate, tree) = value.split_once(", ").unwrap();
+        let tree = BASE64.decode(tree.strip_prefix("tree=:").unwrap().strip_suffix(':').unwrap()).unwrap();
+
+        assert_eq!(name, "IC-Certificate");
+        assert_eq!(certificate, format!("certificate=:{}:", BASE64.encode(b"certificate")));
+        assert_eq!(&tree[..3], &[0xd9, 0xd9, 0xf7]);
+    }
+
+    #[test]
+    fn head_response_drops_the_body_and_the_certificate() {
+        let response = head_response(HttpResponse {
+            status_code: 200,
+            headers: vec![
+                (String::from("Content-Type"), String::from(JSON_CONTENT_TYPE)),
+                (String::from("IC-Certificate"), String::from("certificate=::, tree=::"))
+            ],
+            body: b"{}".to_vec()
+        });
+
+        assert_eq!(response.status_code, 200);
+        assert!(response.body.is_empty());
+        assert_eq!(header(&response, "Content-Type"), Some(JSON_CONTENT_TYPE));
+        assert_eq!(header(&response, "IC-Certificate"), None);
+    }
+}
@@ -33,24 +33,23 @@ impl TokenIdentifier {
     }
 
     pub fn parse_token_index(&self, canister_id: Principal) -> Result<u128, ExtCommonError> {
-        let (canister, index) = self._parse_token_identifier();
-        if &canister[..] != canister_id.as_slice() {
-            return Err(ExtCommonError::InvalidToken(self.to_owned()));
+        match self._parse_token_identifier() {
+            Some((canister, index)) if canister[..] == *canister_id.as_slice() => Ok(index),
+            _ => Err(ExtCommonError::InvalidToken(self.to_owned()))
         }
-        Ok(index)
     }
 
-    fn _parse_token_identifier(&self) -> (Vec<u8>, u128) {
+    fn _parse_token_identifier(&self) -> Option<(Vec<u8>, u128)> {
         let array = Principal::from_text(self.0.clone())
-            .unwrap()
+            .ok()?
             .as_slice()
             .to_vec();
         // ic_cdk::println!("parse_token_identifier {:?}", array);
-        if array.len() <= 4 || &array[0..4] != TDS {
-            return (array, 0);
+        if array.len() <= 4 || array[0..4] != TDS {
+            return Some((array, 0));
         }
         if array.len() <= 8 {
-            return (array, 0);
+            return Some((array, 0));
         }
         let canister = array[4..array.len() - 4].to_vec();
         let index = &array[array.len() - 4..array.len()];
@@ -58,11 +57,11 @@ impl TokenIdentifier {
             | (index[1] as u128) << 16
             | (index[2] as u128) << 8
             | (index[3] as u128);
-        (canister, index)
+        Some((canister, index))
     }
 }
 
-#[derive(CandidType, Clone, Copy, Debug, Deserialize)]
+#[derive(CandidType, Clone, Copy, Debug, Default, Deserialize)]
 pub struct AccountIdentifier([u8; 32]);
 
 pub type AccountIdentifierHex = String;
@@ -94,11 +93,9 @@ impl AccountIdentifier {
     pub fn to_hex(&self) -> String {
         hex::encode(self.0)
     }
-}
 
-impl Default for AccountIdentifier {
-    fn default() -> Self {
-        AccountIdentifier([0u8; 32])
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
     }
 }
 
@@ -249,3 +246,22 @@ pub enum ExtSupplyResult {
     #[serde(rename = "err")]
     Err(ExtCommonError),
 }
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ExtListing {
+    pub locked: Option<u64>,
+    pub seller: Principal,
+    pub price: u64,
+}
+
+pub type ExtTokensEntry = (ExtTokenIndex, Option<ExtListing>, Option<Vec<u8>>);
+
+#[derive(CandidType, Clone, Deserialize)]
+pub enum ExtTokensResult {
+    #[serde(rename = "ok")]
+    Ok(Vec<ExtTokensEntry>),
+    #[serde(rename = "err")]
+    Err(ExtCommonError),
+}
+
+pub type ExtRegistryEntry = (ExtTokenIndex, AccountIdentifierHex);
@@ -2,6 +2,7 @@
 
 use ic_cdk_macros::export_candid;
 use candid::Principal;
+use icrc_ledger_types::icrc1::account::Account;
 
 pub mod storable;
 pub mod icrc_7;
@@ -10,8 +11,22 @@ pub mod state;
 pub mod utils;
 pub mod access;
 pub mod logic;
+pub mod ext;
+pub mod errors;
+pub mod migrations;
+pub mod backup;
+pub mod pause;
+pub mod status;
+pub mod http;
+pub mod badge;
+pub mod metrics;
+pub mod ecdsa;
+pub mod credential;
+pub mod guard;
 
 use types::*;
 use storable::*;
+use icrc_7::ext_types::*;
+use errors::*;
 
 export_candid!();
\ No newline at end of file
@@ -1,51 +1,95 @@
 //! This module contains the logic for issuing achievements and managing reputation.
 
-use candid::Principal;
+use candid::{Principal, Reserved};
+use futures::future::join_all;
 use icrc_ledger_types::icrc1::account::Account;
-use ic_cdk::update;
+use ic_cdk::{query, update};
+use ic_cdk::api::call::RejectionCode;
 
 use crate::icrc_7::types::{MintArg, MintResult};
-use crate::types::AchievementMetadata;
+use crate::types::{AchievementMetadata, DecayedReputation, DecayedReputationEntry, BatchIssueResult};
+use crate::storable::ReputationEntry;
 use crate::state::{
     get_reputation_module_metadata,
-    increment_total_issued,
+    reserve_token_ids,
+    release_token_ids,
     is_canister_allowed,
-    get_achievement_metadata,
+    fetch_achievement_metadata,
     get_principal_achievement_sum_status,
-    _change_principal_achievement_sum_status_to_issued
+    get_accounts_issued,
+    _change_account_achievement_status_to_issued,
+    _record_issuance,
+    _record_revocation,
+    _remove_issuance,
+    _change_account_achievement_status_to_revoked,
+    _add_reputation,
+    _subtract_reputation,
+    get_reputation,
+    get_reputation_of_account,
+    get_achievement_decay,
+    get_issuance_of,
+    get_issuance_of_account,
+    ensure_score_fits,
+    verify_module_hash
 };
+use crate::access::is_admin;
+use crate::badge::badge_url;
+use crate::credential::_forget_credential;
+use crate::http::certify_holding;
+use crate::metrics::{observe_issuance, observe_revocation};
+use crate::pause::ensure_not_paused;
+use crate::guard::PendingGuard;
+use crate::status::ensure_cycles_available;
+use crate::errors::{AccessError, CallError, EligibilityError, IssuanceError, LedgerError, ReputationError};
 
-/// Issues an achievement to a principal.
+/// Issues an achievement to an account.
 ///
-/// This function mints a new achievement token and assigns it to the specified principal.
+/// This function mints a new achievement token and assigns it to the specified account.
+/// If the collection refuses the mint, the reserved token id is released and nothing is recorded.
+/// If the token id cannot be released, the release error is returned instead of the mint error.
+/// If the call is rejected, the token may still have been minted, so its id stays reserved.
 ///
 /// # Arguments
 ///
-/// * `principal` - The principal to whom the achievement will be issued.
+/// * `to` - The account to which the achievement will be issued.
+/// * `achievement` - The principal of the achievement canister.
 /// * `achievement_metadata` - Metadata of the achievement to be issued.
 ///
 /// # Returns
 ///
-/// * `Result<MintResult, String>` - The result of the minting operation.
-async fn issue_achievement(principal: Principal, achievement_metadata: AchievementMetadata) -> Result<MintResult, String> {
+/// * `Result<u128, ReputationError>` - The result of the minting operation.
+async fn issue_achievement(to: Account, achievement: Principal, achievement_metadata: AchievementMetadata) -> Result<u128, ReputationError> {
+    ensure_not_paused(achievement)?;
+    ensure_cycles_available()?;
+
     let reputation_metadata = get_reputation_module_metadata();
+    let token_id = reserve_token_ids(1)?[0];
 
-    let mint_result: (MintResult, ) = ic_cdk::call(reputation_metadata.achievement_collection, "icrc7_mint", (MintArg {
+    let mint_result: Result<(MintResult, ), _> = ic_cdk::call(reputation_metadata.achievement_collection, "icrc7_mint", (MintArg {
         from_subaccount: None,
-        token_id: reputation_metadata.total_issued + 1,
-        token_logo: None,
+        token_id,
+        token_logo: Some(badge_url(token_id)),
         token_name: Some(achievement_metadata.achievement_name),
         memo: None,
         token_description: Some(achievement_metadata.achievement_description),
-        to: Account {
-            owner: principal,
-            subaccount: None
-        }
-    },)).await.unwrap();
+        to
+    },)).await;
 
-    increment_total_issued()?;
+    let n = match mint_result {
+        Ok((Ok(n), )) => n,
+        Ok((Err(err), )) => {
+            release_token_ids(&[token_id])?;
+            return Err(err.into());
+        },
+        Err(rejection) => {
+            return Err(LedgerError::CallFailed(
+                CallError::new(reputation_metadata.achievement_collection, "icrc7_mint", rejection)
+            ).into());
+        }
+    };
+    _record_issuance(token_id, to, achievement)?;
 
-    Ok(mint_result.0)   
+    Ok(n)   
 }
 
 /// Issues an achievement to the caller's identity wallet.
@@ -56,10 +100,12 @@ async fn issue_achievement(principal: Principal, achievement_metadata: Achieveme
 /// # Arguments
 ///
 /// * `achievement` - The principal of the achievement canister.
+/// * `to` - The account receiving the achievement. It must be owned by the caller and defaults
+///   to the default subaccount of the caller.
 ///
 /// # Returns
 ///
-/// * `Result<u128, String>` - The result of the issuance operation.
+/// * `Result<u128, ReputationError>` - The result of the issuance operation.
 ///
 /// # Example
 ///
@@ -69,34 +115,452 @@ async fn issue_achievement(principal: Principal, achievement_metadata: Achieveme
 /// (variant { Ok = "Achievement issued" })
 /// ```
 #[update(name = "issueAchievementToIdentityWallet")]
-async fn issue_achievement_to_identity_wallet(achievement: Principal) -> Result<u128, String> {
+async fn issue_achievement_to_identity_wallet(achievement: Principal, to: Option<Account>) -> Result<u128, ReputationError> {
+    let result = claim_achievement(achievement, to).await;
+    observe_issuance(&result);
+
+    result
+}
+
+/// Issues an achievement claimed by the caller, as described in `issue_achievement_to_identity_wallet`.
+async fn claim_achievement(achievement: Principal, to: Option<Account>) -> Result<u128, ReputationError> {
     let canister_permission = is_canister_allowed(achievement)?;
 
     if !canister_permission.0 {
-        return Err(String::from("Achievement not allowed"));
+        return Err(EligibilityError::AchievementNotAllowed.into());
+    }
+
+    ensure_not_paused(achievement)?;
+
+    let caller = ic_cdk::api::caller();
+    let to = to.unwrap_or(Account {
+        owner: caller,
+        subaccount: None
+    });
+
+    if to.owner != caller {
+        return Err(AccessError::AccountNotOwned.into());
+    }
+
+    if get_principal_achievement_sum_status(caller, achievement) {
+        return Err(EligibilityError::AlreadyIssued.into());
+    }
+
+    ensure_score_fits(caller, achievement)?;
+    let _pending = PendingGuard::issuance(caller, achievement)?;
+    let achievement_metadata = check_achievement_status(caller, achievement).await?;
+
+    let n = issue_achievement(to, achievement, achievement_metadata).await?;
+    _change_account_achievement_status_to_issued(&to, achievement)?;
+    _add_reputation(caller, achievement)?;
+    certify_holding(&to, achievement);
+
+    Ok(n)
+}
+
+/// Issues an achievement on behalf of an identity wallet, initiated by the achievement canister.
+///
+/// This function lets an allowed achievement canister push its achievement to an identity wallet
+/// right after the wallet became eligible, so that the wallet does not have to claim it.
+/// The caller must be an allowed achievement canister.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `to` - The account receiving the achievement. It must be owned by the identity wallet and
+///   defaults to the default subaccount of the identity wallet.
+///
+/// # Returns
+///
+/// * `Result<u128, ReputationError>` - The result of the issuance operation.
+#[update(name = "issueAchievementFromAchievementCanister")]
+async fn issue_achievement_from_achievement_canister(identity_wallet: Principal, to: Option<Account>) -> Result<u128, ReputationError> {
+    let result = issue_pushed_achievement(identity_wallet, to).await;
+    observe_issuance(&result);
+
+    result
+}
+
+/// Issues an achievement pushed by the calling achievement canister, as described in `issue_achievement_from_achievement_canister`.
+async fn issue_pushed_achievement(identity_wallet: Principal, to: Option<Account>) -> Result<u128, ReputationError> {
+    let achievement = ic_cdk::api::caller();
+    let canister_permission = is_canister_allowed(achievement)?;
+
+    if !canister_permission.0 {
+        return Err(EligibilityError::AchievementNotAllowed.into());
+    }
+
+    ensure_not_paused(achievement)?;
+
+    let to = to.unwrap_or(Account {
+        owner: identity_wallet,
+        subaccount: None
+    });
+
+    if to.owner != identity_wallet {
+        return Err(AccessError::AccountNotOwned.into());
+    }
+
+    if get_principal_achievement_sum_status(identity_wallet, achievement) {
+        return Err(EligibilityError::AlreadyIssued.into());
     }
 
+    ensure_score_fits(identity_wallet, achievement)?;
+    let _pending = PendingGuard::issuance(identity_wallet, achievement)?;
+    verify_module_hash(achievement).await?;
+
+    let achievement_metadata = fetch_achievement_metadata(achievement).await?;
+
+    let n = issue_achievement(to, achievement, achievement_metadata).await?;
+    _change_account_achievement_status_to_issued(&to, achievement)?;
+    _add_reputation(identity_wallet, achievement)?;
+    certify_holding(&to, achievement);
+
+    Ok(n)
+}
+
+/// Checks that the caller may receive an achievement and fetches its metadata.
+///
+/// The achievement canister must also still run the module it was approved with. An error returned
+/// by the achievement canister instead of a status, such as a status not found, is passed on as
+/// `StatusUnavailable` rather than read as a status of `0`.
+///
+/// # Arguments
+///
+/// * `caller` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<AchievementMetadata, ReputationError>` - The metadata of the achievement if the caller is allowed to receive it.
+async fn check_achievement_status(caller: Principal, achievement: Principal) -> Result<AchievementMetadata, ReputationError> {
+    let (module_hash, status, metadata) = futures::join!(
+        verify_module_hash(achievement),
+        ic_cdk::call::<_, (Result<u8, Reserved>, )>(achievement, "getPrincipalToAchievementStatusValue", (caller,)),
+        fetch_achievement_metadata(achievement)
+    );
+
+    module_hash?;
+    let status = status.map_err(|rejection| IssuanceError::CallFailed(
+        CallError::new(achievement, "getPrincipalToAchievementStatusValue", rejection)
+    ))?.0.map_err(|_| EligibilityError::StatusUnavailable)?;
+    let metadata = metadata?;
+
+    if status != 1_u8 {
+        return Err(EligibilityError::NotEligible.into());
+    }
+
+    Ok(metadata)
+}
+
+/// Mints several achievement tokens on the achievement collection.
+///
+/// The tokens are minted with a single `icrc7_batch_mint` call, and with one `icrc7_mint` call per
+/// token only when the collection has no `icrc7_batch_mint` method. Any other rejection of the batch
+/// call is returned for every token. A batch result with fewer entries than tokens does not tell
+/// which tokens were minted, so it fails the whole batch with `IncompleteBatch`.
+///
+/// # Arguments
+///
+/// * `collection` - The principal of the achievement collection.
+/// * `mint_args` - The tokens to mint.
+///
+/// # Returns
+///
+/// * `Vec<Result<u128, ReputationError>>` - The result of the minting operation for every token, in order.
+async fn mint_achievements(collection: Principal, mint_args: Vec<MintArg>) -> Vec<Result<u128, ReputationError>> {
+    let batch: Result<(Vec<MintResult>, ), _> = ic_cdk::call(collection, "icrc7_batch_mint", (mint_args.clone(),)).await;
+
+    match batch {
+        Ok((results, )) if results.len() == mint_args.len() => {
+            return results.into_iter().map(|result| result.map_err(ReputationError::from)).collect();
+        },
+        Ok((results, )) => {
+            let err: ReputationError = LedgerError::IncompleteBatch {
+                expected: mint_args.len() as u64,
+                returned: results.len() as u64
+            }.into();
+            return vec![Err(err); mint_args.len()];
+        },
+        Err(rejection) if !is_method_not_found(&rejection) => {
+            let err: ReputationError = LedgerError::CallFailed(
+                CallError::new(collection, "icrc7_batch_mint", rejection)
+            ).into();
+            return vec![Err(err); mint_args.len()];
+        },
+        Err(_) => {}
+    }
+
+    let mut results = vec![];
+
+    for mint_arg in mint_args {
+        let mint_result: Result<(MintResult, ), _> = ic_cdk::call(collection, "icrc7_mint", (mint_arg,)).await;
+        results.push(match mint_result {
+            Ok((result, )) => result.map_err(ReputationError::from),
+            Err(rejection) => Err(LedgerError::CallFailed(
+                CallError::new(collection, "icrc7_mint", rejection)
+            ).into())
+        });
+    }
+
+    results
+}
+
+/// Tells whether a call was rejected because the canister has no such method.
+fn is_method_not_found(rejection: &(RejectionCode, String)) -> bool {
+    rejection.0 == RejectionCode::CanisterError
+        && (rejection.1.contains("IC0302") || rejection.1.contains("has no update method"))
+}
+
+/// Tells whether a failed mint is known to have left its token unminted.
+///
+/// Only an error returned by the collection proves that the token was not minted. A rejected call
+/// or an incomplete batch result may hide a minted token, whose id must then stay reserved.
+fn is_mint_refused(err: &ReputationError) -> bool {
+    matches!(err, ReputationError::Ledger(LedgerError::Mint(_)))
+}
+
+/// Issues several achievements to the caller's identity wallet in one call.
+///
+/// The eligibility of the caller is checked for every achievement in parallel, then all eligible
+/// achievements are minted together. Each achievement gets its own result, so a failure does not
+/// prevent the other achievements from being issued. The token ids reserved for achievements whose
+/// mint was refused by the collection are released, and a failure to release them is returned as
+/// their result; the ids of mints with an unknown outcome stay reserved.
+///
+/// # Arguments
+///
+/// * `achievements` - The principals of the achievement canisters.
+/// * `to` - The account receiving the achievements. It must be owned by the caller and defaults
+///   to the default subaccount of the caller.
+///
+/// # Returns
+///
+/// * `Vec<BatchIssueResult>` - The result of the issuance for every achievement, in order.
+///
+/// # Example
+///
+/// ```bash
+/// dfx --identity pa_identity_wallet canister call reputation_module issueAchievementsToIdentityWallet "(vec { principal \"$(dfx canister id achievement)\" }, null)"
+/// ```
+#[update(name = "issueAchievementsToIdentityWallet")]
+async fn issue_achievements_to_identity_wallet(achievements: Vec<Principal>, to: Option<Account>) -> Vec<BatchIssueResult> {
     let caller = ic_cdk::api::caller();
-    let status: (Result<u8, String>, ) = ic_cdk::call(achievement, "getPrincipalToAchievementStatusValue", (caller,)).await.unwrap();
-    let status_result = status.0.unwrap();
-    let achievement_metadata = get_achievement_metadata(achievement).await.unwrap();
+    let to = to.unwrap_or(Account {
+        owner: caller,
+        subaccount: None
+    });
+
+    let mut results: Vec<Result<u128, ReputationError>> = vec![];
+    let mut candidates: Vec<usize> = vec![];
+    let mut pending: Vec<PendingGuard> = vec![];
+
+    for (i, achievement) in achievements.iter().enumerate() {
+        let result = if to.owner != caller {
+            Err(AccessError::AccountNotOwned.into())
+        } else if achievements[..i].contains(achievement) {
+            Err(EligibilityError::DuplicateAchievement.into())
+        } else {
+            match is_canister_allowed(*achievement).and_then(|permission| ensure_not_paused(*achievement).map(|_| permission)) {
+                Ok(permission) if !permission.0 => Err(EligibilityError::AchievementNotAllowed.into()),
+                Err(err) => Err(err),
+                Ok(_) if get_principal_achievement_sum_status(caller, *achievement) => Err(EligibilityError::AlreadyIssued.into()),
+                Ok(_) => ensure_score_fits(caller, *achievement).and_then(|_| PendingGuard::issuance(caller, *achievement)).map(|guard| {
+                    pending.push(guard);
+                    candidates.push(i);
+                    0
+                })
+            }
+        };
+        results.push(result);
+    }
+
+    let statuses = join_all(candidates.iter().map(|&i| check_achievement_status(caller, achievements[i]))).await;
+
+    let mut eligible: Vec<(usize, AchievementMetadata)> = vec![];
+
+    for (i, status) in candidates.into_iter().zip(statuses) {
+        match status.and_then(|metadata| ensure_not_paused(achievements[i]).map(|_| metadata)) {
+            Ok(metadata) => eligible.push((i, metadata)),
+            Err(err) => results[i] = Err(err)
+        }
+    }
+
+    if !eligible.is_empty() {
+        let reputation_metadata = get_reputation_module_metadata();
+        let token_ids = match ensure_cycles_available().and_then(|_| reserve_token_ids(eligible.len())) {
+            Ok(token_ids) => token_ids,
+            Err(err) => {
+                for (i, _) in eligible {
+                    results[i] = Err(err.clone());
+                }
+                return build_batch_results(achievements, results);
+            }
+        };
+
+        let mint_args: Vec<MintArg> = eligible.iter().zip(&token_ids).map(|((_, metadata), &token_id)| MintArg {
+            from_subaccount: None,
+            token_id,
+            token_logo: Some(badge_url(token_id)),
+            token_name: Some(metadata.achievement_name.clone()),
+            memo: None,
+            token_description: Some(metadata.achievement_description.clone()),
+            to
+        }).collect();
+
+        let mint_results = mint_achievements(reputation_metadata.achievement_collection, mint_args).await;
+
+        let failed_token_ids: Vec<u128> = token_ids.iter()
+            .zip(&mint_results)
+            .filter(|(_, mint_result)| mint_result.as_ref().is_err_and(is_mint_refused))
+            .map(|(&token_id, _)| token_id)
+            .collect();
+        let released = release_token_ids(&failed_token_ids);
+
+        for (((i, _), token_id), mint_result) in eligible.into_iter().zip(token_ids).zip(mint_results) {
+            let achievement = achievements[i];
+
+            results[i] = match mint_result {
+                Ok(n) => {
+                    _record_issuance(token_id, to, achievement)
+                        .and_then(|_| _change_account_achievement_status_to_issued(&to, achievement))
+                        .and_then(|_| _add_reputation(caller, achievement).map(|_| n))
+                        .inspect(|_| certify_holding(&to, achievement))
+                },
+                Err(err) if is_mint_refused(&err) => Err(released.as_ref().err().cloned().unwrap_or(err)),
+                Err(err) => Err(err)
+            };
+        }
+    }
+
+    drop(pending);
+    build_batch_results(achievements, results)
+}
+
+/// Pairs every achievement of a batch with the result of its issuance.
+fn build_batch_results(achievements: Vec<Principal>, results: Vec<Result<u128, ReputationError>>) -> Vec<BatchIssueResult> {
+    results.iter().for_each(observe_issuance);
+
+    achievements
+        .into_iter()
+        .zip(results)
+        .map(|(achievement, result)| BatchIssueResult { achievement, result })
+        .collect()
+}
+
+/// Revokes an achievement issued to an identity wallet.
+///
+/// This function clears the issued status of the achievement in every account of the identity
+/// wallet holding it and removes its score from the
+/// reputation of the identity wallet. The ICRC-7 token itself is left in the collection, since
+/// only its holder may burn it; the revocation is recorded and listed by `getRevokedTokens`, so
+/// that the token can be told apart from the tokens backing a reputation.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the revocation.
+#[update(name = "revokeAchievement")]
+fn revoke_achievement(identity_wallet: Principal, achievement: Principal) -> Result<(), ReputationError> {
+    let result = _revoke_achievement(identity_wallet, achievement);
+    observe_revocation(&result);
+
+    result
+}
 
-    let issued_status = get_principal_achievement_sum_status(caller, achievement);
+/// Revokes an achievement, as described in `revoke_achievement`.
+fn _revoke_achievement(identity_wallet: Principal, achievement: Principal) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    let accounts = get_accounts_issued(identity_wallet, achievement);
 
-    if(issued_status) {
-        return Err(String::from("Achievement already issued"));
+    if accounts.is_empty() {
+        return Err(EligibilityError::NotIssued.into());
     }
 
-    if status_result == 1_u8 {
-        let result = issue_achievement(caller, achievement_metadata).await.unwrap();
-        match result {
-            Ok(n) => {
-                _change_principal_achievement_sum_status_to_issued(caller, achievement)?;
-                return Ok(n)
-            },
-            Err(_) => return Err(String::from("Mint Error"))
+    for account in &accounts {
+        _change_account_achievement_status_to_revoked(account, achievement)?;
+        if let Some((token_id, issuance)) = _remove_issuance(account, achievement) {
+            _record_revocation(token_id, issuance);
         }
-    } else {
-        Err(String::from("You`re not allowed"))
     }
-}
\ No newline at end of file
+    _forget_credential(identity_wallet, achievement);
+    _subtract_reputation(identity_wallet, achievement)?;
+    accounts.iter().for_each(|account| certify_holding(account, achievement));
+
+    Ok(())
+}
+
+/// Retrieves the reputation of a principal with the decay of every achievement applied.
+///
+/// The score of each issued achievement is decayed according to the decay model of its
+/// achievement canister and the time elapsed since the achievement was minted.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `DecayedReputation` - The decayed total score of the principal and its breakdown by achievement.
+#[query(name = "getDecayedReputation")]
+fn get_decayed_reputation(identity_wallet: Principal) -> DecayedReputation {
+    decay_reputation(get_reputation(identity_wallet).breakdown, |achievement| {
+        get_issuance_of(identity_wallet, achievement).map(|(_, issuance)| issuance.issued_at)
+    })
+}
+
+/// Retrieves the part of a principal's reputation earned by the achievements issued to one of its
+/// accounts, with the decay of every achievement applied.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+///
+/// # Returns
+///
+/// * `DecayedReputation` - The decayed total score of the account and its breakdown by achievement.
+#[query(name = "getDecayedReputationOfAccount")]
+fn get_decayed_reputation_of_account(account: Account) -> DecayedReputation {
+    decay_reputation(get_reputation_of_account(account).breakdown, |achievement| {
+        get_issuance_of_account(&account, achievement).map(|(_, issuance)| issuance.issued_at)
+    })
+}
+
+/// Applies the decay of every achievement to the entries of a reputation.
+///
+/// Entries whose issuance time is unknown are not decayed.
+///
+/// # Arguments
+///
+/// * `breakdown` - The entries of the reputation.
+/// * `issued_at` - Looks up the issuance time of an achievement.
+///
+/// # Returns
+///
+/// * `DecayedReputation` - The decayed total score and its breakdown by achievement.
+fn decay_reputation(breakdown: Vec<ReputationEntry>, issued_at: impl Fn(Principal) -> Option<u64>) -> DecayedReputation {
+    let now = ic_cdk::api::time();
+    let mut decayed_reputation = DecayedReputation::default();
+
+    for entry in breakdown {
+        let issued_at = issued_at(entry.achievement).unwrap_or(now);
+        let age_seconds = now.saturating_sub(issued_at) / 1_000_000_000;
+        let score = get_achievement_decay(entry.achievement).apply(entry.score, age_seconds);
+
+        decayed_reputation.total += score;
+        decayed_reputation.breakdown.push(DecayedReputationEntry {
+            achievement: entry.achievement,
+            score,
+            issued_at
+        });
+    }
+
+    decayed_reputation
+}
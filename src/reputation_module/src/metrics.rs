@@ -0,0 +1,176 @@
+//! This module counts issuances and failures and exports them, with resource gauges, in the Prometheus text format.
+
+use ic_metrics_encoder::MetricsEncoder;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableCell;
+use std::cell::RefCell;
+use std::io;
+
+use crate::errors::ReputationError;
+use crate::state::{MEMORY_MANAGER, ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT, get_reputation_module_metadata};
+use crate::status::get_low_cycles_threshold;
+use crate::storable::{Memory, MetricCounters, FailureCount};
+
+/// The size of a WebAssembly page, in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+thread_local! {
+    pub(crate) static METRIC_COUNTERS: RefCell<StableCell<MetricCounters, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))), MetricCounters::default(),
+        ).unwrap()
+    );
+}
+
+/// Applies a change to the metric counters.
+fn update_counters(f: impl FnOnce(&mut MetricCounters)) {
+    METRIC_COUNTERS.with(|c| {
+        let mut cell = c.borrow_mut();
+        let mut counters = cell.get().clone();
+        f(&mut counters);
+        cell.set(counters)
+    }).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
+
+/// Counts a failed call of an operation and records the time of the error.
+///
+/// # Arguments
+///
+/// * `operation` - The name of the failed operation.
+/// * `err` - The error returned by the operation.
+pub fn record_failure(operation: &str, err: &ReputationError) {
+    let reason = err.reason();
+
+    update_counters(|counters| {
+        match counters.failures.iter_mut().find(|f| f.operation == operation && f.reason == reason) {
+            Some(failure) => failure.count += 1,
+            None => counters.failures.push(FailureCount {
+                operation: String::from(operation),
+                reason: String::from(reason),
+                count: 1
+            })
+        }
+        counters.last_error_at = Some(ic_cdk::api::time());
+    });
+}
+
+/// Counts a claimed achievement and its outcome.
+///
+/// # Arguments
+///
+/// * `result` - The result of the issuance.
+pub fn observe_issuance<T>(result: &Result<T, ReputationError>) {
+    match result {
+        Ok(_) => update_counters(|counters| {
+            counters.claims += 1;
+            counters.mints += 1;
+        }),
+        Err(err) => {
+            update_counters(|counters| counters.claims += 1);
+            record_failure("issue", err);
+        }
+    }
+}
+
+/// Counts a revocation and its outcome.
+///
+/// # Arguments
+///
+/// * `result` - The result of the revocation.
+pub fn observe_revocation<T>(result: &Result<T, ReputationError>) {
+    match result {
+        Ok(_) => update_counters(|counters| counters.revocations += 1),
+        Err(err) => record_failure("revoke", err)
+    }
+}
+
+/// Counts a credential request and its outcome.
+///
+/// Only newly signed credentials are counted as signatures; cached credentials returned again are not.
+///
+/// # Arguments
+///
+/// * `result` - The result of the credential request, `true` if a new credential was signed.
+pub fn observe_credential<T>(result: &Result<(T, bool), ReputationError>) {
+    match result {
+        Ok((_, true)) => update_counters(|counters| {
+            counters.credential_signatures = Some(counters.credential_signatures.unwrap_or(0) + 1);
+        }),
+        Ok(_) => {},
+        Err(err) => record_failure("credential", err)
+    }
+}
+
+/// Retrieves the number of signed credentials and of failed credential requests.
+///
+/// # Returns
+///
+/// * `(u64, u64)` - The number of credential signatures and of credential failures.
+pub fn get_credential_counts() -> (u64, u64) {
+    let counters = METRIC_COUNTERS.with(|c| c.borrow().get().clone());
+    let failures = counters.failures.iter()
+        .filter(|f| f.operation == "credential")
+        .map(|f| f.count)
+        .sum();
+
+    (counters.credential_signatures.unwrap_or(0), failures)
+}
+
+/// Retrieves the size of the heap memory, in bytes.
+fn heap_memory_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return 0;
+}
+
+/// Writes every metric of the reputation module.
+fn encode_metrics(encoder: &mut MetricsEncoder<Vec<u8>>) -> io::Result<()> {
+    let counters = METRIC_COUNTERS.with(|c| c.borrow().get().clone());
+
+    encoder.encode_counter("reputation_claims_total", counters.claims as f64, "Number of achievements claimed or pushed for issuance.")?;
+    encoder.encode_counter("reputation_mints_total", counters.mints as f64, "Number of achievement tokens minted and recorded.")?;
+    encoder.encode_counter("reputation_revocations_total", counters.revocations as f64, "Number of achievements revoked.")?;
+    encoder.encode_counter(
+        "reputation_credential_signatures_total",
+        counters.credential_signatures.unwrap_or(0) as f64,
+        "Number of verifiable credentials signed."
+    )?;
+
+    let mut failures = encoder.counter_vec("reputation_failures_total", "Number of failed operations by reason.")?;
+    for failure in &counters.failures {
+        failures = failures.value(&[("operation", &failure.operation), ("reason", &failure.reason)], failure.count as f64)?;
+    }
+
+    let mut holders = encoder.gauge_vec("reputation_holders", "Number of holders of every achievement.")?;
+    for (achievement, count) in ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| h.borrow().iter().collect::<Vec<_>>()) {
+        holders = holders.value(&[("achievement", &achievement.0.to_text())], count as f64)?;
+    }
+
+    encoder.encode_gauge("reputation_total_issued", get_reputation_module_metadata().total_issued as f64, "Number of token ids reserved for issued achievements.")?;
+    encoder.encode_gauge("reputation_cycles_balance", ic_cdk::api::canister_balance128() as f64, "Cycles balance of the reputation module.")?;
+    encoder.encode_gauge("reputation_low_cycles_threshold", get_low_cycles_threshold() as f64, "Cycles balance below which issuance is refused.")?;
+    encoder.encode_gauge("reputation_stable_memory_bytes", (ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE) as f64, "Size of the stable memory, in bytes.")?;
+    encoder.encode_gauge("reputation_heap_memory_bytes", heap_memory_bytes() as f64, "Size of the heap memory, in bytes.")?;
+    encoder.encode_gauge(
+        "reputation_last_error_timestamp_seconds",
+        counters.last_error_at.map_or(0.0, |at| (at / 1_000_000_000) as f64),
+        "Time of the last failed operation, in seconds since the epoch, or 0 if none failed."
+    )?;
+
+    Ok(())
+}
+
+/// Renders the metrics of the reputation module in the Prometheus text exposition format.
+///
+/// # Returns
+///
+/// * `Vec<u8>` - The metrics document.
+pub fn render_metrics() -> Vec<u8> {
+    let mut encoder = MetricsEncoder::new(vec![], (ic_cdk::api::time() / 1_000_000) as i64);
+
+    encode_metrics(&mut encoder).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+
+    encoder.into_inner()
+}
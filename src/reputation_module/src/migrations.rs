@@ -0,0 +1,331 @@
+//! This module versions the stable-memory schema of the reputation module and migrates older layouts after an upgrade.
+
+use ic_cdk::query;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+
+use crate::storable::{Memory, MigrationReport};
+use crate::state::{
+    MEMORY_MANAGER, get_schema_version, _set_schema_version, _migrate_principal_sums, _rebuild_leaderboard,
+    _rebuild_account_achievements, _migrate_account_keys, _rebuild_holder_counts, _rebuild_achievements, _rebuild_issuance_counts
+};
+
+/// The stable-memory schema version written by this build.
+///
+/// Canisters deployed before the schema was versioned report version `0`.
+pub const SCHEMA_VERSION: u32 = 6;
+
+thread_local! {
+    static SCHEMA_VERSION_TO_MIGRATION_REPORT: RefCell<StableBTreeMap<u32, MigrationReport, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31))),
+        )
+    );
+}
+
+/// A migration upgrading the stable memory from the previous schema version.
+///
+/// `migrate` returns the number of entries it wrote and the keys it could not migrate.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    migrate: fn() -> (u64, Vec<String>),
+}
+
+/// The migrations of the schema, in ascending order of version.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Re-key issued statuses from legacy principal sums to principal pairs",
+        migrate: migrate_principal_sums,
+    },
+    Migration {
+        version: 2,
+        description: "Rebuild the leaderboard, held achievements, holder counts and catalog indexes",
+        migrate: rebuild_indexes,
+    },
+    Migration {
+        version: 3,
+        description: "Number the issued tokens of every achievement for their badges",
+        migrate: number_issuances,
+    },
+    Migration {
+        version: 4,
+        description: "Index the holders of achievements by EXT account identifier",
+        migrate: index_accounts,
+    },
+    Migration {
+        version: 5,
+        description: "Count the ranked principals of every score",
+        migrate: count_scores,
+    },
+    Migration {
+        version: 6,
+        description: "Key issued statuses, holders and held achievements by account",
+        migrate: key_by_account,
+    },
+];
+
+fn migrate_principal_sums() -> (u64, Vec<String>) {
+    _migrate_principal_sums()
+}
+
+fn rebuild_indexes() -> (u64, Vec<String>) {
+    let rebuilt = _rebuild_leaderboard()
+        + _rebuild_account_achievements()
+        + _rebuild_holder_counts()
+        + _rebuild_achievements();
+    (rebuilt, vec![])
+}
+
+fn number_issuances() -> (u64, Vec<String>) {
+    (_rebuild_issuance_counts(), vec![])
+}
+
+fn index_accounts() -> (u64, Vec<String>) {
+    (_rebuild_account_achievements(), vec![])
+}
+
+fn count_scores() -> (u64, Vec<String>) {
+    (_rebuild_leaderboard(), vec![])
+}
+
+fn key_by_account() -> (u64, Vec<String>) {
+    (_migrate_account_keys(), vec![])
+}
+
+/// Runs every migration newer than the stored schema version.
+///
+/// The schema version is bumped after each migration, so that an upgrade interrupted by a trap
+/// resumes from the last applied migration. Traps if the stored schema is newer than this build,
+/// which prevents downgrading to a build unable to read it.
+pub fn run_migrations() {
+    let stored_version = get_schema_version();
+
+    if stored_version > SCHEMA_VERSION {
+        ic_cdk::trap(&format!(
+            "Stable memory schema version {} is newer than the supported version {}",
+            stored_version, SCHEMA_VERSION
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > stored_version) {
+        ic_cdk::println!("Migrating stable memory to schema version {}: {}", migration.version, migration.description);
+        let report = apply_migration(migration, ic_cdk::api::time());
+        if !report.unmigrated_keys.is_empty() {
+            ic_cdk::println!("{} keys could not be migrated, see getMigrationReports", report.unmigrated_keys.len());
+        }
+    }
+}
+
+/// Applies a migration, bumps the schema version and stores the report of the migration.
+///
+/// # Arguments
+///
+/// * `migration` - The migration to apply.
+/// * `migrated_at` - The time of the upgrade, in nanoseconds since the epoch.
+///
+/// # Returns
+///
+/// * `MigrationReport` - The report of the migration.
+fn apply_migration(migration: &Migration, migrated_at: u64) -> MigrationReport {
+    let (migrated, unmigrated_keys) = (migration.migrate)();
+    _set_schema_version(migration.version);
+
+    let report = MigrationReport { version: migration.version, migrated_at, migrated, unmigrated_keys };
+    SCHEMA_VERSION_TO_MIGRATION_REPORT.with(|r| r.borrow_mut().insert(migration.version, report.clone()));
+    report
+}
+
+/// Retrieves the reports of the migrations applied to the stable memory.
+///
+/// Migrations applied before the reports were introduced have no report.
+///
+/// # Returns
+///
+/// * `Vec<MigrationReport>` - The reports in ascending order of schema version, with the keys every migration could not migrate.
+#[query(name = "getMigrationReports")]
+pub fn get_migration_reports() -> Vec<MigrationReport> {
+    SCHEMA_VERSION_TO_MIGRATION_REPORT.with(|r| r.borrow().iter().map(|(_, report)| report).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+    use icrc_ledger_types::icrc1::account::Account;
+
+    use crate::ext::tokens_ext;
+    use crate::icrc_7::ext_types::{AccountIdentifier, ExtTokensResult};
+    use crate::state::{
+        LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED, LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED, ACHIEVEMENT_CANISTER_TO_BOOL,
+        TOKEN_ID_TO_ISSUANCE, PRINCIPAL_TO_REPUTATION, get_account_achievement_status, get_held_achievements_of_account,
+        get_holders_of, get_holder_count, get_issuance, get_rank, get_leaderboard
+    };
+    use crate::storable::{CanisterPermission, Issuance, PrincipalPair, PrincipalSum, Reputation, StorablePrincipal};
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n])
+    }
+
+    /// Applies the migrations newer than `version`, as `run_migrations` does after an upgrade.
+    fn migrate_from(version: u32) {
+        _set_schema_version(version);
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+            apply_migration(migration, 42);
+        }
+    }
+
+    /// Stores an issued token the way it was written before tokens were numbered.
+    fn seed_issuance(token_id: u128, owner: Principal, achievement: Principal) {
+        TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().insert(token_id, Issuance {
+            owner,
+            subaccount: None,
+            achievement,
+            issued_at: token_id as u64,
+            ordinal: None
+        }));
+    }
+
+    fn report(version: u32) -> MigrationReport {
+        get_migration_reports().into_iter().find(|report| report.version == version).unwrap()
+    }
+
+    #[test]
+    fn migrations_are_listed_in_ascending_order_up_to_the_schema_version() {
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[1].version == pair[0].version + 1));
+        assert_eq!(MIGRATIONS.first().unwrap().version, 1);
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn principal_sums_are_keyed_by_account() {
+        let (holder, other, achievement) = (principal(1), principal(2), principal(9));
+
+        LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|l| {
+            let mut legacy = l.borrow_mut();
+            legacy.insert(PrincipalSum(format!("{}{}", holder, achievement)), true);
+            legacy.insert(PrincipalSum(format!("{}{}", other, achievement)), false);
+            legacy.insert(PrincipalSum(String::from("not-a-principal-sum")), true);
+        });
+
+        migrate_from(0);
+
+        assert!(get_account_achievement_status(Account::from(holder), achievement));
+        assert!(!get_account_achievement_status(Account::from(other), achievement));
+        assert_eq!(get_holders_of(achievement), vec![Account::from(holder)]);
+        assert_eq!(get_holder_count(achievement), 1);
+        assert!(LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|l| l.borrow().is_empty()));
+        assert!(LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| p.borrow().is_empty()));
+
+        let report = report(1);
+        assert_eq!(report.migrated, 2);
+        assert_eq!(report.unmigrated_keys, vec![String::from("not-a-principal-sum")]);
+        assert_eq!(get_migration_reports().len(), SCHEMA_VERSION as usize);
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn rebuilt_indexes_cover_the_legacy_state() {
+        let (holder, achievement) = (principal(1), principal(9));
+
+        ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| {
+            let mut permissions = p.borrow_mut();
+            permissions.insert(StorablePrincipal(achievement), CanisterPermission(true));
+            permissions.insert(StorablePrincipal(principal(8)), CanisterPermission(false));
+        });
+        PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(holder), Reputation {
+            total: 1,
+            breakdown: vec![]
+        }));
+        LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| p.borrow_mut().insert(PrincipalPair(holder, achievement), true));
+
+        migrate_from(1);
+
+        assert_eq!(report(2).migrated, 1 + 2);
+        assert_eq!(get_rank(holder), Some(1));
+        assert!(get_account_achievement_status(Account::from(holder), achievement));
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn issuances_without_ordinals_are_numbered_in_token_order() {
+        let (first, second) = (principal(9), principal(8));
+
+        seed_issuance(3, principal(1), first);
+        seed_issuance(1, principal(2), first);
+        seed_issuance(2, principal(3), second);
+        seed_issuance(4, principal(4), first);
+
+        migrate_from(2);
+
+        let ordinals: Vec<Option<u64>> = (1..=4).map(|token_id| get_issuance(token_id).unwrap().ordinal).collect();
+        assert_eq!(ordinals, vec![Some(1), Some(1), Some(2), Some(3)]);
+        assert_eq!(report(3).migrated, 4);
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn issued_tokens_are_indexed_by_account() {
+        let (holder, achievement) = (principal(1), principal(9));
+
+        seed_issuance(7, holder, achievement);
+
+        migrate_from(3);
+
+        let held = get_held_achievements_of_account(&Account::from(holder));
+        assert_eq!(held.iter().map(|held| (held.achievement, held.token_id)).collect::<Vec<_>>(), vec![(achievement, 7)]);
+        assert!(get_account_achievement_status(Account::from(holder), achievement));
+        assert_eq!(get_holder_count(achievement), 1);
+
+        let account_identifier = AccountIdentifier::from_principal(&holder, &None).to_hex();
+        match tokens_ext(account_identifier) {
+            ExtTokensResult::Ok(tokens) => assert_eq!(tokens.into_iter().map(|token| token.0).collect::<Vec<_>>(), vec![7]),
+            ExtTokensResult::Err(err) => panic!("{:?}", err)
+        }
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn scores_are_counted_for_the_leaderboard() {
+        for (n, total) in [(1, 10), (2, 30), (3, 10)] {
+            PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(principal(n)), Reputation {
+                total,
+                breakdown: vec![]
+            }));
+        }
+
+        migrate_from(4);
+
+        assert_eq!(report(5).migrated, 3);
+        assert_eq!(get_rank(principal(2)), Some(1));
+        assert_eq!(get_rank(principal(1)), Some(2));
+        assert_eq!(get_rank(principal(3)), Some(2));
+        assert_eq!(get_leaderboard(None, 10).entries.len(), 3);
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn legacy_statuses_without_a_token_go_to_the_default_account() {
+        let (with_token, without_token, achievement) = (principal(1), principal(2), principal(9));
+
+        seed_issuance(1, with_token, achievement);
+        LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| {
+            let mut legacy = p.borrow_mut();
+            legacy.insert(PrincipalPair(with_token, achievement), true);
+            legacy.insert(PrincipalPair(without_token, achievement), true);
+        });
+
+        migrate_from(5);
+
+        let mut holders = get_holders_of(achievement);
+        holders.sort();
+        assert_eq!(holders, vec![Account::from(with_token), Account::from(without_token)]);
+        assert_eq!(get_holder_count(achievement), 2);
+        assert_eq!(report(6).migrated, 2);
+        assert!(LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| p.borrow().is_empty()));
+        assert_eq!(get_schema_version(), SCHEMA_VERSION);
+    }
+}
@@ -0,0 +1,180 @@
+//! This module provides the emergency pause switch of the reputation module, globally and per achievement.
+
+use candid::Principal;
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{StableBTreeMap, StableCell, StableVec};
+use std::cell::RefCell;
+
+use crate::access::is_admin;
+use crate::errors::{AccessError, IssuanceError, PauseError, ReputationError};
+use crate::state::MEMORY_MANAGER;
+use crate::storable::{Memory, PauseEvent, StorablePrincipal};
+use crate::types::Paging;
+
+thread_local! {
+    pub(crate) static PAUSED: RefCell<StableCell<bool, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))), false,
+        ).unwrap()
+    );
+
+    pub(crate) static PAUSED_ACHIEVEMENTS: RefCell<StableBTreeMap<StorablePrincipal, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+        )
+    );
+
+    pub(crate) static PAUSE_LOG: RefCell<StableVec<PauseEvent, Memory>> = RefCell::new(
+        StableVec::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))),
+        ).unwrap()
+    );
+}
+
+/// Checks that issuance of an achievement is not paused.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - `Ok` if neither the reputation module nor the achievement is paused.
+pub fn ensure_not_paused(achievement: Principal) -> Result<(), ReputationError> {
+    if is_paused() {
+        return Err(PauseError::ModulePaused.into());
+    }
+
+    if is_achievement_paused(achievement) {
+        return Err(PauseError::AchievementPaused(achievement).into());
+    }
+
+    Ok(())
+}
+
+/// Appends a change of the pause switch to the pause log.
+///
+/// # Arguments
+///
+/// * `achievement` - The paused achievement canister, or `None` for the whole reputation module.
+/// * `paused` - The new state of the switch.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the operation.
+fn _log_pause_event(achievement: Option<Principal>, paused: bool) -> Result<(), ReputationError> {
+    let event = PauseEvent {
+        achievement,
+        paused,
+        changed_by: ic_cdk::api::caller(),
+        changed_at: ic_cdk::api::time()
+    };
+
+    ic_cdk::println!(
+        "Pause switch of {} set to {} by {}",
+        achievement.map_or(String::from("reputation module"), |a| a.to_text()),
+        paused,
+        event.changed_by
+    );
+
+    PAUSE_LOG.with(|l| l.borrow_mut().push(&event))
+        .map_err(|err| IssuanceError::StorageError(format!("{:?}", err)).into())
+}
+
+/// Pauses or resumes every claim and mint of the reputation module.
+///
+/// Queries keep working while the reputation module is paused.
+///
+/// # Arguments
+///
+/// * `paused` - `true` to pause, `false` to resume.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setPaused")]
+pub fn set_paused(paused: bool) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    PAUSED.with(|p| p.borrow_mut().set(paused))
+        .map_err(|err| IssuanceError::StorageError(format!("{:?}", err)))?;
+
+    _log_pause_event(None, paused)
+}
+
+/// Pauses or resumes the claims and mints of a single achievement.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+/// * `paused` - `true` to pause, `false` to resume.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setAchievementPaused")]
+pub fn set_achievement_paused(achievement: Principal, paused: bool) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    PAUSED_ACHIEVEMENTS.with(|p| {
+        let mut paused_achievements = p.borrow_mut();
+        if paused {
+            paused_achievements.insert(StorablePrincipal(achievement), ());
+        } else {
+            paused_achievements.remove(&StorablePrincipal(achievement));
+        }
+    });
+
+    _log_pause_event(Some(achievement), paused)
+}
+
+/// Checks whether the reputation module is paused.
+///
+/// # Returns
+///
+/// * `bool` - `true` if every claim and mint is paused.
+#[query(name = "isPaused")]
+pub fn is_paused() -> bool {
+    PAUSED.with(|p| *p.borrow().get())
+}
+
+/// Checks whether an achievement is paused.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the claims and mints of the achievement are paused.
+#[query(name = "isAchievementPaused")]
+pub fn is_achievement_paused(achievement: Principal) -> bool {
+    PAUSED_ACHIEVEMENTS.with(|p| p.borrow().contains_key(&StorablePrincipal(achievement)))
+}
+
+/// Retrieves the changes of the pause switch, oldest first.
+///
+/// # Arguments
+///
+/// * `paging` - The offset and the maximum number of events to return, capped at 1000.
+///
+/// # Returns
+///
+/// * `Vec<PauseEvent>` - The pause events of the page.
+#[query(name = "getPauseLog")]
+pub fn get_pause_log(paging: Paging) -> Vec<PauseEvent> {
+    let (offset, limit) = paging.bounds();
+
+    PAUSE_LOG.with(|l| {
+        l.borrow()
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .collect()
+    })
+}
@@ -4,29 +4,63 @@ use ic_stable_structures::{
     DefaultMemoryImpl, StableBTreeMap, StableVec, StableCell
 };
 use candid::Principal;
-use ic_cdk::{query, update};
+use ic_cdk::{query, update, init, post_upgrade};
+use ic_cdk::api::management_canister::main::{canister_info, CanisterInfoRequest};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::time::Duration;
 
-use crate::utils::build_principal_sum;
-use crate::types::AchievementMetadata;
-use crate::access::is_controller;
+use icrc_ledger_types::icrc1::account::Account;
+
+use crate::utils::{parse_principal_sum, normalize_subaccount};
+use crate::types::{
+    AchievementMetadata, LeaderboardEntry, LeaderboardPage, AchievementOf, Paging, Holder, HoldersPage, AchievementFilters,
+    AchievementListing, ReputationModuleInitArgs, MAX_PAGE
+};
+use crate::access::is_admin;
+use crate::migrations::{run_migrations, SCHEMA_VERSION};
+use crate::ecdsa::_set_ecdsa_key;
+use crate::credential::_start_public_key_fetch;
+use crate::ext::{_index_account, _unindex_account, _clear_account_index};
+use crate::http::{certify_all, certify_metadata, certify_badge, certify_badges_of, certify_all_badges};
+use crate::errors::{AccessError, CallError, ConfigError, EligibilityError, IssuanceError, ReputationError};
 use crate::storable::{
-    Memory, CanisterPermission, StorablePrincipal, ReputationModuleMetadata, PrincipalSum
+    Memory, CanisterPermission, StorablePrincipal, ReputationModuleMetadata, PrincipalSum, Issuance,
+    AchievementWeight, Reputation, ReputationEntry, DecayModel,
+    LeaderboardKey, HeldAchievement, HeldAchievements, PrincipalPair, AccountPair, StorableAccount,
+    ModuleHash, Revocation, Holding
 };
 use crate::Standard;
 
+/// The interval at which the cached achievement metadata is refreshed.
+const METADATA_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The delay before the first retry of an approval that failed after install. It doubles on every retry.
+const APPROVAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The number of attempts made to approve the allowed achievement canisters after install.
+const MAX_APPROVAL_ATTEMPTS: u32 = 6;
+
+/// How long the installed module hash of an achievement canister is trusted before it is fetched again.
+const MODULE_HASH_TTL: Duration = Duration::from_secs(10 * 60);
+
 thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+    /// The installed module hash of every achievement canister fetched recently, with the time it expires at.
+    static INSTALLED_MODULE_HASHES: RefCell<BTreeMap<Principal, (ModuleHash, u64)>> = const { RefCell::new(BTreeMap::new()) };
+
+    pub(crate) static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 
-    static ACHIEVEMENT_CANISTER_TO_BOOL: RefCell<StableBTreeMap<StorablePrincipal, CanisterPermission, Memory>> = RefCell::new(
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_BOOL: RefCell<StableBTreeMap<StorablePrincipal, CanisterPermission, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
         )
     );
 
-    static METADATA: RefCell<StableCell<ReputationModuleMetadata, Memory>> = RefCell::new(
+    pub(crate) static METADATA: RefCell<StableCell<ReputationModuleMetadata, Memory>> = RefCell::new(
         StableCell::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))), ReputationModuleMetadata::default(),
         ).unwrap()
@@ -38,243 +72,2120 @@ thread_local! {
         ).unwrap()
     );
 
-    static SUPPORTED_STANDARDS: RefCell<StableVec<Standard, Memory>> = RefCell::new(
+    pub(crate) static SUPPORTED_STANDARDS: RefCell<StableVec<Standard, Memory>> = RefCell::new(
         StableVec::init(
             MEMORY_MANAGER.with(|a| a.borrow().get(MemoryId::new(4))),
         ).unwrap()
     );
 
-    static PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED: RefCell<StableBTreeMap<PrincipalSum, bool, Memory>> = RefCell::new(
+    pub(crate) static LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED: RefCell<StableBTreeMap<PrincipalSum, bool, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
         )
     );
 
+    pub(crate) static TOKEN_ID_TO_ISSUANCE: RefCell<StableBTreeMap<u128, Issuance, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_WEIGHT: RefCell<StableBTreeMap<StorablePrincipal, AchievementWeight, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
+    pub(crate) static PRINCIPAL_TO_REPUTATION: RefCell<StableBTreeMap<StorablePrincipal, Reputation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_DECAY: RefCell<StableBTreeMap<StorablePrincipal, DecayModel, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    pub(crate) static LEADERBOARD: RefCell<StableBTreeMap<LeaderboardKey, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    pub(crate) static LEGACY_PRINCIPAL_TO_ACHIEVEMENTS: RefCell<StableBTreeMap<StorablePrincipal, HeldAchievements, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_METADATA: RefCell<StableBTreeMap<StorablePrincipal, AchievementMetadata, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    pub(crate) static LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED: RefCell<StableBTreeMap<PrincipalPair, bool, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+
+    pub(crate) static LEGACY_ACHIEVEMENT_PAIR_TO_IS_ISSUED: RefCell<StableBTreeMap<PrincipalPair, bool, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))),
+        )
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_MODULE_HASH: RefCell<StableBTreeMap<StorablePrincipal, ModuleHash, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))),
+        )
+    );
+
+    pub(crate) static ADMINS: RefCell<StableBTreeMap<StorablePrincipal, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))),
+        )
+    );
+
+    static STABLE_SCHEMA_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))), 0,
+        ).unwrap()
+    );
+
+    pub(crate) static ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))),
+        )
+    );
+
+    pub(crate) static RELEASED_TOKEN_IDS: RefCell<StableBTreeMap<u128, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28))),
+        )
+    );
+
+    pub(crate) static TOKEN_ID_TO_REVOCATION: RefCell<StableBTreeMap<u128, Revocation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29))),
+        )
+    );
+
+    pub(crate) static SCORE_TO_PRINCIPAL_COUNT: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30))),
+        )
+    );
+
+    pub(crate) static ACCOUNT_TO_ACHIEVEMENTS: RefCell<StableBTreeMap<StorableAccount, HeldAchievements, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(32))),
+        )
+    );
+
+    pub(crate) static ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED: RefCell<StableBTreeMap<AccountPair, bool, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(33))),
+        )
+    );
+
+    static ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING: RefCell<StableBTreeMap<AccountPair, Holding, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(34))),
+        )
+    );
+
 }
 
-/// Changes the status of a principal's achievement to issued.
+/// Changes the status of an account's achievement to issued.
 ///
-/// This function updates the status of a principal's achievement to indicate that it has been issued.
+/// This function updates the status of an account's achievement to indicate that it has been issued,
+/// and adds the account to the holders of the achievement together with its token, if recorded.
 ///
 /// # Arguments
 ///
-/// * `identity_wallet` - The principal of the identity wallet.
+/// * `account` - The account of the identity wallet.
 /// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<(), String>` - The result of the update operation.
-pub fn _change_principal_achievement_sum_status_to_issued(identity_wallet: Principal, achievement: Principal) -> Result<(), String> {
-    let principal_sum = build_principal_sum(identity_wallet, achievement);
+/// * `Result<(), ReputationError>` - The result of the update operation.
+pub fn _change_account_achievement_status_to_issued(account: &Account, achievement: Principal) -> Result<(), ReputationError> {
+    let subaccount = *account.effective_subaccount();
 
-    PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow_mut().insert(PrincipalSum(principal_sum), true));
+    let issuance = get_issuance_of_account(account, achievement);
+    let holding = Holding {
+        token_id: issuance.as_ref().map(|(token_id, _)| *token_id),
+        issued_at: issuance.map(|(_, issuance)| issuance.issued_at)
+    };
+
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow_mut().insert(AccountPair(account.owner, achievement, subaccount), true));
+    let previous = ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| a.borrow_mut().insert(AccountPair(achievement, account.owner, subaccount), holding));
+
+    if previous.is_none() {
+        _change_holder_count(achievement, 1);
+    }
 
     Ok(())
 }
 
-/// Retrieves the status of a principal's achievement.
+/// Changes the status of an account's achievement to revoked.
 ///
-/// This function checks if a principal's achievement has been issued.
+/// This function removes the issued status of an account's achievement.
 ///
 /// # Arguments
 ///
-/// * `identity_wallet` - The principal of the identity wallet.
+/// * `account` - The account of the identity wallet.
 /// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `bool` - `true` if the achievement has been issued, `false` otherwise.
-#[query(name = "getPrincipalAchievementSumStatus")]
-pub fn get_principal_achievement_sum_status(identity_wallet: Principal, achievement: Principal) -> bool {
-    let principal_sum = build_principal_sum(identity_wallet, achievement);
+/// * `Result<(), ReputationError>` - The result of the update operation.
+pub fn _change_account_achievement_status_to_revoked(account: &Account, achievement: Principal) -> Result<(), ReputationError> {
+    let subaccount = *account.effective_subaccount();
 
-    if let Some(issued_status) = PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow().get(&PrincipalSum(principal_sum))) {
-        issued_status
-    } else {
-        false
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow_mut().remove(&AccountPair(account.owner, achievement, subaccount)));
+    let previous = ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| a.borrow_mut().remove(&AccountPair(achievement, account.owner, subaccount)));
+
+    if previous.is_some() {
+        _change_holder_count(achievement, -1);
     }
+
+    Ok(())
 }
 
-/// Sets the supported standards for the reputation module.
-///
-/// This function updates the list of supported standards for the reputation module.
+/// Builds the account of a principal and a stored subaccount.
+pub fn account_of(owner: Principal, subaccount: [u8; 32]) -> Account {
+    Account { owner, subaccount: normalize_subaccount(Some(subaccount)) }
+}
+
+/// Retrieves the accounts of a principal an achievement was issued to.
 ///
 /// # Arguments
 ///
-/// * `standards` - A vector of supported standards.
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<(), String>` - The result of the update operation.
-#[update(name = "setSupportedStandards")]
-pub fn set_supported_standards(standards: Vec<Standard>) -> Result<(), String> {
-    let id = ic_cdk::api::caller();
-    let is_controller = ic_cdk::api::is_controller(&id);
-
-    if !is_controller {
-        return Err(String::from("Access denied"))
-    }
-
-    SUPPORTED_STANDARDS.with(|p| {
-        let b_p = p.borrow_mut();
+/// * `Vec<Account>` - The accounts holding the achievement, ordered by subaccount.
+pub fn get_accounts_issued(identity_wallet: Principal, achievement: Principal) -> Vec<Account> {
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| {
+        p.borrow()
+            .range(AccountPair::pair_lower_bound(identity_wallet, achievement)..)
+            .take_while(|(key, _)| key.0 == identity_wallet && key.1 == achievement)
+            .filter(|(_, issued)| *issued)
+            .map(|(key, _)| account_of(key.0, key.2))
+            .collect()
+    })
+}
 
-        for (_, _) in b_p.iter().enumerate() {
-            b_p.pop();
-        }
-        for (_, e) in standards.iter().enumerate() {
-            b_p.push(e);
-        }
+/// Retrieves the achievement canisters issued to any account of a principal.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Vec<Principal>` - The achievement canisters, ordered by principal.
+pub fn get_issued_achievements_of(identity_wallet: Principal) -> Vec<Principal> {
+    let mut achievements: Vec<Principal> = ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| {
+        p.borrow()
+            .range(AccountPair::lower_bound(identity_wallet)..)
+            .take_while(|(key, _)| key.0 == identity_wallet)
+            .filter(|(_, issued)| *issued)
+            .map(|(key, _)| key.1)
+            .collect()
     });
-    Ok(())
+    achievements.dedup();
+
+    achievements
 }
 
-/// Retrieves the supported standards for the reputation module.
+/// Retrieves the accounts an achievement was issued to.
 ///
-/// This function returns the list of supported standards for the reputation module.
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Vec<Standard>` - A vector of supported standards.
-#[query(name = "getSupportedStandards")]
-pub fn get_supported_standards() -> Vec<Standard> {
-    SUPPORTED_STANDARDS.with(|p| {
-        let mut standards: Vec<Standard> = vec![];
-        for (_, e) in p.borrow().iter().enumerate() {
-            standards.push(e)
-        }
-        standards
+/// * `Vec<Account>` - The accounts holding the achievement, ordered by owner and subaccount.
+pub fn get_holders_of(achievement: Principal) -> Vec<Account> {
+    ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| {
+        a.borrow()
+            .range(AccountPair::lower_bound(achievement)..)
+            .take_while(|(key, _)| key.0 == achievement)
+            .map(|(key, _)| account_of(key.1, key.2))
+            .collect()
     })
 }
 
-/// Changes the permission of a canister.
+/// Retrieves a page of the holders of an achievement.
 ///
-/// This function updates the permission of a specified canister.
+/// Holders are accounts, ordered by owner and subaccount. Pass the `next_cursor` of a page to retrieve the following one.
 ///
 /// # Arguments
 ///
-/// * `canister` - The principal of the canister.
-/// * `permission` - The new permission status.
+/// * `achievement` - The principal of the achievement canister.
+/// * `cursor` - The last holder of the previous page, or `None` to start from the beginning.
+/// * `limit` - The maximum number of holders to return, capped at 1000.
 ///
 /// # Returns
 ///
-/// * `Result<String, String>` - The result of the update operation.
-#[update(name = "changePermissionCanister")]
-pub fn change_permission_canister(canister: Principal, permission: bool) -> Result<String, String> {
-    let id = ic_cdk::api::caller();
-    let is_controller = ic_cdk::api::is_controller(&id);
+/// * `HoldersPage` - The holders together with their token ids and issuance times.
+#[query(name = "getHolders")]
+pub fn get_holders(achievement: Principal, cursor: Option<Account>, limit: u64) -> HoldersPage {
+    let limit = limit.min(MAX_PAGE) as usize;
+    let start = match cursor {
+        Some(cursor) => Bound::Excluded(AccountPair(achievement, cursor.owner, *cursor.effective_subaccount())),
+        None => Bound::Included(AccountPair::lower_bound(achievement))
+    };
+
+    let mut holders: Vec<Holder> = ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| {
+        a.borrow()
+            .range((start, Bound::Unbounded))
+            .take_while(|(key, _)| key.0 == achievement)
+            .take(limit + 1)
+            .map(|(key, holding)| Holder {
+                principal: key.1,
+                subaccount: normalize_subaccount(Some(key.2)),
+                token_id: holding.token_id,
+                issued_at: holding.issued_at
+            })
+            .collect()
+    });
 
-    if is_controller {
-        ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| p.borrow_mut().insert(StorablePrincipal(canister), CanisterPermission(permission)));
-        Ok(String::from("Granted permissions to canister"))
+    let has_more = holders.len() > limit;
+    holders.truncate(limit);
+    let next_cursor = if has_more {
+        holders.last().map(|holder| Account { owner: holder.principal, subaccount: holder.subaccount })
     } else {
-        Err(String::from("Access denied"))
-    }
+        None
+    };
+
+    HoldersPage { holders, next_cursor }
 }
 
-/// Checks if a canister is allowed.
+/// Retrieves the number of holders of an achievement.
 ///
-/// This function checks if a specified canister has the required permissions.
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `u64` - The number of accounts holding the achievement.
+#[query(name = "getHolderCount")]
+pub fn get_holder_count(achievement: Principal) -> u64 {
+    ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| h.borrow().get(&StorablePrincipal(achievement)))
+        .unwrap_or(0)
+}
+
+/// Adjusts the number of holders of an achievement.
 ///
 /// # Arguments
 ///
-/// * `canister` - The principal of the canister.
+/// * `achievement` - The principal of the achievement canister.
+/// * `delta` - The change in the number of holders.
+fn _change_holder_count(achievement: Principal, delta: i64) {
+    let count = get_holder_count(achievement).saturating_add_signed(delta);
+
+    ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| {
+        let mut holder_counts = h.borrow_mut();
+        if count == 0 {
+            holder_counts.remove(&StorablePrincipal(achievement));
+        } else {
+            holder_counts.insert(StorablePrincipal(achievement), count);
+        }
+    });
+}
+
+/// Rebuilds the holder count of every achievement from the issued statuses.
 ///
 /// # Returns
 ///
-/// * `Result<CanisterPermission, String>` - The permission status of the canister.
-#[query(name = "isCanisterAllowed")]
-pub fn is_canister_allowed(canister: Principal) -> Result<CanisterPermission, String> {
-    if let Some(permission) = ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| p.borrow().get(&StorablePrincipal(canister))) {
-        Ok(permission)
-    } else {
-        Err(String::from("Canister not found"))
+/// * `u64` - The number of achievements with at least one holder.
+pub fn _rebuild_holder_counts() -> u64 {
+    ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| h.borrow_mut().clear_new());
+
+    let achievements: Vec<Principal> = ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| {
+        a.borrow()
+            .iter()
+            .map(|(key, _)| key.0)
+            .collect()
+    });
+
+    for achievement in achievements {
+        _change_holder_count(achievement, 1);
     }
+
+    ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|h| h.borrow().len())
 }
 
-/// Increments the total number of issued achievements.
+/// Migrates the issued statuses keyed by legacy principal sums to principal pairs.
 ///
-/// This function increments the total count of issued achievements in the reputation module.
+/// The principal pairs are keyed by account by a later migration. The legacy map is cleared once its
+/// entries are migrated. Entries whose key cannot be parsed are dropped with it and returned, so that
+/// they are kept in the migration report.
 ///
 /// # Returns
 ///
-/// * `Result<(), String>` - The result of the increment operation.
-pub fn increment_total_issued() -> Result<(), String> {
-    let mut reputation_module_metadata = get_reputation_module_metadata();
-    reputation_module_metadata.total_issued += 1;
+/// * `(u64, Vec<String>)` - The number of migrated entries and the keys that could not be parsed.
+pub fn _migrate_principal_sums() -> (u64, Vec<String>) {
+    let legacy_entries: Vec<(PrincipalSum, bool)> = LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|l| l.borrow().iter().collect());
+    let mut migrated = 0;
+    let mut unparsed_keys = vec![];
 
-    _update_canister_metadata(reputation_module_metadata)?;
+    for (principal_sum, issued) in legacy_entries {
+        if let Some((identity_wallet, achievement)) = parse_principal_sum(&principal_sum.0) {
+            if issued {
+                LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| p.borrow_mut().insert(PrincipalPair(identity_wallet, achievement), true));
+            }
+            migrated += 1;
+        } else {
+            unparsed_keys.push(principal_sum.0);
+        }
+    }
+    LEGACY_PRINCIPAL_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|l| l.borrow_mut().clear_new());
 
-    Ok(())
+    (migrated, unparsed_keys)
 }
 
-/// Updates the metadata of the reputation module.
+/// Records an issued achievement token.
 ///
-/// This function updates the metadata of the reputation module with the provided metadata.
+/// This function stores the holder and the achievement canister of a freshly minted token.
 ///
 /// # Arguments
 ///
-/// * `metadata` - The new metadata for the reputation module.
+/// * `token_id` - The identifier of the minted token.
+/// * `to` - The account of the identity wallet holding the token.
+/// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<ReputationModuleMetadata, String>` - The result of the update operation.
-pub fn _update_canister_metadata(metadata: ReputationModuleMetadata) -> Result<ReputationModuleMetadata, String> {
-    Ok(METADATA.with(|m| {
-        let mut metadata_module = m.borrow_mut();
-        metadata_module.set(metadata)
-    }).unwrap_or_else(|err| {
-        ic_cdk::trap(&format!("{:?}", err))
-    }))
+/// * `Result<(), ReputationError>` - The result of the update operation.
+pub fn _record_issuance(token_id: u128, to: Account, achievement: Principal) -> Result<(), ReputationError> {
+    let issuance = Issuance {
+        owner: to.owner,
+        subaccount: normalize_subaccount(to.subaccount),
+        achievement,
+        issued_at: ic_cdk::api::time(),
+        ordinal: Some(_next_ordinal(achievement))
+    };
+
+    _index_account_achievement(token_id, &issuance);
+    TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().insert(token_id, issuance));
+    certify_badge(token_id);
+
+    Ok(())
 }
 
-/// Updates the metadata of the reputation module.
+/// Stores an issued achievement token restored from a backup.
 ///
-/// This function updates the metadata of the reputation module with the provided metadata.
+/// The token is added to the index of its account, and the issuance count of its achievement is
+/// raised to its ordinal. Tokens without an ordinal are numbered after the highest ordinal of their
+/// achievement, as done by `_rebuild_issuance_counts`.
 ///
 /// # Arguments
 ///
-/// * `metadata` - The new metadata for the reputation module.
+/// * `token_id` - The identifier of the token.
+/// * `issuance` - The issuance record of the token.
+pub fn _import_issuance(token_id: u128, mut issuance: Issuance) {
+    let achievement = StorablePrincipal(issuance.achievement);
+    let count = ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| i.borrow().get(&achievement)).unwrap_or(0);
+    let ordinal = *issuance.ordinal.get_or_insert(count + 1);
+
+    ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| i.borrow_mut().insert(achievement, ordinal.max(count)));
+    _index_account_achievement(token_id, &issuance);
+    TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().insert(token_id, issuance));
+}
+
+/// Assigns the next ordinal of an achievement.
+///
+/// Ordinals start at 1 and are never reused, even when an achievement is revoked.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<ReputationModuleMetadata, String>` - The result of the update operation.
-#[update(name = "updateReputationModuleMetadata")]
-pub fn update_reputation_canister_metadata(metadata: ReputationModuleMetadata) -> Result<ReputationModuleMetadata, String> {
-    if(!is_controller()) {
-        return Err(String::from("Access denied"));
-    }
-    _update_canister_metadata(metadata)
+/// * `u64` - The ordinal of the next token of the achievement.
+fn _next_ordinal(achievement: Principal) -> u64 {
+    ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| {
+        let mut issuance_counts = i.borrow_mut();
+        let ordinal = issuance_counts.get(&StorablePrincipal(achievement)).unwrap_or(0) + 1;
+        issuance_counts.insert(StorablePrincipal(achievement), ordinal);
+
+        ordinal
+    })
 }
 
-/// Retrieves the metadata of the reputation module.
+/// Numbers the issued tokens without an ordinal and rebuilds the issuance count of every achievement.
 ///
-/// This function returns the current metadata of the reputation module.
+/// Tokens are numbered in token id order, after the highest ordinal already assigned to their achievement.
 ///
 /// # Returns
 ///
-/// * `ReputationModuleMetadata` - The current metadata of the reputation module.
-#[query(name = "getReputationModuleMetadata")]
-pub fn get_reputation_module_metadata() -> ReputationModuleMetadata {
-    METADATA.with(|m| {
-        let metadata = m.borrow();
-        metadata.get().clone()
-    })
+/// * `u64` - The number of tokens numbered.
+pub fn _rebuild_issuance_counts() -> u64 {
+    ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| i.borrow_mut().clear_new());
+
+    let mut numbered = 0;
+
+    for (token_id, mut issuance) in get_issuances() {
+        let achievement = issuance.achievement;
+        let count = ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| i.borrow().get(&StorablePrincipal(achievement)))
+            .unwrap_or(0);
+        let ordinal = match issuance.ordinal {
+            Some(ordinal) => ordinal.max(count),
+            None => {
+                issuance.ordinal = Some(count + 1);
+                TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().insert(token_id, issuance));
+                numbered += 1;
+                count + 1
+            }
+        };
+
+        ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|i| i.borrow_mut().insert(StorablePrincipal(achievement), ordinal));
+    }
+
+    numbered
 }
 
-/// Retrieves the metadata of an achievement.
+/// Adds an issued achievement token to the index of the account holding it.
+///
+/// If the account is already a holder of the achievement, its holder entry is given the token.
+///
+/// # Arguments
 ///
-/// This function returns the metadata of a specified achievement.
+/// * `token_id` - The identifier of the token.
+/// * `issuance` - The issuance record of the token.
+fn _index_account_achievement(token_id: u128, issuance: &Issuance) {
+    ACCOUNT_TO_ACHIEVEMENTS.with(|a| {
+        let mut account_to_achievements = a.borrow_mut();
+        let key = StorableAccount(Account { owner: issuance.owner, subaccount: issuance.subaccount });
+        let mut held = account_to_achievements.get(&key).unwrap_or_default();
+
+        held.0.retain(|h| h.achievement != issuance.achievement);
+        held.0.push(HeldAchievement {
+            achievement: issuance.achievement,
+            subaccount: issuance.subaccount,
+            token_id,
+            issued_at: issuance.issued_at
+        });
+        account_to_achievements.insert(key, held);
+    });
+    ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| {
+        let mut holders = a.borrow_mut();
+        let key = AccountPair(issuance.achievement, issuance.owner, issuance.subaccount.unwrap_or([0u8; 32]));
+
+        if holders.contains_key(&key) {
+            holders.insert(key, Holding { token_id: Some(token_id), issued_at: Some(issuance.issued_at) });
+        }
+    });
+    _index_account(issuance.owner, issuance.subaccount);
+}
+
+/// Removes the issued achievement token held by an account.
 ///
 /// # Arguments
 ///
+/// * `account` - The account holding the token.
 /// * `achievement` - The principal of the achievement canister.
 ///
 /// # Returns
 ///
-/// * `Result<AchievementMetadata, String>` - The metadata of the achievement.
-#[query(name = "getAchievementMetadata")]
-pub async fn get_achievement_metadata(achievement: Principal) -> Result<AchievementMetadata, String> {
-    let achievement_metadata: (AchievementMetadata, ) = ic_cdk::call(achievement, "getAchievementMetadata", ()).await.unwrap();
+/// * `Option<(u128, Issuance)>` - The identifier and the issuance record of the removed token, if any.
+pub fn _remove_issuance(account: &Account, achievement: Principal) -> Option<(u128, Issuance)> {
+    let (token_id, issuance) = get_issuance_of_account(account, achievement)?;
 
-    Ok(achievement_metadata.0)
-}
\ No newline at end of file
+    TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow_mut().remove(&token_id));
+    certify_badge(token_id);
+    let account_still_holds = ACCOUNT_TO_ACHIEVEMENTS.with(|a| {
+        let mut account_to_achievements = a.borrow_mut();
+        let key = StorableAccount(*account);
+        let mut held = account_to_achievements.get(&key).unwrap_or_default();
+
+        held.0.retain(|h| h.achievement != achievement);
+        if held.0.is_empty() {
+            account_to_achievements.remove(&key);
+            false
+        } else {
+            account_to_achievements.insert(key, held);
+            true
+        }
+    });
+
+    if !account_still_holds {
+        _unindex_account(account.owner, issuance.subaccount);
+    }
+
+    Some((token_id, issuance))
+}
+
+/// Records that an issued achievement token was revoked.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the revoked token.
+/// * `issuance` - The issuance record of the revoked token.
+pub fn _record_revocation(token_id: u128, issuance: Issuance) {
+    TOKEN_ID_TO_REVOCATION.with(|r| r.borrow_mut().insert(token_id, Revocation {
+        owner: issuance.owner,
+        subaccount: issuance.subaccount,
+        achievement: issuance.achievement,
+        issued_at: issuance.issued_at,
+        revoked_at: ic_cdk::api::time()
+    }));
+}
+
+/// Retrieves a page of the revoked achievement tokens.
+///
+/// Revoked tokens no longer count towards any reputation, but they stay in the achievement
+/// collection, since only their holders may burn them. Tokens are ordered by token id.
+///
+/// # Arguments
+///
+/// * `after` - The last token id of the previous page, or `None` to start from the beginning.
+/// * `limit` - The maximum number of tokens to return, capped at 1000.
+///
+/// # Returns
+///
+/// * `Vec<(u128, Revocation)>` - The token ids together with their revocation records.
+#[query(name = "getRevokedTokens")]
+pub fn get_revoked_tokens(after: Option<u128>, limit: u64) -> Vec<(u128, Revocation)> {
+    let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+
+    TOKEN_ID_TO_REVOCATION.with(|r| {
+        r.borrow()
+            .range((start, Bound::Unbounded))
+            .take(limit.min(MAX_PAGE) as usize)
+            .collect()
+    })
+}
+
+/// Retrieves the achievement token held by any account of a principal.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet holding the token.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Option<(u128, Issuance)>` - The earliest issued token id and its issuance record, if the token exists.
+pub fn get_issuance_of(identity_wallet: Principal, achievement: Principal) -> Option<(u128, Issuance)> {
+    let held = get_held_achievements(identity_wallet)
+        .into_iter()
+        .find(|h| h.achievement == achievement)?;
+
+    get_issuance(held.token_id).map(|issuance| (held.token_id, issuance))
+}
+
+/// Retrieves the achievement token held by an account.
+///
+/// # Arguments
+///
+/// * `account` - The account holding the token.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Option<(u128, Issuance)>` - The token id and its issuance record, if the token exists.
+pub fn get_issuance_of_account(account: &Account, achievement: Principal) -> Option<(u128, Issuance)> {
+    let held = get_held_achievements_of_account(account)
+        .into_iter()
+        .find(|h| h.achievement == achievement)?;
+
+    get_issuance(held.token_id).map(|issuance| (held.token_id, issuance))
+}
+
+/// Retrieves the achievements held by every account of a principal, in issuance order.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Vec<HeldAchievement>` - The achievements held by the accounts of the principal.
+pub fn get_held_achievements(identity_wallet: Principal) -> Vec<HeldAchievement> {
+    let mut held: Vec<HeldAchievement> = ACCOUNT_TO_ACHIEVEMENTS.with(|a| {
+        a.borrow()
+            .range(StorableAccount::lower_bound(identity_wallet)..=StorableAccount::upper_bound(identity_wallet))
+            .flat_map(|(_, held)| held.0)
+            .collect()
+    });
+    held.sort_by_key(|h| (h.issued_at, h.token_id));
+
+    held
+}
+
+/// Retrieves the achievements held by an account, in issuance order.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+///
+/// # Returns
+///
+/// * `Vec<HeldAchievement>` - The achievements held by the account.
+pub fn get_held_achievements_of_account(account: &Account) -> Vec<HeldAchievement> {
+    ACCOUNT_TO_ACHIEVEMENTS.with(|a| a.borrow().get(&StorableAccount(*account)))
+        .unwrap_or_default()
+        .0
+}
+
+/// Retrieves a page of the achievements held by every account of a principal.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `paging` - The pagination parameters. The limit is capped at 1000.
+///
+/// # Returns
+///
+/// * `Vec<AchievementOf>` - The held achievements together with the cached metadata of their achievement canisters.
+#[query(name = "getAchievementsOf")]
+pub fn get_achievements_of(identity_wallet: Principal, paging: Paging) -> Vec<AchievementOf> {
+    let (offset, limit) = paging.bounds();
+
+    get_held_achievements(identity_wallet)
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|held| AchievementOf {
+            achievement: held.achievement,
+            subaccount: held.subaccount,
+            token_id: held.token_id,
+            issued_at: held.issued_at,
+            metadata: get_cached_achievement_metadata(held.achievement)
+        })
+        .collect()
+}
+
+/// Retrieves a page of the achievements held by an account.
+///
+/// Unlike `getAchievementsOf`, only the achievements issued to the given subaccount are returned.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+/// * `paging` - The pagination parameters. The limit is capped at 1000.
+///
+/// # Returns
+///
+/// * `Vec<AchievementOf>` - The held achievements together with the cached metadata of their achievement canisters.
+#[query(name = "getAchievementsOfAccount")]
+pub fn get_achievements_of_account(account: Account, paging: Paging) -> Vec<AchievementOf> {
+    let (offset, limit) = paging.bounds();
+
+    get_held_achievements_of_account(&account)
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|held| AchievementOf {
+            achievement: held.achievement,
+            subaccount: held.subaccount,
+            token_id: held.token_id,
+            issued_at: held.issued_at,
+            metadata: get_cached_achievement_metadata(held.achievement)
+        })
+        .collect()
+}
+
+/// Rebuilds the per-account achievement index and the EXT account identifier index from the issued achievement tokens.
+///
+/// # Returns
+///
+/// * `u64` - The number of indexed accounts.
+pub fn _rebuild_account_achievements() -> u64 {
+    ACCOUNT_TO_ACHIEVEMENTS.with(|a| a.borrow_mut().clear_new());
+    _clear_account_index();
+
+    for (token_id, issuance) in get_issuances() {
+        _index_account_achievement(token_id, &issuance);
+    }
+
+    ACCOUNT_TO_ACHIEVEMENTS.with(|a| a.borrow().len())
+}
+
+/// Re-keys the issued statuses, the holder index and the held achievements by account.
+///
+/// Every issued token gives the status of the account it was minted to. Legacy statuses without an
+/// issued token, written before tokens were recorded, are given to the default account of their
+/// principal. The legacy maps keyed by principal are cleared afterwards.
+///
+/// # Returns
+///
+/// * `u64` - The number of issued statuses keyed by account.
+pub fn _migrate_account_keys() -> u64 {
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow_mut().clear_new());
+    ACHIEVEMENT_PLUS_ACCOUNT_TO_HOLDING.with(|a| a.borrow_mut().clear_new());
+    _rebuild_account_achievements();
+
+    for (_, issuance) in get_issuances() {
+        let account = Account { owner: issuance.owner, subaccount: issuance.subaccount };
+        _change_account_achievement_status_to_issued(&account, issuance.achievement).ok();
+    }
+
+    let legacy_statuses: Vec<PrincipalPair> = LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| {
+        p.borrow().iter().filter(|(_, issued)| *issued).map(|(pair, _)| pair).collect()
+    });
+    for PrincipalPair(identity_wallet, achievement) in legacy_statuses {
+        if get_accounts_issued(identity_wallet, achievement).is_empty() {
+            _change_account_achievement_status_to_issued(&Account::from(identity_wallet), achievement).ok();
+        }
+    }
+
+    _rebuild_holder_counts();
+
+    LEGACY_PRINCIPAL_TO_ACHIEVEMENTS.with(|p| p.borrow_mut().clear_new());
+    LEGACY_PRINCIPAL_PAIR_TO_IS_ISSUED.with(|p| p.borrow_mut().clear_new());
+    LEGACY_ACHIEVEMENT_PAIR_TO_IS_ISSUED.with(|a| a.borrow_mut().clear_new());
+
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow().len())
+}
+
+/// Caches the metadata of an achievement canister.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+/// * `metadata` - The metadata returned by the achievement canister.
+pub fn _cache_achievement_metadata(achievement: Principal, metadata: AchievementMetadata) {
+    let renamed = get_cached_achievement_metadata(achievement)
+        .is_none_or(|cached| cached.achievement_name != metadata.achievement_name);
+
+    ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| m.borrow_mut().insert(StorablePrincipal(achievement), metadata));
+
+    if renamed {
+        certify_badges_of(achievement);
+    }
+}
+
+/// Retrieves the cached metadata of an achievement canister.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Option<AchievementMetadata>` - The cached metadata, if the achievement canister was seen before.
+pub fn get_cached_achievement_metadata(achievement: Principal) -> Option<AchievementMetadata> {
+    ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| m.borrow().get(&StorablePrincipal(achievement)))
+}
+
+/// Retrieves an issued achievement token.
+///
+/// # Arguments
+///
+/// * `token_id` - The identifier of the token.
+///
+/// # Returns
+///
+/// * `Option<Issuance>` - The issuance record, if the token exists.
+pub fn get_issuance(token_id: u128) -> Option<Issuance> {
+    TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow().get(&token_id))
+}
+
+/// Retrieves all issued achievement tokens ordered by token id.
+///
+/// # Returns
+///
+/// * `Vec<(u128, Issuance)>` - The token ids together with their issuance records.
+pub fn get_issuances() -> Vec<(u128, Issuance)> {
+    TOKEN_ID_TO_ISSUANCE.with(|t| t.borrow().iter().collect())
+}
+
+/// Retrieves the status of a principal's achievement.
+///
+/// This function checks if a principal's achievement has been issued to any of its accounts.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the achievement has been issued, `false` otherwise.
+#[query(name = "getPrincipalAchievementSumStatus")]
+pub fn get_principal_achievement_sum_status(identity_wallet: Principal, achievement: Principal) -> bool {
+    !get_accounts_issued(identity_wallet, achievement).is_empty()
+}
+
+/// Retrieves the status of an account's achievement.
+///
+/// Unlike `getPrincipalAchievementSumStatus`, only the given subaccount is checked.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the achievement has been issued to the account, `false` otherwise.
+#[query(name = "getAccountAchievementStatus")]
+pub fn get_account_achievement_status(account: Account, achievement: Principal) -> bool {
+    ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|p| p.borrow().get(&AccountPair(account.owner, achievement, *account.effective_subaccount())))
+        .unwrap_or(false)
+}
+
+/// Sets the supported standards for the reputation module.
+///
+/// This function updates the list of supported standards for the reputation module.
+///
+/// # Arguments
+///
+/// * `standards` - A vector of supported standards.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setSupportedStandards")]
+pub fn set_supported_standards(standards: Vec<Standard>) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into())
+    }
+
+    SUPPORTED_STANDARDS.with(|p| {
+        let b_p = p.borrow_mut();
+
+        while b_p.pop().is_some() {}
+        for standard in standards.iter() {
+            b_p.push(standard).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+        }
+    });
+    Ok(())
+}
+
+/// Retrieves the supported standards for the reputation module.
+///
+/// This function returns the list of supported standards for the reputation module.
+///
+/// # Returns
+///
+/// * `Vec<Standard>` - A vector of supported standards.
+#[query(name = "getSupportedStandards")]
+pub fn get_supported_standards() -> Vec<Standard> {
+    SUPPORTED_STANDARDS.with(|p| {
+        let mut standards: Vec<Standard> = vec![];
+        for e in p.borrow().iter() {
+            standards.push(e)
+        }
+        standards
+    })
+}
+
+/// Changes the permission of a canister.
+///
+/// This function updates the permission of a specified canister and registers it in the
+/// achievement catalog. When the canister is allowed, its metadata is cached and the hash of its
+/// currently installed module is recorded; achievements are only issued while the canister keeps
+/// running that module. Allowing the canister again re-approves its current module.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the canister.
+/// * `permission` - The new permission status.
+///
+/// # Returns
+///
+/// * `Result<String, ReputationError>` - The result of the update operation.
+#[update(name = "changePermissionCanister")]
+pub async fn change_permission_canister(canister: Principal, permission: bool) -> Result<String, ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    if permission {
+        _approve_achievement_canister(canister).await?;
+    }
+
+    _set_canister_permission(canister, permission)?;
+
+    Ok(String::from("Granted permissions to canister"))
+}
+
+/// Records the module hash and caches the metadata of an achievement canister being allowed.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the approval.
+async fn _approve_achievement_canister(canister: Principal) -> Result<(), ReputationError> {
+    let module_hash = fetch_module_hash(canister).await?;
+    fetch_achievement_metadata(canister).await?;
+    _cache_installed_module_hash(canister, module_hash.clone());
+
+    ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow_mut().insert(StorablePrincipal(canister), module_hash));
+
+    Ok(())
+}
+
+/// Sets the permission of a canister and registers it in the catalog the first time.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the canister.
+/// * `permission` - The new permission status.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+pub fn _set_canister_permission(canister: Principal, permission: bool) -> Result<(), ReputationError> {
+    let previous = ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| p.borrow_mut().insert(StorablePrincipal(canister), CanisterPermission(permission)));
+
+    if previous.is_none() {
+        _register_achievement(canister)?;
+    }
+
+    Ok(())
+}
+
+/// Adds an achievement canister to the catalog.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+fn _register_achievement(canister: Principal) -> Result<(), ReputationError> {
+    ACHIEVEMENTS.with(|a| a.borrow_mut().push(&StorablePrincipal(canister)))
+        .map_err(|err| IssuanceError::StorageError(format!("{:?}", err)).into())
+}
+
+/// Rebuilds the achievement catalog from the canister permissions.
+///
+/// # Returns
+///
+/// * `u64` - The number of registered achievement canisters.
+pub fn _rebuild_achievements() -> u64 {
+    ACHIEVEMENTS.with(|a| {
+        let achievements = a.borrow_mut();
+
+        while achievements.pop().is_some() {}
+
+        ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| {
+            for (canister, _) in p.borrow().iter() {
+                achievements.push(&canister).unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+            }
+        });
+
+        achievements.len()
+    })
+}
+
+/// Retrieves a page of the achievement catalog.
+///
+/// The catalog is served from cached state only, without calling the achievement canisters.
+/// Achievement canisters are listed in registration order.
+///
+/// # Arguments
+///
+/// * `paging` - The pagination parameters, applied after filtering. The limit is capped at 1000.
+/// * `filters` - The filters to apply.
+///
+/// # Returns
+///
+/// * `Vec<AchievementListing>` - The achievement canisters with their permission, metadata and holder count.
+#[query(name = "listAchievements")]
+pub fn list_achievements(paging: Paging, filters: AchievementFilters) -> Vec<AchievementListing> {
+    let (offset, limit) = paging.bounds();
+    let canisters: Vec<Principal> = ACHIEVEMENTS.with(|a| a.borrow().iter().map(|canister| canister.0).collect());
+
+    canisters
+        .into_iter()
+        .map(|canister| AchievementListing {
+            canister,
+            permission: is_canister_allowed(canister).map(|permission| permission.0).unwrap_or(false),
+            metadata: get_cached_achievement_metadata(canister),
+            holder_count: get_holder_count(canister)
+        })
+        .filter(|listing| filters.permission.is_none_or(|permission| listing.permission == permission))
+        .filter(|listing| filters.min_holders.is_none_or(|min_holders| listing.holder_count >= min_holders))
+        .filter(|listing| match (&filters.name_contains, &listing.metadata) {
+            (None, _) => true,
+            (Some(name), Some(metadata)) => metadata.achievement_name.to_lowercase().contains(&name.to_lowercase()),
+            (Some(_), None) => false
+        })
+        .skip(offset)
+        .take(limit)
+        .collect()
+}
+
+/// Fetches the hash of the module currently installed on a canister.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the canister.
+///
+/// # Returns
+///
+/// * `Result<ModuleHash, ReputationError>` - The module hash of the canister.
+async fn fetch_module_hash(canister: Principal) -> Result<ModuleHash, ReputationError> {
+    let (info, ) = canister_info(CanisterInfoRequest {
+        canister_id: canister,
+        num_requested_changes: None
+    }).await.map_err(|rejection| IssuanceError::CallFailed(
+        CallError::new(Principal::management_canister(), "canister_info", rejection)
+    ))?;
+
+    info.module_hash
+        .map(ModuleHash)
+        .ok_or_else(|| IssuanceError::CanisterHasNoModule.into())
+}
+
+/// Caches the installed module hash of an achievement canister for `MODULE_HASH_TTL`.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+/// * `module_hash` - The module hash installed on the canister.
+fn _cache_installed_module_hash(canister: Principal, module_hash: ModuleHash) {
+    let expires_at = ic_cdk::api::time().saturating_add(MODULE_HASH_TTL.as_nanos() as u64);
+    INSTALLED_MODULE_HASHES.with(|m| m.borrow_mut().insert(canister, (module_hash, expires_at)));
+}
+
+/// Retrieves the installed module hash of an achievement canister, from the cache while it is fresh.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<ModuleHash, ReputationError>` - The module hash of the canister.
+async fn installed_module_hash(canister: Principal) -> Result<ModuleHash, ReputationError> {
+    let now = ic_cdk::api::time();
+    let cached = INSTALLED_MODULE_HASHES.with(|m| m.borrow().get(&canister).cloned());
+
+    if let Some((module_hash, expires_at)) = cached {
+        if now < expires_at {
+            return Ok(module_hash);
+        }
+    }
+
+    let module_hash = fetch_module_hash(canister).await?;
+    _cache_installed_module_hash(canister, module_hash.clone());
+
+    Ok(module_hash)
+}
+
+/// Verifies that an achievement canister still runs the module it was approved with.
+///
+/// The installed module hash is fetched with `canister_info` at most once per `MODULE_HASH_TTL`,
+/// so an upgrade of the achievement canister is noticed within that delay.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - `Ok` if the installed module matches the approved one.
+pub async fn verify_module_hash(canister: Principal) -> Result<(), ReputationError> {
+    let approved = ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().get(&StorablePrincipal(canister)))
+        .ok_or(EligibilityError::ModuleNotApproved)?;
+
+    if installed_module_hash(canister).await? != approved {
+        return Err(EligibilityError::ModuleChanged.into());
+    }
+
+    Ok(())
+}
+
+/// Retrieves the hash of the module an achievement canister was approved with.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Option<String>` - The approved module hash in hexadecimal format, if any.
+#[query(name = "getApprovedModuleHash")]
+pub fn get_approved_module_hash(canister: Principal) -> Option<String> {
+    ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().get(&StorablePrincipal(canister)))
+        .map(|module_hash| hex::encode(module_hash.0))
+}
+
+/// Checks if a canister is allowed.
+///
+/// This function checks if a specified canister has the required permissions.
+///
+/// # Arguments
+///
+/// * `canister` - The principal of the canister.
+///
+/// # Returns
+///
+/// * `Result<CanisterPermission, ReputationError>` - The permission status of the canister.
+#[query(name = "isCanisterAllowed")]
+pub fn is_canister_allowed(canister: Principal) -> Result<CanisterPermission, ReputationError> {
+    if let Some(permission) = ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| p.borrow().get(&StorablePrincipal(canister))) {
+        Ok(permission)
+    } else {
+        Err(EligibilityError::CanisterNotFound.into())
+    }
+}
+
+/// Reserves token ids for achievements about to be minted.
+///
+/// Token ids released by failed mints are reused first, lowest first. The remaining ids are taken
+/// by incrementing the total count of issued achievements in the reputation module before minting,
+/// so that concurrent issuances never mint the same token id.
+///
+/// # Arguments
+///
+/// * `count` - The number of token ids to reserve.
+///
+/// # Returns
+///
+/// * `Result<Vec<u128>, ReputationError>` - The reserved token ids.
+pub fn reserve_token_ids(count: usize) -> Result<Vec<u128>, ReputationError> {
+    let mut token_ids: Vec<u128> = RELEASED_TOKEN_IDS.with(|r| {
+        let mut released = r.borrow_mut();
+        std::iter::from_fn(|| released.pop_first().map(|(token_id, _)| token_id))
+            .take(count)
+            .collect()
+    });
+
+    if token_ids.len() < count {
+        let mut reputation_module_metadata = get_reputation_module_metadata();
+        let first_token_id = reputation_module_metadata.total_issued + 1;
+        let missing = (count - token_ids.len()) as u128;
+        reputation_module_metadata.total_issued += missing;
+
+        _update_canister_metadata(reputation_module_metadata)?;
+        token_ids.extend(first_token_id..first_token_id + missing);
+    }
+
+    Ok(token_ids)
+}
+
+/// Releases token ids reserved for achievements that could not be minted.
+///
+/// The released ids are kept in a pool and handed out again by `reserve_token_ids`, so that failed
+/// mints never leave permanent gaps. Released ids at the end of the reserved range are rolled back
+/// from the total count of issued achievements instead.
+///
+/// # Arguments
+///
+/// * `token_ids` - The reserved token ids to release.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+pub fn release_token_ids(token_ids: &[u128]) -> Result<(), ReputationError> {
+    if token_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut reputation_module_metadata = get_reputation_module_metadata();
+
+    RELEASED_TOKEN_IDS.with(|r| {
+        let mut released = r.borrow_mut();
+
+        for &token_id in token_ids {
+            released.insert(token_id, ());
+        }
+
+        while released.last_key_value().is_some_and(|(token_id, _)| token_id == reputation_module_metadata.total_issued) {
+            released.pop_last();
+            reputation_module_metadata.total_issued -= 1;
+        }
+    });
+
+    _update_canister_metadata(reputation_module_metadata)?;
+
+    Ok(())
+}
+
+/// Updates the metadata of the reputation module.
+///
+/// This function updates the metadata of the reputation module with the provided metadata.
+///
+/// # Arguments
+///
+/// * `metadata` - The new metadata for the reputation module.
+///
+/// # Returns
+///
+/// * `Result<ReputationModuleMetadata, ReputationError>` - The result of the update operation.
+pub fn _update_canister_metadata(metadata: ReputationModuleMetadata) -> Result<ReputationModuleMetadata, ReputationError> {
+    let issuer_renamed = get_reputation_module_metadata().issuer_name != metadata.issuer_name;
+    let previous = METADATA.with(|m| {
+        let mut metadata_module = m.borrow_mut();
+        metadata_module.set(metadata)
+    }).unwrap_or_else(|err| {
+        ic_cdk::trap(&format!("{:?}", err))
+    });
+    certify_metadata();
+
+    if issuer_renamed {
+        certify_all_badges();
+    }
+
+    Ok(previous)
+}
+
+/// Updates the metadata of the reputation module.
+///
+/// This function updates the metadata of the reputation module with the provided metadata.
+///
+/// # Arguments
+///
+/// * `metadata` - The new metadata for the reputation module.
+///
+/// # Returns
+///
+/// * `Result<ReputationModuleMetadata, ReputationError>` - The result of the update operation.
+#[update(name = "updateReputationModuleMetadata")]
+pub fn update_reputation_canister_metadata(metadata: ReputationModuleMetadata) -> Result<ReputationModuleMetadata, ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+    _update_canister_metadata(metadata)
+}
+
+/// Retrieves the metadata of the reputation module.
+///
+/// This function returns the current metadata of the reputation module.
+///
+/// # Returns
+///
+/// * `ReputationModuleMetadata` - The current metadata of the reputation module.
+#[query(name = "getReputationModuleMetadata")]
+pub fn get_reputation_module_metadata() -> ReputationModuleMetadata {
+    METADATA.with(|m| {
+        let metadata = m.borrow();
+        metadata.get().clone()
+    })
+}
+
+/// Retrieves the metadata of an achievement.
+///
+/// This function returns the cached metadata of a specified achievement. The cache is filled
+/// when the achievement canister is allowed, refreshed periodically, and updated whenever the
+/// achievement canister notifies a change of its metadata.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<AchievementMetadata, ReputationError>` - The metadata of the achievement.
+#[query(name = "getAchievementMetadata")]
+pub fn get_achievement_metadata(achievement: Principal) -> Result<AchievementMetadata, ReputationError> {
+    get_cached_achievement_metadata(achievement).ok_or_else(|| IssuanceError::MetadataNotCached.into())
+}
+
+/// Fetches the metadata of an achievement from its canister and caches it.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<AchievementMetadata, ReputationError>` - The metadata of the achievement.
+pub async fn fetch_achievement_metadata(achievement: Principal) -> Result<AchievementMetadata, ReputationError> {
+    let achievement_metadata: (AchievementMetadata, ) = ic_cdk::call(achievement, "getAchievementMetadata", ())
+        .await
+        .map_err(|rejection| IssuanceError::CallFailed(
+            CallError::new(achievement, "getAchievementMetadata", rejection)
+        ))?;
+
+    _cache_achievement_metadata(achievement, achievement_metadata.0.clone());
+
+    Ok(achievement_metadata.0)
+}
+
+/// Refreshes the cached metadata of an achievement.
+///
+/// This function forces a refresh of the cache by calling the achievement canister.
+/// Only admins can refresh the cache.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<AchievementMetadata, ReputationError>` - The refreshed metadata of the achievement.
+#[update(name = "refreshAchievementMetadata")]
+pub async fn refresh_achievement_metadata(achievement: Principal) -> Result<AchievementMetadata, ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+    is_canister_allowed(achievement)?;
+
+    fetch_achievement_metadata(achievement).await
+}
+
+/// Refreshes the cached metadata of every achievement of the catalog.
+pub async fn _refresh_all_achievement_metadata() {
+    let canisters: Vec<Principal> = ACHIEVEMENTS.with(|a| a.borrow().iter().map(|canister| canister.0).collect());
+
+    for canister in canisters {
+        if let Err(err) = fetch_achievement_metadata(canister).await {
+            ic_cdk::println!("Unable to refresh metadata of {}: {:?}", canister, err);
+        }
+    }
+}
+
+/// Starts the timer refreshing the cached achievement metadata.
+pub fn _start_metadata_refresh_timer() {
+    ic_cdk_timers::set_timer_interval(METADATA_REFRESH_INTERVAL, || ic_cdk::spawn(_refresh_all_achievement_metadata()));
+}
+
+/// Updates the cached metadata of the calling achievement canister.
+///
+/// This function is called by an achievement canister whenever its metadata changes. Only
+/// achievement canisters currently allowed to issue achievements can update their metadata.
+///
+/// # Arguments
+///
+/// * `metadata` - The new metadata of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "notifyAchievementMetadataChanged")]
+pub fn notify_achievement_metadata_changed(metadata: AchievementMetadata) -> Result<(), ReputationError> {
+    let achievement = ic_cdk::api::caller();
+
+    if !is_canister_allowed(achievement)?.0 {
+        return Err(EligibilityError::AchievementNotAllowed.into());
+    }
+    _cache_achievement_metadata(achievement, metadata);
+
+    Ok(())
+}
+
+/// Sets the reputation weight of an achievement canister.
+///
+/// The weight is applied to achievements issued after the change.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+/// * `weight` - The number of reputation points granted by the achievement.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setAchievementWeight")]
+pub fn set_achievement_weight(achievement: Principal, weight: u64) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    is_canister_allowed(achievement)?;
+
+    ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| w.borrow_mut().insert(StorablePrincipal(achievement), AchievementWeight(weight)));
+
+    Ok(())
+}
+
+/// Retrieves the reputation weight of an achievement canister.
+///
+/// Achievements without a configured weight are worth a single point.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `u64` - The number of reputation points granted by the achievement.
+#[query(name = "getAchievementWeight")]
+pub fn get_achievement_weight(achievement: Principal) -> u64 {
+    ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| w.borrow().get(&StorablePrincipal(achievement)))
+        .map(|weight| weight.0)
+        .unwrap_or(1)
+}
+
+/// Sets the decay model of an achievement canister.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+/// * `decay` - The decay model applied to the score of the achievement.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setAchievementDecay")]
+pub fn set_achievement_decay(achievement: Principal, decay: DecayModel) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    is_canister_allowed(achievement)?;
+
+    ACHIEVEMENT_CANISTER_TO_DECAY.with(|d| d.borrow_mut().insert(StorablePrincipal(achievement), decay));
+
+    Ok(())
+}
+
+/// Retrieves the decay model of an achievement canister.
+///
+/// Achievements without a configured decay model never decay.
+///
+/// # Arguments
+///
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `DecayModel` - The decay model applied to the score of the achievement.
+#[query(name = "getAchievementDecay")]
+pub fn get_achievement_decay(achievement: Principal) -> DecayModel {
+    ACHIEVEMENT_CANISTER_TO_DECAY.with(|d| d.borrow().get(&StorablePrincipal(achievement)))
+        .unwrap_or(DecayModel::None)
+}
+
+/// Sums the scores of a reputation breakdown.
+///
+/// # Arguments
+///
+/// * `breakdown` - The scores of every achievement.
+///
+/// # Returns
+///
+/// * `Result<u64, ReputationError>` - The total score, or `ScoreOverflow` if it does not fit a `u64`.
+fn total_score(breakdown: &[ReputationEntry]) -> Result<u64, ReputationError> {
+    breakdown.iter()
+        .try_fold(0_u64, |total, entry| total.checked_add(entry.score))
+        .ok_or_else(|| IssuanceError::ScoreOverflow.into())
+}
+
+/// Builds the reputation of a principal with the weight of an achievement added.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<Reputation, ReputationError>` - The reputation the principal would have, or `ScoreOverflow`.
+fn with_achievement_score(identity_wallet: Principal, achievement: Principal) -> Result<Reputation, ReputationError> {
+    let mut reputation = get_reputation(identity_wallet);
+
+    reputation.breakdown.retain(|entry| entry.achievement != achievement);
+    reputation.breakdown.push(ReputationEntry { achievement, score: get_achievement_weight(achievement) });
+    reputation.total = total_score(&reputation.breakdown)?;
+
+    Ok(reputation)
+}
+
+/// Checks that the weight of an achievement can be added to a principal's reputation.
+///
+/// Called before minting, so that no token is minted for a score that cannot be recorded.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - `Ok` if the total score stays within a `u64`, `ScoreOverflow` otherwise.
+pub fn ensure_score_fits(identity_wallet: Principal, achievement: Principal) -> Result<(), ReputationError> {
+    with_achievement_score(identity_wallet, achievement).map(|_| ())
+}
+
+/// Adds the weight of an achievement to a principal's reputation.
+///
+/// Nothing is written if the total score would overflow.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<Reputation, ReputationError>` - The updated reputation of the principal, or `ScoreOverflow`.
+pub fn _add_reputation(identity_wallet: Principal, achievement: Principal) -> Result<Reputation, ReputationError> {
+    let previous_total = get_reputation(identity_wallet).total;
+    let reputation = with_achievement_score(identity_wallet, achievement)?;
+
+    PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(identity_wallet), reputation.clone()));
+    _update_leaderboard(identity_wallet, Some(previous_total), Some(reputation.total));
+
+    Ok(reputation)
+}
+
+/// Removes the score of an achievement from a principal's reputation.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `achievement` - The principal of the achievement canister.
+///
+/// # Returns
+///
+/// * `Result<Reputation, ReputationError>` - The updated reputation of the principal.
+pub fn _subtract_reputation(identity_wallet: Principal, achievement: Principal) -> Result<Reputation, ReputationError> {
+    let mut reputation = get_reputation(identity_wallet);
+    let previous_total = reputation.total;
+
+    reputation.breakdown.retain(|entry| entry.achievement != achievement);
+    reputation.total = total_score(&reputation.breakdown)?;
+
+    PRINCIPAL_TO_REPUTATION.with(|r| {
+        let mut reputations = r.borrow_mut();
+        if reputation.breakdown.is_empty() {
+            reputations.remove(&StorablePrincipal(identity_wallet));
+        } else {
+            reputations.insert(StorablePrincipal(identity_wallet), reputation.clone());
+        }
+    });
+    let total = if reputation.breakdown.is_empty() { None } else { Some(reputation.total) };
+    _update_leaderboard(identity_wallet, Some(previous_total), total);
+
+    Ok(reputation)
+}
+
+/// Retrieves the reputation of a principal.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Reputation` - The total score of the principal and its breakdown by achievement.
+#[query(name = "getReputation")]
+pub fn get_reputation(identity_wallet: Principal) -> Reputation {
+    PRINCIPAL_TO_REPUTATION.with(|r| r.borrow().get(&StorablePrincipal(identity_wallet)))
+        .unwrap_or_default()
+}
+
+/// Retrieves the part of a principal's reputation earned by the achievements issued to one of its accounts.
+///
+/// # Arguments
+///
+/// * `account` - The account of the identity wallet.
+///
+/// # Returns
+///
+/// * `Reputation` - The total score of the achievements issued to the account and its breakdown by achievement.
+#[query(name = "getReputationOfAccount")]
+pub fn get_reputation_of_account(account: Account) -> Reputation {
+    let mut reputation = get_reputation(account.owner);
+
+    reputation.breakdown.retain(|entry| get_account_achievement_status(account, entry.achievement));
+    reputation.total = reputation.breakdown.iter().map(|entry| entry.score).sum();
+
+    reputation
+}
+
+/// Moves a principal within the leaderboard after its score changed.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+/// * `previous_score` - The score the principal was ranked with, if any.
+/// * `score` - The new score of the principal, or `None` to remove it from the leaderboard.
+pub fn _update_leaderboard(identity_wallet: Principal, previous_score: Option<u64>, score: Option<u64>) {
+    LEADERBOARD.with(|l| {
+        let mut leaderboard = l.borrow_mut();
+
+        if let Some(previous_score) = previous_score {
+            if leaderboard.remove(&LeaderboardKey { score: Reverse(previous_score), principal: identity_wallet }).is_some() {
+                _adjust_score_count(previous_score, false);
+            }
+        }
+        if let Some(score) = score {
+            if leaderboard.insert(LeaderboardKey { score: Reverse(score), principal: identity_wallet }, ()).is_none() {
+                _adjust_score_count(score, true);
+            }
+        }
+    });
+}
+
+/// Adjusts the number of ranked principals sharing a score.
+///
+/// # Arguments
+///
+/// * `score` - The score of the principal.
+/// * `increment` - Whether a principal was ranked with the score (`true`) or removed from it (`false`).
+fn _adjust_score_count(score: u64, increment: bool) {
+    SCORE_TO_PRINCIPAL_COUNT.with(|c| {
+        let mut counts = c.borrow_mut();
+        let count = counts.get(&score).unwrap_or(0);
+        let count = if increment { count + 1 } else { count.saturating_sub(1) };
+
+        if count == 0 {
+            counts.remove(&score);
+        } else {
+            counts.insert(score, count);
+        }
+    });
+}
+
+/// Retrieves the number of ranked principals sharing a score.
+fn score_count(score: u64) -> u64 {
+    SCORE_TO_PRINCIPAL_COUNT.with(|c| c.borrow().get(&score)).unwrap_or(0)
+}
+
+/// Rebuilds the leaderboard from the stored reputation of every principal.
+///
+/// # Returns
+///
+/// * `u64` - The number of ranked principals.
+pub fn _rebuild_leaderboard() -> u64 {
+    LEADERBOARD.with(|l| {
+        let mut leaderboard = l.borrow_mut();
+        leaderboard.clear_new();
+        SCORE_TO_PRINCIPAL_COUNT.with(|c| c.borrow_mut().clear_new());
+
+        PRINCIPAL_TO_REPUTATION.with(|r| {
+            for (principal, reputation) in r.borrow().iter() {
+                leaderboard.insert(LeaderboardKey { score: Reverse(reputation.total), principal: principal.0 }, ());
+                _adjust_score_count(reputation.total, true);
+            }
+        });
+
+        leaderboard.len()
+    })
+}
+
+/// Checks whether a principal is an admin of the reputation module.
+///
+/// # Arguments
+///
+/// * `principal` - The principal to check.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the principal is an admin, `false` otherwise.
+pub fn is_admin_principal(principal: Principal) -> bool {
+    ADMINS.with(|a| a.borrow().contains_key(&StorablePrincipal(principal)))
+}
+
+/// Retrieves the admins of the reputation module.
+///
+/// # Returns
+///
+/// * `Vec<Principal>` - The admins set at install time.
+#[query(name = "getAdmins")]
+pub fn get_admins() -> Vec<Principal> {
+    ADMINS.with(|a| a.borrow().iter().map(|(admin, _)| admin.0).collect())
+}
+
+/// Validates the arguments passed to the reputation module at install time.
+///
+/// # Arguments
+///
+/// * `args` - The init arguments.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - `Ok` if the arguments are valid.
+fn _validate_init_args(args: &ReputationModuleInitArgs) -> Result<(), ReputationError> {
+    if args.issuer_name.trim().is_empty() {
+        return Err(ConfigError::EmptyField(String::from("issuer_name")).into());
+    }
+
+    let reserved = [Principal::anonymous(), Principal::management_canister(), ic_cdk::api::id()];
+
+    if reserved.contains(&args.achievement_collection) {
+        return Err(ConfigError::InvalidPrincipal(args.achievement_collection).into());
+    }
+
+    for principals in [&args.allowed_canisters, &args.admins] {
+        for (i, principal) in principals.iter().enumerate() {
+            if reserved.contains(principal) {
+                return Err(ConfigError::InvalidPrincipal(*principal).into());
+            }
+            if principals[..i].contains(principal) {
+                return Err(ConfigError::DuplicatePrincipal(*principal).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the arguments passed to the reputation module at install time.
+///
+/// The allowed canisters are registered right away, while their module hashes and metadata are
+/// fetched by a one-off timer since calls cannot be made during install. An allowed canister whose
+/// approval fails can be approved again with `changePermissionCanister`.
+///
+/// # Arguments
+///
+/// * `args` - The init arguments.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the configuration.
+fn _apply_init_args(args: ReputationModuleInitArgs) -> Result<(), ReputationError> {
+    _validate_init_args(&args)?;
+
+    _update_canister_metadata(ReputationModuleMetadata {
+        achievement_collection: args.achievement_collection,
+        issuer_name: args.issuer_name,
+        issuer_description: args.issuer_description,
+        total_issued: 0
+    })?;
+
+    ADMINS.with(|a| {
+        let mut admins = a.borrow_mut();
+        for admin in &args.admins {
+            admins.insert(StorablePrincipal(*admin), ());
+        }
+    });
+
+    for canister in &args.allowed_canisters {
+        _set_canister_permission(*canister, true)?;
+    }
+
+    if let Some(ecdsa_key) = args.ecdsa_key {
+        _set_ecdsa_key(ecdsa_key);
+    }
+
+    _schedule_approvals(args.allowed_canisters, 0);
+
+    Ok(())
+}
+
+/// Approves the achievement canisters allowed at install time from a timer.
+///
+/// Canisters that could not be approved are retried with an exponential backoff, up to
+/// `MAX_APPROVAL_ATTEMPTS` attempts. Canisters that are no longer allowed, or were approved in the
+/// meantime, are skipped. Canisters left unapproved are reported by `getCanisterStatus`.
+///
+/// # Arguments
+///
+/// * `canisters` - The principals of the achievement canisters to approve.
+/// * `attempt` - The number of attempts already made.
+fn _schedule_approvals(canisters: Vec<Principal>, attempt: u32) {
+    let delay = match attempt {
+        0 => Duration::ZERO,
+        _ => APPROVAL_RETRY_DELAY * 2_u32.pow(attempt - 1)
+    };
+
+    ic_cdk_timers::set_timer(delay, move || ic_cdk::spawn(async move {
+        let mut failed = vec![];
+
+        for canister in canisters {
+            let allowed = is_canister_allowed(canister).is_ok_and(|permission| permission.0);
+            let approved = ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().contains_key(&StorablePrincipal(canister)));
+
+            if !allowed || approved {
+                continue;
+            }
+            if let Err(err) = _approve_achievement_canister(canister).await {
+                ic_cdk::println!("Unable to approve achievement canister {} (attempt {}): {:?}", canister, attempt + 1, err);
+                failed.push(canister);
+            }
+        }
+
+        if !failed.is_empty() && attempt + 1 < MAX_APPROVAL_ATTEMPTS {
+            _schedule_approvals(failed, attempt + 1);
+        }
+    }));
+}
+
+/// Retrieves the allowed achievement canisters that have no approved module hash yet.
+///
+/// # Returns
+///
+/// * `Vec<Principal>` - The principals of the unapproved achievement canisters.
+pub fn get_unapproved_canisters() -> Vec<Principal> {
+    ACHIEVEMENT_CANISTER_TO_BOOL.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(canister, permission)| {
+                permission.0 && !ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().contains_key(canister))
+            })
+            .map(|(canister, _)| canister.0)
+            .collect()
+    })
+}
+
+/// Initializes the reputation module, optionally configuring it from the install arguments.
+///
+/// # Arguments
+///
+/// * `args` - The initial configuration. When omitted, the module must be configured after deployment.
+#[init]
+fn init(args: Option<ReputationModuleInitArgs>) {
+    if let Some(args) = args {
+        if let Err(err) = _apply_init_args(args) {
+            ic_cdk::trap(&format!("Invalid init arguments: {:?}", err));
+        }
+    }
+
+    _set_schema_version(SCHEMA_VERSION);
+    certify_metadata();
+    _start_metadata_refresh_timer();
+    _start_public_key_fetch();
+}
+
+/// Retrieves the stable-memory schema version of the reputation module.
+///
+/// # Returns
+///
+/// * `u32` - The schema version, `0` for state written before the schema was versioned.
+#[query(name = "getSchemaVersion")]
+pub fn get_schema_version() -> u32 {
+    STABLE_SCHEMA_VERSION.with(|v| *v.borrow().get())
+}
+
+/// Stores the stable-memory schema version of the reputation module.
+///
+/// # Arguments
+///
+/// * `version` - The schema version.
+pub fn _set_schema_version(version: u32) {
+    STABLE_SCHEMA_VERSION.with(|v| v.borrow_mut().set(version))
+        .unwrap_or_else(|err| ic_cdk::trap(&format!("{:?}", err)));
+}
+
+/// Migrates the stable memory to the current schema version after an upgrade and certifies the HTTP responses again.
+#[post_upgrade]
+fn post_upgrade() {
+    _start_metadata_refresh_timer();
+    _start_public_key_fetch();
+
+    run_migrations();
+    certify_all();
+}
+
+/// Rebuilds the leaderboard from the stored reputation of every principal.
+///
+/// # Returns
+///
+/// * `Result<u64, ReputationError>` - The number of ranked principals.
+#[update(name = "rebuildLeaderboard")]
+pub fn rebuild_leaderboard() -> Result<u64, ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    Ok(_rebuild_leaderboard())
+}
+
+/// Retrieves a page of the leaderboard.
+///
+/// Principals with equal scores share the same rank. Pass the `next_cursor` of a page to retrieve the
+/// following one; the ranks of a page are derived from the rank of its cursor, so they may be off if
+/// scores changed in between.
+///
+/// # Arguments
+///
+/// * `cursor` - The last entry of the previous page, or `None` to start from the top.
+/// * `limit` - The maximum number of entries to return, capped at 1000.
+///
+/// # Returns
+///
+/// * `LeaderboardPage` - The ranked principals with their scores.
+#[query(name = "getLeaderboard")]
+pub fn get_leaderboard(cursor: Option<LeaderboardEntry>, limit: u64) -> LeaderboardPage {
+    let limit = limit.min(MAX_PAGE) as usize;
+    let start = match &cursor {
+        Some(cursor) => Bound::Excluded(LeaderboardKey { score: Reverse(cursor.score), principal: cursor.principal }),
+        None => Bound::Unbounded
+    };
+    let mut previous = cursor.map(|cursor| (cursor.score, cursor.rank));
+
+    let keys: Vec<LeaderboardKey> = LEADERBOARD.with(|l| {
+        l.borrow().range((start, Bound::Unbounded)).take(limit + 1).map(|(key, _)| key).collect()
+    });
+
+    let has_more = keys.len() > limit;
+    let entries: Vec<LeaderboardEntry> = keys
+        .into_iter()
+        .take(limit)
+        .map(|key| {
+            let score = key.score.0;
+            let rank = match previous {
+                Some((previous_score, rank)) if previous_score == score => rank,
+                Some((previous_score, rank)) => rank + score_count(previous_score),
+                None => get_rank_of_score(score)
+            };
+            previous = Some((score, rank));
+            LeaderboardEntry { rank, principal: key.principal, score }
+        })
+        .collect();
+    let next_cursor = if has_more { entries.last().cloned() } else { None };
+
+    LeaderboardPage { entries, next_cursor }
+}
+
+/// Retrieves the rank of a principal in the leaderboard.
+///
+/// The rank is computed from the number of principals ranked with every higher score, so the cost
+/// grows with the number of distinct higher scores rather than with the number of principals.
+///
+/// # Arguments
+///
+/// * `identity_wallet` - The principal of the identity wallet.
+///
+/// # Returns
+///
+/// * `Option<u64>` - The rank of the principal, or `None` if it holds no achievements.
+#[query(name = "getRank")]
+pub fn get_rank(identity_wallet: Principal) -> Option<u64> {
+    let score = PRINCIPAL_TO_REPUTATION.with(|r| r.borrow().get(&StorablePrincipal(identity_wallet)))?.total;
+
+    Some(get_rank_of_score(score))
+}
+
+/// Computes the rank of a score: one more than the number of principals ranked with a higher score.
+fn get_rank_of_score(score: u64) -> u64 {
+    SCORE_TO_PRINCIPAL_COUNT.with(|c| {
+        c.borrow()
+            .range((Bound::Excluded(score), Bound::Unbounded))
+            .fold(1, |rank, (_, count)| rank + count)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n])
+    }
+
+    fn set_score(identity_wallet: Principal, score: u64) {
+        let previous = PRINCIPAL_TO_REPUTATION.with(|r| r.borrow().get(&StorablePrincipal(identity_wallet)))
+            .map(|reputation| reputation.total);
+
+        PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(identity_wallet), Reputation {
+            total: score,
+            breakdown: vec![]
+        }));
+        _update_leaderboard(identity_wallet, previous, Some(score));
+    }
+
+    fn ranks(page: LeaderboardPage) -> Vec<(Principal, u64)> {
+        page.entries.into_iter().map(|entry| (entry.principal, entry.rank)).collect()
+    }
+
+    #[test]
+    fn leaderboard_ranks_equal_scores_together() {
+        set_score(principal(1), 10);
+        set_score(principal(2), 30);
+        set_score(principal(3), 10);
+        set_score(principal(4), 5);
+
+        assert_eq!(
+            ranks(get_leaderboard(None, 10)),
+            vec![(principal(2), 1), (principal(1), 2), (principal(3), 2), (principal(4), 4)]
+        );
+        let first_page = get_leaderboard(None, 2);
+        let cursor = first_page.next_cursor.clone();
+        assert_eq!(ranks(first_page), vec![(principal(2), 1), (principal(1), 2)]);
+        let second_page = get_leaderboard(cursor, 2);
+        assert!(second_page.next_cursor.is_none());
+        assert_eq!(ranks(second_page), vec![(principal(3), 2), (principal(4), 4)]);
+        assert_eq!(get_rank(principal(3)), Some(2));
+        assert_eq!(get_rank(principal(4)), Some(4));
+        assert_eq!(get_rank(principal(5)), None);
+    }
+
+    #[test]
+    fn leaderboard_moves_a_principal_when_its_score_changes() {
+        set_score(principal(1), 10);
+        set_score(principal(2), 20);
+        set_score(principal(1), 30);
+
+        assert_eq!(ranks(get_leaderboard(None, 10)), vec![(principal(1), 1), (principal(2), 2)]);
+        assert_eq!(get_rank_of_score(20), 2);
+        assert_eq!(score_count(10), 0);
+        assert_eq!(_rebuild_leaderboard(), 2);
+        assert_eq!(ranks(get_leaderboard(None, 10)), vec![(principal(1), 1), (principal(2), 2)]);
+        assert_eq!(get_rank_of_score(20), 2);
+        assert_eq!(score_count(30), 1);
+    }
+
+    #[test]
+    fn total_score_reports_an_overflow() {
+        let entry = |score| ReputationEntry { achievement: principal(1), score };
+
+        assert_eq!(total_score(&[entry(1), entry(2)]).unwrap(), 3);
+        assert_eq!(total_score(&[entry(u64::MAX), entry(0)]).unwrap(), u64::MAX);
+        assert!(matches!(
+            total_score(&[entry(u64::MAX), entry(1)]),
+            Err(ReputationError::Issuance(IssuanceError::ScoreOverflow))
+        ));
+    }
+
+    #[test]
+    fn score_that_would_overflow_is_refused_before_minting() {
+        PRINCIPAL_TO_REPUTATION.with(|r| r.borrow_mut().insert(StorablePrincipal(principal(1)), Reputation {
+            total: u64::MAX - 1,
+            breakdown: vec![ReputationEntry { achievement: principal(2), score: u64::MAX - 1 }]
+        }));
+
+        assert!(ensure_score_fits(principal(1), principal(3)).is_ok());
+
+        ACHIEVEMENT_CANISTER_TO_WEIGHT.with(|w| w.borrow_mut().insert(StorablePrincipal(principal(3)), AchievementWeight(2)));
+
+        assert!(matches!(
+            ensure_score_fits(principal(1), principal(3)),
+            Err(ReputationError::Issuance(IssuanceError::ScoreOverflow))
+        ));
+        assert!(ensure_score_fits(principal(1), principal(2)).is_ok());
+    }
+
+    #[test]
+    fn achievements_issued_to_several_accounts_of_a_principal_are_kept_apart() {
+        let achievement = principal(9);
+        let default = Account::from(principal(1));
+        let savings = Account { owner: principal(1), subaccount: Some([1u8; 32]) };
+
+        _change_account_achievement_status_to_issued(&default, achievement).unwrap();
+        _change_account_achievement_status_to_issued(&savings, achievement).unwrap();
+        _import_issuance(1, Issuance { owner: principal(1), subaccount: None, achievement, issued_at: 7, ordinal: None });
+
+        assert_eq!(get_accounts_issued(principal(1), achievement), vec![default, savings]);
+        assert_eq!(get_issued_achievements_of(principal(1)), vec![achievement]);
+        assert_eq!(get_holder_count(achievement), 2);
+        assert!(get_account_achievement_status(savings, achievement));
+        assert!(!get_account_achievement_status(Account { owner: principal(1), subaccount: Some([2u8; 32]) }, achievement));
+
+        let first_page = get_holders(achievement, None, 1);
+        assert_eq!(first_page.next_cursor, Some(default));
+        assert_eq!(first_page.holders.iter().map(|holder| (holder.token_id, holder.issued_at)).collect::<Vec<_>>(), vec![(Some(1), Some(7))]);
+        let second_page = get_holders(achievement, first_page.next_cursor, 1);
+        assert_eq!(second_page.holders.iter().map(|holder| (holder.subaccount, holder.token_id)).collect::<Vec<_>>(), vec![(savings.subaccount, None)]);
+        assert!(second_page.next_cursor.is_none());
+
+        _change_account_achievement_status_to_revoked(&default, achievement).unwrap();
+        assert_eq!(get_holders_of(achievement), vec![savings]);
+        assert_eq!(get_holder_count(achievement), 1);
+        assert!(get_principal_achievement_sum_status(principal(1), achievement));
+    }
+}
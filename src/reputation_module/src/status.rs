@@ -0,0 +1,120 @@
+//! This module reports the resource usage of the reputation module and refuses minting when its cycles run low.
+
+use ic_cdk::{query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableCell;
+use std::cell::RefCell;
+
+use crate::access::is_admin;
+use crate::errors::{AccessError, IssuanceError, ReputationError};
+use crate::state::{
+    MEMORY_MANAGER, ACHIEVEMENT_CANISTER_TO_BOOL, TOKEN_ID_TO_ISSUANCE, ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED,
+    PRINCIPAL_TO_REPUTATION, LEADERBOARD, SCORE_TO_PRINCIPAL_COUNT, ACCOUNT_TO_ACHIEVEMENTS, ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT,
+    ACHIEVEMENT_CANISTER_TO_METADATA, ACHIEVEMENT_CANISTER_TO_MODULE_HASH, ADMINS,
+    ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT, TOKEN_ID_TO_REVOCATION, get_reputation_module_metadata, get_unapproved_canisters
+};
+use crate::credential::PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL;
+use crate::metrics::get_credential_counts;
+use crate::storable::Memory;
+use crate::types::{CanisterStatus, MapSize};
+
+/// The size of a WebAssembly page, in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// The default cycles balance below which minting is refused.
+const DEFAULT_LOW_CYCLES_THRESHOLD: u128 = 100_000_000_000;
+
+thread_local! {
+    pub(crate) static LOW_CYCLES_THRESHOLD: RefCell<StableCell<u128, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))), DEFAULT_LOW_CYCLES_THRESHOLD,
+        ).unwrap()
+    );
+}
+
+/// Retrieves the cycles balance below which minting is refused.
+///
+/// # Returns
+///
+/// * `u128` - The low cycles threshold.
+pub fn get_low_cycles_threshold() -> u128 {
+    LOW_CYCLES_THRESHOLD.with(|t| *t.borrow().get())
+}
+
+/// Sets the cycles balance below which minting is refused.
+///
+/// # Arguments
+///
+/// * `threshold` - The new low cycles threshold. `0` never refuses minting.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - The result of the update operation.
+#[update(name = "setLowCyclesThreshold")]
+pub fn set_low_cycles_threshold(threshold: u128) -> Result<(), ReputationError> {
+    if !is_admin() {
+        return Err(AccessError::AccessDenied.into());
+    }
+
+    LOW_CYCLES_THRESHOLD.with(|t| t.borrow_mut().set(threshold))
+        .map_err(|err| IssuanceError::StorageError(format!("{:?}", err)))?;
+
+    Ok(())
+}
+
+/// Checks that the cycles balance is above the low cycles threshold.
+///
+/// # Returns
+///
+/// * `Result<(), ReputationError>` - `Ok` if expensive operations may proceed.
+pub fn ensure_cycles_available() -> Result<(), ReputationError> {
+    let balance = ic_cdk::api::canister_balance128();
+    let threshold = get_low_cycles_threshold();
+
+    if balance < threshold {
+        return Err(IssuanceError::LowCycles { balance, threshold }.into());
+    }
+
+    Ok(())
+}
+
+/// Retrieves the resource usage of the reputation module.
+///
+/// # Returns
+///
+/// * `CanisterStatus` - The cycles balance, stable memory usage, number of entries of every stable map
+///   allowed achievement canisters that could not be approved yet and the number of signed and failed credentials.
+#[query(name = "getCanisterStatus")]
+pub fn get_canister_status() -> CanisterStatus {
+    let map_sizes = [
+        ("permissions", ACHIEVEMENT_CANISTER_TO_BOOL.with(|m| m.borrow().len())),
+        ("module_hashes", ACHIEVEMENT_CANISTER_TO_MODULE_HASH.with(|m| m.borrow().len())),
+        ("achievement_metadata", ACHIEVEMENT_CANISTER_TO_METADATA.with(|m| m.borrow().len())),
+        ("issuances", TOKEN_ID_TO_ISSUANCE.with(|m| m.borrow().len())),
+        ("revocations", TOKEN_ID_TO_REVOCATION.with(|m| m.borrow().len())),
+        ("issued_statuses", ACCOUNT_PLUS_ACHIEVEMENT_TO_IS_ISSUED.with(|m| m.borrow().len())),
+        ("held_achievements", ACCOUNT_TO_ACHIEVEMENTS.with(|m| m.borrow().len())),
+        ("holder_counts", ACHIEVEMENT_CANISTER_TO_HOLDER_COUNT.with(|m| m.borrow().len())),
+        ("issuance_counts", ACHIEVEMENT_CANISTER_TO_ISSUANCE_COUNT.with(|m| m.borrow().len())),
+        ("credentials", PRINCIPAL_PLUS_ACHIEVEMENT_TO_CREDENTIAL.with(|m| m.borrow().len())),
+        ("reputations", PRINCIPAL_TO_REPUTATION.with(|m| m.borrow().len())),
+        ("leaderboard", LEADERBOARD.with(|m| m.borrow().len())),
+        ("score_counts", SCORE_TO_PRINCIPAL_COUNT.with(|m| m.borrow().len())),
+        ("admins", ADMINS.with(|m| m.borrow().len())),
+    ];
+    let (credential_signatures, credential_failures) = get_credential_counts();
+
+    CanisterStatus {
+        cycles_balance: ic_cdk::api::canister_balance128(),
+        low_cycles_threshold: get_low_cycles_threshold(),
+        stable_memory_bytes: ic_cdk::api::stable::stable64_size() * WASM_PAGE_SIZE,
+        map_sizes: map_sizes.into_iter().map(|(name, len)| MapSize {
+            name: String::from(name),
+            len
+        }).collect(),
+        total_issued: get_reputation_module_metadata().total_issued,
+        unapproved_canisters: get_unapproved_canisters(),
+        credential_signatures,
+        credential_failures
+    }
+}
@@ -2,15 +2,23 @@
 
 use candid::{Principal, CandidType, Deserialize, Encode, Decode};
 use ic_stable_structures::memory_manager::VirtualMemory;
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
 use ic_stable_structures::{
     storable::Bound, DefaultMemoryImpl, Storable,
 };
 use std::borrow::Cow;
+use std::cmp::Reverse;
+
+use crate::types::AchievementMetadata;
 
 pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 pub const MAX_VALUE_SIZE: u32 = 100;
 pub const MAX_KEY_SIZE: u32 = 100;
+pub const MAX_PAUSE_EVENT_SIZE: u32 = 256;
+
+/// The maximum length of a principal in bytes.
+const MAX_PRINCIPAL_SIZE: usize = 29;
 
 /// A wrapper for `Principal` to make it storable.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -20,10 +28,125 @@ pub struct StorablePrincipal(pub Principal);
 #[derive(CandidType, Deserialize, Clone)]
 pub struct CanisterPermission(pub bool);
 
+/// Represents the SHA-256 hash of the module installed on a canister.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct ModuleHash(pub Vec<u8>);
+
 /// Represents a sum of principal and achievement.
+///
+/// Legacy key built by concatenating the textual representation of both principals.
+/// Superseded by `PrincipalPair` and only kept to migrate existing entries.
 #[derive(CandidType, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PrincipalSum(pub String);
 
+/// A fixed-layout key composed of two principals, ordered by the first one.
+///
+/// Keys sharing the same first principal are adjacent, which allows prefix scans.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct PrincipalPair(pub Principal, pub Principal);
+
+/// A fixed-layout key composed of two principals and a subaccount, ordered by the first principal,
+/// then the second one and the subaccount.
+///
+/// The subaccount belongs to the account of whichever principal is the owner, so that the same pair
+/// of principals can be recorded for several accounts of the owner.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct AccountPair(pub Principal, pub Principal, pub Subaccount);
+
+/// A wrapper for `Account` to make it storable, ordered by owner and then by subaccount.
+///
+/// The default subaccount is stored as 32 zero bytes, so that `None` and the zero subaccount are the same key.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct StorableAccount(pub Account);
+
+/// Represents an issued achievement token and its holder.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Issuance {
+    pub owner: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub achievement: Principal,
+    pub issued_at: u64,
+    pub ordinal: Option<u64>
+}
+
+/// Represents an achievement token revoked by an admin.
+///
+/// Revoking an achievement removes its score, but the token stays in the achievement collection,
+/// where only its holder may burn it. The revocation is kept so that the token can be told apart
+/// from the tokens backing a reputation.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Revocation {
+    pub owner: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub achievement: Principal,
+    pub issued_at: u64,
+    pub revoked_at: u64
+}
+
+/// Represents the outcome of a migration of the stable-memory schema.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct MigrationReport {
+    pub version: u32,
+    pub migrated_at: u64,
+    pub migrated: u64,
+    pub unmigrated_keys: Vec<String>
+}
+
+/// Represents an achievement held by an account.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HeldAchievement {
+    pub achievement: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub token_id: u128,
+    pub issued_at: u64
+}
+
+/// Represents the token of an account holding an achievement, as listed among its holders.
+///
+/// The token id and issuance time are unknown for achievements issued before tokens were recorded.
+#[derive(CandidType, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Holding {
+    pub token_id: Option<u128>,
+    pub issued_at: Option<u64>
+}
+
+/// Represents the achievements held by an account, in issuance order.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct HeldAchievements(pub Vec<HeldAchievement>);
+
+/// Represents the reputation weight of an achievement canister.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct AchievementWeight(pub u64);
+
+/// Represents the score a principal received from a single achievement.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ReputationEntry {
+    pub achievement: Principal,
+    pub score: u64
+}
+
+/// Represents the reputation of a principal.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct Reputation {
+    pub total: u64,
+    pub breakdown: Vec<ReputationEntry>
+}
+
+/// Represents how the score of an achievement decays over time.
+#[derive(CandidType, Deserialize, Clone)]
+pub enum DecayModel {
+    None,
+    HalfLife { half_life_seconds: u64 },
+    Linear { lifetime_seconds: u64 }
+}
+
+/// Represents the position of a principal in the leaderboard, ordered by descending score.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct LeaderboardKey {
+    pub score: Reverse<u64>,
+    pub principal: Principal
+}
+
 /// Metadata for the reputation module.
 #[derive(CandidType, Deserialize, Clone)]
 pub struct ReputationModuleMetadata {
@@ -34,39 +157,84 @@ pub struct ReputationModuleMetadata {
 }
 
 /// Represents a standard supported by the reputation module.
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Clone)]
 pub struct Standard {
     pub name: String,
     pub url: String,
 }
 
+/// Records a change of the pause switch of the reputation module or of an achievement.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct PauseEvent {
+    pub achievement: Option<Principal>,
+    pub paused: bool,
+    pub changed_by: Principal,
+    pub changed_at: u64
+}
+
+/// Represents the threshold ECDSA keys available to sign credentials.
+#[derive(CandidType, Deserialize, Clone, Copy, Default)]
+pub enum EcdsaKeyIds {
+    TestKeyLocalDevelopment,
+    TestKey1,
+    #[default]
+    ProductionKey1,
+}
+
+/// Represents a verifiable credential issued for an achievement token.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct IssuedCredential {
+    pub token_id: u128,
+    pub credential: String,
+    pub expires_at: Option<u64>
+}
+
+/// Represents the number of failed calls of an operation for a reason.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct FailureCount {
+    pub operation: String,
+    pub reason: String,
+    pub count: u64
+}
+
+/// Represents the counters exported by the metrics endpoint.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct MetricCounters {
+    pub claims: u64,
+    pub mints: u64,
+    pub revocations: u64,
+    pub failures: Vec<FailureCount>,
+    pub last_error_at: Option<u64>,
+    pub credential_signatures: Option<u64>
+}
+
 macro_rules! impl_storable {
-    ($($t:ty),*) => {
+    ($bound:expr; $($t:ty),*) => {
         $(
             impl Storable for $t {
-                fn to_bytes(&self) -> Cow<[u8]> {
+                fn to_bytes(&self) -> Cow<'_, [u8]> {
                     Cow::Owned(Encode!(self).unwrap())
                 }
 
-                fn from_bytes(bytes: Cow<[u8]>) -> Self {
+                fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
                     Decode!(bytes.as_ref(), Self).unwrap()
                 }
 
-                const BOUND: Bound = Bound::Bounded {
-                    max_size: MAX_VALUE_SIZE,
-                    is_fixed_size: false,
-                };
+                const BOUND: Bound = $bound;
             }
         )*
     };
+    ($($t:ty),*) => {
+        impl_storable!(Bound::Bounded { max_size: MAX_VALUE_SIZE, is_fixed_size: false }; $($t),*);
+    };
 }
 
 impl Storable for StorablePrincipal {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(Principal::from_bytes(bytes))
     }
 
@@ -77,11 +245,11 @@ impl Storable for StorablePrincipal {
 }
 
 impl Storable for PrincipalSum {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(String::from_bytes(bytes))
     }
 
@@ -91,9 +259,123 @@ impl Storable for PrincipalSum {
     };
 }
 
-impl ReputationModuleMetadata {
+/// Writes a principal as its length followed by its bytes, padded to the maximum principal size.
+fn write_principal(bytes: &mut Vec<u8>, principal: &Principal) {
+    let slice = principal.as_slice();
+    bytes.push(slice.len() as u8);
+    bytes.extend_from_slice(slice);
+    bytes.resize(bytes.len() + MAX_PRINCIPAL_SIZE - slice.len(), 0);
+}
+
+/// Reads a principal written by `write_principal` at an offset.
+fn read_principal(bytes: &[u8], offset: usize) -> Principal {
+    let len = bytes[offset] as usize;
+    Principal::from_slice(&bytes[offset + 1..offset + 1 + len])
+}
+
+impl PrincipalPair {
+    /// Creates the smallest key whose first principal is `first`, to start a prefix scan.
+    pub fn lower_bound(first: Principal) -> Self {
+        Self(first, Principal::management_canister())
+    }
+}
+
+impl Storable for PrincipalPair {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(2 * (MAX_PRINCIPAL_SIZE + 1));
+        write_principal(&mut bytes, &self.0);
+        write_principal(&mut bytes, &self.1);
+
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Self(read_principal(&bytes, 0), read_principal(&bytes, MAX_PRINCIPAL_SIZE + 1))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 2 * (MAX_PRINCIPAL_SIZE as u32 + 1),
+        is_fixed_size: true,
+    };
+}
+
+impl AccountPair {
+    /// Creates the smallest key whose first principal is `first`, to start a prefix scan.
+    pub fn lower_bound(first: Principal) -> Self {
+        Self(first, Principal::management_canister(), [0u8; 32])
+    }
+
+    /// Creates the smallest key of a pair of principals, to scan the subaccounts recorded for it.
+    pub fn pair_lower_bound(first: Principal, second: Principal) -> Self {
+        Self(first, second, [0u8; 32])
+    }
+}
+
+impl Storable for AccountPair {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(2 * (MAX_PRINCIPAL_SIZE + 1) + 32);
+        write_principal(&mut bytes, &self.0);
+        write_principal(&mut bytes, &self.1);
+        bytes.extend_from_slice(&self.2);
+
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        let offset = 2 * (MAX_PRINCIPAL_SIZE + 1);
+        let mut subaccount = [0u8; 32];
+        subaccount.copy_from_slice(&bytes[offset..offset + 32]);
+
+        Self(read_principal(&bytes, 0), read_principal(&bytes, MAX_PRINCIPAL_SIZE + 1), subaccount)
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 2 * (MAX_PRINCIPAL_SIZE as u32 + 1) + 32,
+        is_fixed_size: true,
+    };
+}
+
+impl StorableAccount {
+    /// Creates the smallest key of an owner, to start a scan of its accounts.
+    pub fn lower_bound(owner: Principal) -> Self {
+        Self(Account { owner, subaccount: None })
+    }
+
+    /// Creates the largest key of an owner, to end a scan of its accounts.
+    pub fn upper_bound(owner: Principal) -> Self {
+        Self(Account { owner, subaccount: Some([u8::MAX; 32]) })
+    }
+}
+
+impl Storable for StorableAccount {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(MAX_PRINCIPAL_SIZE + 1 + 32);
+        write_principal(&mut bytes, &self.0.owner);
+        bytes.extend_from_slice(self.0.effective_subaccount());
+
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        let offset = MAX_PRINCIPAL_SIZE + 1;
+        let mut subaccount = [0u8; 32];
+        subaccount.copy_from_slice(&bytes[offset..offset + 32]);
+
+        Self(Account {
+            owner: read_principal(&bytes, 0),
+            subaccount: Some(subaccount).filter(|s| s != &[0u8; 32])
+        })
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_PRINCIPAL_SIZE as u32 + 1 + 32,
+        is_fixed_size: true,
+    };
+}
+
+impl Default for ReputationModuleMetadata {
     /// Creates a default instance of `ReputationModuleMetadata`.
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             achievement_collection: Principal::anonymous(),
             issuer_description: String::default(),
@@ -103,14 +385,36 @@ impl ReputationModuleMetadata {
     }
 }
 
-impl_storable!(ReputationModuleMetadata, Standard);
+impl DecayModel {
+    /// Applies the decay to a score that was granted `age_seconds` ago.
+    pub fn apply(&self, score: u64, age_seconds: u64) -> f64 {
+        let score = score as f64;
+
+        match self {
+            DecayModel::None => score,
+            DecayModel::HalfLife { half_life_seconds: 0 } | DecayModel::Linear { lifetime_seconds: 0 } => 0.0,
+            DecayModel::HalfLife { half_life_seconds } => {
+                score * 0.5_f64.powf(age_seconds as f64 / *half_life_seconds as f64)
+            },
+            DecayModel::Linear { lifetime_seconds } => {
+                score * (1.0 - age_seconds as f64 / *lifetime_seconds as f64).max(0.0)
+            }
+        }
+    }
+}
+
+impl_storable!(ReputationModuleMetadata, Standard, DecayModel, EcdsaKeyIds);
+
+impl_storable!(Bound::Unbounded; IssuedCredential, MetricCounters, Issuance, Revocation, Reputation, HeldAchievements, AchievementMetadata, MigrationReport);
+
+impl_storable!(Bound::Bounded { max_size: MAX_PAUSE_EVENT_SIZE, is_fixed_size: false }; PauseEvent);
 
 impl Storable for CanisterPermission {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         self.0.to_bytes()
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
         Self(bool::from_bytes(bytes))
     }
 
@@ -118,4 +422,242 @@ impl Storable for CanisterPermission {
         max_size: MAX_KEY_SIZE,
         is_fixed_size: false,
     };
-}
\ No newline at end of file
+}
+
+impl Storable for AchievementWeight {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        self.0.to_bytes()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Self(u64::from_bytes(bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_VALUE_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+impl Storable for LeaderboardKey {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = self.score.0.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.principal.as_slice());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        let mut score = [0u8; 8];
+        score.copy_from_slice(&bytes[..8]);
+
+        Self {
+            score: Reverse(u64::from_be_bytes(score)),
+            principal: Principal::from_slice(&bytes[8..])
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_KEY_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+impl Storable for Holding {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        let mut bytes = vec![u8::from(self.token_id.is_some()) | u8::from(self.issued_at.is_some()) << 1];
+        bytes.extend_from_slice(&self.token_id.unwrap_or_default().to_be_bytes());
+        bytes.extend_from_slice(&self.issued_at.unwrap_or_default().to_be_bytes());
+
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        let mut token_id = [0u8; 16];
+        let mut issued_at = [0u8; 8];
+        token_id.copy_from_slice(&bytes[1..17]);
+        issued_at.copy_from_slice(&bytes[17..25]);
+
+        Self {
+            token_id: (bytes[0] & 1 != 0).then(|| u128::from_be_bytes(token_id)),
+            issued_at: (bytes[0] & 2 != 0).then(|| u64::from_be_bytes(issued_at))
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 25,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for ModuleHash {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<'_, [u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_VALUE_SIZE,
+        is_fixed_size: false,
+    };
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_principal_sum;
+
+    const IDENTITY_WALLET: &str = "rrkah-fqaaa-aaaaa-aaaaq-cai";
+    const ACHIEVEMENT: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+
+    #[test]
+    fn decay_none_keeps_the_score() {
+        assert_eq!(DecayModel::None.apply(10, 0), 10.0);
+        assert_eq!(DecayModel::None.apply(10, u64::MAX), 10.0);
+    }
+
+    #[test]
+    fn decay_half_life_halves_the_score_every_half_life() {
+        let decay = DecayModel::HalfLife { half_life_seconds: 100 };
+
+        assert_eq!(decay.apply(8, 0), 8.0);
+        assert_eq!(decay.apply(8, 100), 4.0);
+        assert_eq!(decay.apply(8, 300), 1.0);
+        assert!((decay.apply(8, 50) - 8.0 / 2_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn decay_linear_reaches_zero_at_the_end_of_the_lifetime() {
+        let decay = DecayModel::Linear { lifetime_seconds: 100 };
+
+        assert_eq!(decay.apply(10, 0), 10.0);
+        assert_eq!(decay.apply(10, 25), 7.5);
+        assert_eq!(decay.apply(10, 100), 0.0);
+        assert_eq!(decay.apply(10, 1_000), 0.0);
+    }
+
+    #[test]
+    fn decay_with_zero_duration_drops_the_score() {
+        assert_eq!(DecayModel::HalfLife { half_life_seconds: 0 }.apply(10, 0), 0.0);
+        assert_eq!(DecayModel::Linear { lifetime_seconds: 0 }.apply(10, 0), 0.0);
+    }
+
+    #[test]
+    fn principal_pair_survives_a_storable_round_trip() {
+        let pairs = [
+            PrincipalPair(Principal::from_text(IDENTITY_WALLET).unwrap(), Principal::from_text(ACHIEVEMENT).unwrap()),
+            PrincipalPair(Principal::anonymous(), Principal::management_canister()),
+            PrincipalPair(Principal::from_slice(&[7; MAX_PRINCIPAL_SIZE]), Principal::from_slice(&[1])),
+        ];
+
+        for pair in pairs {
+            let bytes = pair.to_bytes();
+
+            assert_eq!(bytes.len(), 2 * (MAX_PRINCIPAL_SIZE + 1));
+            assert!(PrincipalPair::from_bytes(bytes) == pair);
+        }
+    }
+
+    #[test]
+    fn principal_pair_lower_bound_starts_the_prefix_of_its_principal() {
+        let first = Principal::from_text(IDENTITY_WALLET).unwrap();
+        let mut keys = [
+            PrincipalPair(Principal::from_slice(&[9]), Principal::anonymous()),
+            PrincipalPair(first, Principal::from_text(ACHIEVEMENT).unwrap()),
+            PrincipalPair(first, Principal::anonymous()),
+            PrincipalPair::lower_bound(first),
+        ];
+        keys.sort();
+
+        let position = keys.iter().position(|key| *key == PrincipalPair::lower_bound(first)).unwrap();
+        assert!(keys[position + 1..].iter().take(2).all(|key| key.0 == first));
+        assert!(keys[..position].iter().all(|key| key.0 != first));
+    }
+
+    #[test]
+    fn account_pair_survives_a_storable_round_trip() {
+        let pair = AccountPair(Principal::from_text(ACHIEVEMENT).unwrap(), Principal::from_text(IDENTITY_WALLET).unwrap(), [7u8; 32]);
+        let bytes = pair.to_bytes();
+
+        assert_eq!(bytes.len(), 2 * (MAX_PRINCIPAL_SIZE + 1) + 32);
+        assert!(AccountPair::from_bytes(bytes) == pair);
+    }
+
+    #[test]
+    fn account_pairs_of_a_pair_of_principals_follow_its_lower_bound() {
+        let owner = Principal::from_text(IDENTITY_WALLET).unwrap();
+        let achievement = Principal::from_text(ACHIEVEMENT).unwrap();
+        let mut keys = [
+            AccountPair(owner, Principal::from_slice(&[u8::MAX; 29]), [0u8; 32]),
+            AccountPair(owner, achievement, [1u8; 32]),
+            AccountPair::pair_lower_bound(owner, achievement),
+            AccountPair::lower_bound(owner),
+        ];
+        keys.sort();
+
+        assert!(keys[0] == AccountPair::lower_bound(owner));
+        assert!(keys[1] == AccountPair::pair_lower_bound(owner, achievement));
+        assert!(keys[2] == AccountPair(owner, achievement, [1u8; 32]));
+    }
+
+    #[test]
+    fn storable_account_normalizes_the_default_subaccount() {
+        let owner = Principal::from_text(IDENTITY_WALLET).unwrap();
+        let default = StorableAccount(Account { owner, subaccount: Some([0u8; 32]) });
+        let subaccount = StorableAccount(Account { owner, subaccount: Some([3u8; 32]) });
+
+        assert!(StorableAccount::from_bytes(default.to_bytes()).0.subaccount.is_none());
+        assert!(StorableAccount::from_bytes(subaccount.to_bytes()) == subaccount);
+        assert!(StorableAccount::lower_bound(owner) <= default && default < subaccount);
+        assert!(subaccount <= StorableAccount::upper_bound(owner));
+    }
+
+    #[test]
+    fn holding_survives_a_storable_round_trip() {
+        for holding in [
+            Holding { token_id: Some(u128::MAX), issued_at: Some(42) },
+            Holding { token_id: None, issued_at: None },
+        ] {
+            let bytes = holding.to_bytes();
+
+            assert_eq!(bytes.len(), 25);
+            assert_eq!(Holding::from_bytes(bytes), holding);
+        }
+    }
+
+    #[test]
+    fn legacy_principal_sum_parses_into_a_principal_pair() {
+        let principal_sum = PrincipalSum(format!("{}{}", IDENTITY_WALLET, ACHIEVEMENT));
+        let (identity_wallet, achievement) = parse_principal_sum(&principal_sum.0).unwrap();
+        let pair = PrincipalPair(identity_wallet, achievement);
+
+        assert_eq!(identity_wallet.to_text(), IDENTITY_WALLET);
+        assert_eq!(achievement.to_text(), ACHIEVEMENT);
+        assert!(PrincipalPair::from_bytes(pair.to_bytes()) == pair);
+        assert!(PrincipalSum::from_bytes(principal_sum.to_bytes()) == principal_sum);
+    }
+
+    #[test]
+    fn invalid_legacy_principal_sum_is_not_parsed() {
+        assert!(parse_principal_sum("").is_none());
+        assert!(parse_principal_sum(IDENTITY_WALLET).is_none());
+        assert!(parse_principal_sum(&format!("{}-{}", IDENTITY_WALLET, ACHIEVEMENT)).is_none());
+    }
+
+    #[test]
+    fn leaderboard_keys_order_by_descending_score_then_principal() {
+        let key = |score: u64, principal: u8| LeaderboardKey { score: Reverse(score), principal: Principal::from_slice(&[principal]) };
+        let mut keys = vec![key(5, 1), key(10, 2), key(10, 1), key(0, 3), key(u64::MAX, 4)];
+        keys.sort();
+
+        assert!(keys == vec![key(u64::MAX, 4), key(10, 1), key(10, 2), key(5, 1), key(0, 3)]);
+    }
+
+    #[test]
+    fn leaderboard_key_survives_a_storable_round_trip() {
+        let key = LeaderboardKey { score: Reverse(42), principal: Principal::from_text("2vxsx-fae").unwrap() };
+
+        assert!(LeaderboardKey::from_bytes(key.to_bytes()) == key);
+    }
+}
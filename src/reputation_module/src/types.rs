@@ -1,11 +1,250 @@
 //! This module defines the types used in the reputation module.
 
-use candid::CandidType;
+use candid::{CandidType, Principal};
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
 use serde::Deserialize;
 
+use crate::errors::ReputationError;
+use crate::storable::{
+    ReputationModuleMetadata, Standard, DecayModel, Issuance, Reputation, EcdsaKeyIds, PauseEvent, MetricCounters,
+    IssuedCredential, Revocation
+};
+
 /// Metadata for an achievement.
 #[derive(CandidType, Deserialize, Clone)]
 pub struct AchievementMetadata {
     pub achievement_name: String,
     pub achievement_description: String
 }
+
+/// The decayed score a principal holds from a single achievement.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct DecayedReputationEntry {
+    pub achievement: Principal,
+    pub score: f64,
+    pub issued_at: u64
+}
+
+/// The reputation of a principal with the decay of every achievement applied.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct DecayedReputation {
+    pub total: f64,
+    pub breakdown: Vec<DecayedReputationEntry>
+}
+
+/// A principal and its position in the leaderboard.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct LeaderboardEntry {
+    pub rank: u64,
+    pub principal: Principal,
+    pub score: u64
+}
+
+/// A page of the leaderboard.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct LeaderboardPage {
+    pub entries: Vec<LeaderboardEntry>,
+    pub next_cursor: Option<LeaderboardEntry>
+}
+
+/// The maximum number of entries returned by a single page of a list query.
+pub const MAX_PAGE: u64 = 1000;
+
+/// Pagination parameters for list queries.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Paging {
+    pub offset: u64,
+    pub limit: u64
+}
+
+impl Paging {
+    /// Retrieves the number of entries to skip and the number of entries to return.
+    ///
+    /// # Returns
+    ///
+    /// * `(usize, usize)` - The offset, saturated to the address space, and the limit, capped at `MAX_PAGE`.
+    pub fn bounds(&self) -> (usize, usize) {
+        (usize::try_from(self.offset).unwrap_or(usize::MAX), self.limit.min(MAX_PAGE) as usize)
+    }
+}
+
+/// An achievement held by a principal together with the cached metadata of its achievement canister.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct AchievementOf {
+    pub achievement: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub token_id: u128,
+    pub issued_at: u64,
+    pub metadata: Option<AchievementMetadata>
+}
+
+/// A principal holding an achievement.
+///
+/// The token id and issuance time are unknown for achievements issued before tokens were recorded.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Holder {
+    pub principal: Principal,
+    pub subaccount: Option<Subaccount>,
+    pub token_id: Option<u128>,
+    pub issued_at: Option<u64>
+}
+
+/// A page of the holders of an achievement.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HoldersPage {
+    pub holders: Vec<Holder>,
+    pub next_cursor: Option<Account>
+}
+
+/// The outcome of issuing a single achievement of a batch.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BatchIssueResult {
+    pub achievement: Principal,
+    pub result: Result<u128, ReputationError>
+}
+
+/// Filters applied when listing the achievement catalog.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct AchievementFilters {
+    pub permission: Option<bool>,
+    pub min_holders: Option<u64>,
+    pub name_contains: Option<String>
+}
+
+/// An achievement canister of the catalog.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct AchievementListing {
+    pub canister: Principal,
+    pub permission: bool,
+    pub metadata: Option<AchievementMetadata>,
+    pub holder_count: u64
+}
+
+/// Arguments configuring the reputation module at install time.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ReputationModuleInitArgs {
+    pub achievement_collection: Principal,
+    pub issuer_name: String,
+    pub issuer_description: String,
+    pub allowed_canisters: Vec<Principal>,
+    pub admins: Vec<Principal>,
+    pub ecdsa_key: Option<EcdsaKeyIds>
+}
+
+/// A stable map of the reputation module included in a state backup.
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum BackupSection {
+    Metadata,
+    Admins,
+    Standards,
+    Permissions,
+    ModuleHashes,
+    AchievementMetadata,
+    Weights,
+    Decays,
+    Issuances,
+    RevokedTokens,
+    IssuedStatuses,
+    Reputations,
+    Credentials,
+    ReleasedTokenIds,
+    Paused,
+    PausedAchievements,
+    PauseLog,
+    LowCyclesThreshold,
+    MetricCounters,
+    EcdsaKey
+}
+
+/// The records of a backup section.
+#[derive(CandidType, Deserialize, Clone)]
+pub enum BackupRecords {
+    Metadata(Vec<ReputationModuleMetadata>),
+    Admins(Vec<Principal>),
+    Standards(Vec<Standard>),
+    Permissions(Vec<(Principal, bool)>),
+    ModuleHashes(Vec<(Principal, Vec<u8>)>),
+    AchievementMetadata(Vec<(Principal, AchievementMetadata)>),
+    Weights(Vec<(Principal, u64)>),
+    Decays(Vec<(Principal, DecayModel)>),
+    Issuances(Vec<(u128, Issuance)>),
+    RevokedTokens(Vec<(u128, Revocation)>),
+    IssuedStatuses(Vec<(Account, Principal)>),
+    Reputations(Vec<(Principal, Reputation)>),
+    Credentials(Vec<(Principal, Principal, IssuedCredential)>),
+    ReleasedTokenIds(Vec<u128>),
+    Paused(Vec<bool>),
+    PausedAchievements(Vec<Principal>),
+    PauseLog(Vec<PauseEvent>),
+    LowCyclesThreshold(Vec<u128>),
+    MetricCounters(Vec<MetricCounters>),
+    EcdsaKey(Vec<EcdsaKeyIds>)
+}
+
+/// A page of the records of a backup section.
+///
+/// The checksum is the SHA-256 of the candid encoding of the section, offset and records of the chunk.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BackupChunk {
+    pub version: u32,
+    pub section: BackupSection,
+    pub offset: u64,
+    pub records: BackupRecords,
+    pub next_offset: Option<u64>,
+    pub checksum: String
+}
+
+/// The number of records of a backup section.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BackupSectionSize {
+    pub section: BackupSection,
+    pub count: u64
+}
+
+/// Describes a full state backup of the reputation module.
+///
+/// The checksum is the checksum of the whole backup, as returned by `finalizeStateImport`.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub schema_version: u32,
+    pub sections: Vec<BackupSectionSize>,
+    pub checksum: String
+}
+
+/// The number of entries of a stable map.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct MapSize {
+    pub name: String,
+    pub len: u64
+}
+
+/// The resource usage of the reputation module.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CanisterStatus {
+    pub cycles_balance: u128,
+    pub low_cycles_threshold: u128,
+    pub stable_memory_bytes: u64,
+    pub map_sizes: Vec<MapSize>,
+    pub total_issued: u128,
+    pub unapproved_canisters: Vec<Principal>,
+    pub credential_signatures: u64,
+    pub credential_failures: u64
+}
+
+/// An HTTP request received through the HTTP gateway.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+/// An HTTP response returned through the HTTP gateway.
+#[derive(CandidType, Deserialize, Clone)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
@@ -1,24 +1,116 @@
 //! This module provides utility functions for the reputation module.
 
 use candid::Principal;
+use icrc_ledger_types::icrc1::account::Subaccount;
 
-/// Builds a string representation of a principal sum.
+/// Parses a legacy principal sum back into its principals.
 ///
-/// This function concatenates the string representations of an identity wallet and an achievement principal.
+/// A principal sum is the concatenation of the textual representations of an identity wallet
+/// and an achievement principal. Since the textual representation carries a checksum, only the
+/// correct split point yields two valid principals.
 ///
 /// # Arguments
 ///
-/// * `identity_wallet` - The principal of the identity wallet.
-/// * `achievement` - The principal of the achievement canister.
+/// * `principal_sum` - The concatenated string representation of the principal sum.
 ///
 /// # Returns
 ///
-/// * `String` - The concatenated string representation of the principal sum.
-pub fn build_principal_sum(identity_wallet: Principal, achievement: Principal) -> String {
-    let mut principal_sum = String::from("");
-    principal_sum.push_str(&identity_wallet.to_string());
-    principal_sum.push_str(&achievement.to_string());
+/// * `Option<(Principal, Principal)>` - The identity wallet and the achievement principal, if the sum is valid.
+pub fn parse_principal_sum(principal_sum: &str) -> Option<(Principal, Principal)> {
+    (1..principal_sum.len())
+        .filter(|&i| principal_sum.is_char_boundary(i))
+        .find_map(|i| {
+            let identity_wallet = Principal::from_text(&principal_sum[..i]).ok()?;
+            let achievement = Principal::from_text(&principal_sum[i..]).ok()?;
 
-    principal_sum
+            Some((identity_wallet, achievement))
+        })
 }
 
+/// Normalizes a subaccount so that the default subaccount is always represented by `None`.
+///
+/// # Arguments
+///
+/// * `subaccount` - The subaccount to normalize.
+///
+/// # Returns
+///
+/// * `Option<Subaccount>` - `None` for the default subaccount, the subaccount otherwise.
+pub fn normalize_subaccount(subaccount: Option<Subaccount>) -> Option<Subaccount> {
+    subaccount.filter(|s| s != &[0u8; 32])
+}
+
+/// Formats a timestamp as a UTC calendar date.
+///
+/// # Arguments
+///
+/// * `timestamp` - The number of nanoseconds since the Unix epoch.
+///
+/// # Returns
+///
+/// * `String` - The date in the `YYYY-MM-DD` format.
+pub fn format_date(timestamp: u64) -> String {
+    // Civil date from the number of days since the epoch, counted in 400-year eras starting in March.
+    let days = (timestamp / 1_000_000_000 / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a timestamp as a UTC date and time in the RFC 3339 format.
+///
+/// # Arguments
+///
+/// * `timestamp` - The number of nanoseconds since the Unix epoch.
+///
+/// # Returns
+///
+/// * `String` - The date and time in the `YYYY-MM-DDTHH:MM:SSZ` format.
+pub fn format_datetime(timestamp: u64) -> String {
+    let seconds_of_day = timestamp / 1_000_000_000 % 86_400;
+
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        format_date(timestamp), seconds_of_day / 3_600, seconds_of_day / 60 % 60, seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn format_date_starts_at_the_epoch() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_datetime(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_datetime(NANOS_PER_SECOND - 1), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn format_date_handles_leap_years() {
+        assert_eq!(format_date(951_782_400 * NANOS_PER_SECOND), "2000-02-29");
+        assert_eq!(format_date(1_709_164_800 * NANOS_PER_SECOND), "2024-02-29");
+        assert_eq!(format_date(4_107_456_000 * NANOS_PER_SECOND), "2100-02-28");
+        assert_eq!(format_date(4_107_542_400 * NANOS_PER_SECOND), "2100-03-01");
+    }
+
+    #[test]
+    fn format_datetime_rolls_over_at_the_end_of_the_year() {
+        assert_eq!(format_datetime(1_704_067_199 * NANOS_PER_SECOND), "2023-12-31T23:59:59Z");
+        assert_eq!(format_datetime(1_704_067_200 * NANOS_PER_SECOND), "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn format_datetime_covers_the_largest_timestamp() {
+        assert_eq!(format_datetime(u64::MAX), "2554-07-21T23:34:33Z");
+    }
+}
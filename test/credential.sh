@@ -0,0 +1,34 @@
+#!/bin/bash
+# Issues a verifiable credential for the achievement held by pa_identity_wallet and checks the
+# issuer DID document served through the local HTTP gateway.
+#
+# Expects a running replica with the reputation module deployed with
+# `ecdsa_key = opt variant { TestKeyLocalDevelopment }` and an achievement issued as in issue_achievement.sh.
+
+set -e
+
+GATEWAY=http://localhost:$(dfx info webserver-port)
+REPUTATION_MODULE=$(dfx canister id reputation_module)
+ACHIEVEMENT=$(dfx canister id achievement)
+
+expect() {
+  if [[ "$2" != *"$3"* ]]; then
+    echo "FAIL: $1: expected $3, got $2"
+    exit 1
+  fi
+  echo "ok: $1"
+}
+
+CREDENTIAL=$(dfx --identity pa_identity_wallet canister call reputation_module issueCredential "(principal \"$ACHIEVEMENT\")")
+JWT=$(echo "$CREDENTIAL" | sed -nE 's/.*Ok = "([^"]+)".*/\1/p')
+PAYLOAD=$(echo "$JWT" | cut -d. -f2 | tr '_-' '/+')
+while (( ${#PAYLOAD} % 4 )); do PAYLOAD="$PAYLOAD="; done
+
+expect "credential is a signed JWT" "$(echo "$JWT" | tr -cd . | wc -c)" "2"
+expect "credential names the achievement" "$(echo "$PAYLOAD" | base64 -d)" "\"id\":\"$ACHIEVEMENT\""
+expect "credential is reused" \
+  "$(dfx --identity pa_identity_wallet canister call reputation_module issueCredential "(principal \"$ACHIEVEMENT\")")" "$JWT"
+expect "issuer DID document" \
+  "$(curl -s "$GATEWAY/.well-known/did.json?canisterId=$REPUTATION_MODULE")" "EcdsaSecp256k1VerificationKey2019"
+expect "credential refused without the achievement" \
+  "$(dfx --identity pa_local_wallet canister call reputation_module issueCredential "(principal \"$ACHIEVEMENT\")")" "NotIssued"
@@ -0,0 +1,52 @@
+#!/bin/bash
+# Fetches the JSON documents served by both canisters through the local HTTP gateway, which rejects
+# responses whose certificate does not match their body.
+#
+# Expects a running replica with both canisters deployed and an achievement issued to the
+# pa_identity_wallet identity, as in issue_achievement.sh.
+
+set -e
+
+GATEWAY=http://localhost:$(dfx info webserver-port)
+REPUTATION_MODULE=$(dfx canister id reputation_module)
+ACHIEVEMENT=$(dfx canister id achievement)
+IDENTITY_WALLET=$(dfx --identity pa_identity_wallet identity get-principal)
+
+expect() {
+  if [[ "$2" != *"$3"* ]]; then
+    echo "FAIL: $1: expected $3, got $2"
+    exit 1
+  fi
+  echo "ok: $1"
+}
+
+fetch() {
+  curl -s -w ' %{http_code}' "$GATEWAY$2?canisterId=$1"
+}
+
+expect "reputation module metadata" \
+  "$(fetch "$REPUTATION_MODULE" /metadata.json)" '"issuer_name":"test"'
+expect "holders of the achievement" \
+  "$(fetch "$REPUTATION_MODULE" "/holders/$ACHIEVEMENT.json")" "\"principal\":\"$IDENTITY_WALLET\""
+expect "reputation of the identity wallet" \
+  "$(fetch "$REPUTATION_MODULE" "/reputation/$IDENTITY_WALLET.json")" "\"achievement\":\"$ACHIEVEMENT\""
+TOKEN_ID=$(curl -s "$GATEWAY/reputation/$IDENTITY_WALLET.json?canisterId=$REPUTATION_MODULE" | sed -E 's/.*"token_id":([0-9]+).*/\1/')
+
+expect "badge of the issued token" \
+  "$(fetch "$REPUTATION_MODULE" "/badges/$TOKEN_ID.svg")" "Issued by test"
+expect "unknown principal" \
+  "$(fetch "$REPUTATION_MODULE" "/reputation/aaaaa-aa.json")" " 404"
+expect "achievement metadata" \
+  "$(fetch "$ACHIEVEMENT" /metadata.json)" '"achievement_name":"Test achievement"'
+
+expect "reputation module metrics" \
+  "$(curl -s "http://$REPUTATION_MODULE.raw.localhost:$(dfx info webserver-port)/metrics")" "reputation_mints_total"
+expect "achievement metrics" \
+  "$(curl -s "http://$ACHIEVEMENT.raw.localhost:$(dfx info webserver-port)/metrics")" "achievement_claims_total"
+
+dfx canister call achievement updateAchivementMetadata '(record {achievement_name="Renamed achievement"; achievement_description="Description of test achievement"})'
+
+expect "achievement metadata certified after update" \
+  "$(fetch "$ACHIEVEMENT" /metadata.json)" '"achievement_name":"Renamed achievement"'
+
+dfx canister call achievement updateAchivementMetadata '(record {achievement_name="Test achievement"; achievement_description="Description of test achievement"})'
@@ -0,0 +1,65 @@
+#!/bin/bash
+# Installs both canisters from an unversioned commit (the first commit by default), issues an
+# achievement with the old layout, upgrades to the working tree and checks that the migrations
+# brought the state to the current schema version without losing it.
+#
+# Expects a running replica, the icrc7 collection and the pa_identity_wallet identity set up as in
+# icrc-7_configurate.sh and the README.
+
+set -e
+
+OLD_REF=${1:-$(git rev-list --max-parents=0 HEAD)}
+OLD_TREE=$(mktemp -d)
+TARGET=wasm32-unknown-unknown
+
+expect() {
+  if [[ "$2" != *"$3"* ]]; then
+    echo "FAIL: $1: expected $3, got $2"
+    exit 1
+  fi
+  echo "ok: $1"
+}
+
+git worktree add --detach "$OLD_TREE" "$OLD_REF"
+trap 'git worktree remove --force "$OLD_TREE"' EXIT
+
+(cd "$OLD_TREE" && cargo build --target $TARGET --release -p reputation_module -p achievement)
+
+dfx canister create reputation_module
+dfx canister create achievement
+dfx canister install reputation_module --mode reinstall --yes --wasm "$OLD_TREE/target/$TARGET/release/reputation_module.wasm"
+dfx canister install achievement --mode reinstall --yes --wasm "$OLD_TREE/target/$TARGET/release/achievement.wasm"
+
+ACHIEVEMENT=$(dfx canister id achievement)
+IDENTITY_WALLET=$(dfx --identity pa_identity_wallet identity get-principal)
+
+dfx canister call reputation_module updateReputationModuleMetadata "record {achievement_collection=principal \"$(dfx canister id icrc7)\"; issuer_name=\"test\"; issuer_description=\"test\"; total_issued=0}"
+dfx canister call achievement updateAchivementMetadata '(record {achievement_name="Test achievement"; achievement_description="Description of test achievement"})'
+dfx canister call reputation_module changePermissionCanister "(principal \"$ACHIEVEMENT\", true)"
+dfx --identity pa_identity_wallet canister call achievement receiveAchievementFromIdentityWallet "(vec {})"
+dfx --identity pa_identity_wallet canister call reputation_module issueAchievementToIdentityWallet "(principal \"$ACHIEVEMENT\")"
+
+dfx build reputation_module
+dfx build achievement
+dfx canister install reputation_module --mode upgrade --yes --argument '(null)'
+dfx canister install achievement --mode upgrade --yes --argument '(null)'
+
+expect "reputation module schema version" \
+  "$(dfx canister call reputation_module getSchemaVersion)" "(6 : nat32)"
+expect "achievement schema version" \
+  "$(dfx canister call achievement getSchemaVersion)" "(1 : nat32)"
+expect "issued status migrated from principal sum" \
+  "$(dfx canister call reputation_module getPrincipalAchievementSumStatus "(principal \"$IDENTITY_WALLET\", principal \"$ACHIEVEMENT\")")" "(true)"
+expect "holder count rebuilt" \
+  "$(dfx canister call reputation_module getHolderCount "(principal \"$ACHIEVEMENT\")")" "(1 : nat64)"
+expect "achievement registered in catalog" \
+  "$(dfx canister call reputation_module listAchievements '(record {offset=0; limit=10}, record {})')" "$ACHIEVEMENT"
+expect "achievement metadata preserved" \
+  "$(dfx canister call achievement getAchievementMetadata)" "Test achievement"
+expect "achievement status preserved" \
+  "$(dfx canister call achievement getPrincipalToAchievementStatusValue "(principal \"$IDENTITY_WALLET\")")" "Ok = 1"
+
+dfx canister install reputation_module --mode upgrade --yes --argument '(null)'
+
+expect "state survives a second upgrade" \
+  "$(dfx canister call reputation_module getHolderCount "(principal \"$ACHIEVEMENT\")")" "(1 : nat64)"